@@ -0,0 +1,38 @@
+#!/usr/bin/env sh
+# A stand-in for a reference engine, used to test automated perft divides. It
+# only knows the position "k7/8/8/8/8/8/8/K7 w - - 0 1", and forgets that the
+# black king can move to b7 after 1. Kb2.
+
+fen="k7/8/8/8/8/8/8/K7 w - - 0 1"
+moves=""
+
+while read -r line; do
+    case "$line" in
+        "position fen $fen")
+            moves=""
+            ;;
+        "position fen $fen moves "*)
+            moves="${line#"position fen $fen moves "}"
+            ;;
+        "go perft 2")
+            echo "a1a2: 3"
+            echo "a1b1: 3"
+            echo "a1b2: 2"
+            echo
+            echo "Nodes searched: 8"
+            ;;
+        "go perft 1")
+            if [ "$moves" = "a1b2" ]; then
+                echo "a8a7: 1"
+                echo "a8b8: 1"
+                echo
+                echo "Nodes searched: 2"
+            else
+                echo "Nodes searched: 0"
+            fi
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
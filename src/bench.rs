@@ -0,0 +1,80 @@
+//! A fixed set of searches, whose total node count acts as a signature of the
+//! search and evaluation. Changes which aren't meant to alter the behaviour of
+//! the engine shouldn't change the signature.
+
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    position::Position,
+    search::{SearchBuilder, TTable, DEFAULT_HASH_MB},
+};
+
+pub const BENCH_DEPTH: usize = 6;
+
+const BENCH_POSITIONS: [&str; 12] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 9",
+    "2r3k1/pp3ppp/4p3/3p4/3P4/2P2N2/P4PPP/4R1K1 w - - 0 25",
+    "8/5pk1/6p1/7p/7P/6P1/5PK1/3r4 b - - 0 40",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+pub struct BenchResults {
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl BenchResults {
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(1e-9)) as u64
+    }
+}
+
+/// Search every bench position to `depth`, each with an empty transposition
+/// table, counting the nodes of every iteration.
+pub fn run(depth: usize) -> BenchResults {
+    let nodes = Rc::new(Cell::new(0u64));
+    let now = Instant::now();
+    let mut ttable = TTable::with_size_mb(DEFAULT_HASH_MB);
+
+    for fen in BENCH_POSITIONS {
+        let pos = Position::from_fen(fen).unwrap();
+        let counter = nodes.clone();
+
+        ttable.clear();
+
+        let mut search = SearchBuilder::new(pos)
+            .with_depth(depth)
+            .with_ttable(ttable)
+            .with_report_callback(move |results| counter.set(counter.get() + results.nodes as u64))
+            .build();
+        search.go();
+        ttable = search.into_ttable();
+    }
+
+    BenchResults {
+        nodes: nodes.get(),
+        time: now.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+
+    #[test]
+    fn reproducible() {
+        assert_eq!(run(2).nodes, run(2).nodes);
+    }
+}
@@ -5,18 +5,18 @@ use rmace::position::{
     movegen::rays::{BISHOP_OCC_MASK, ROOK_OCC_MASK},
 };
 
-fn search(v: u64, bbds: &Vec<BitBoard>, popcnt: u32, collisions: &mut Vec<bool>) -> bool {
+fn search(v: u64, bbds: &[BitBoard], popcnt: u32, collisions: &mut [bool]) -> bool {
     let shift = 63 - (popcnt - 1);
     for bb in bbds.iter() {
         let idx = ((u64::from(*bb).overflowing_mul(v).0) >> shift) as usize;
-        if collisions[idx] == false {
+        if !collisions[idx] {
             collisions[idx] = true;
         } else {
             return false;
         }
     }
 
-    return true;
+    true
 }
 
 fn random_fewbits() -> u64 {
@@ -33,16 +33,13 @@ fn search_ray(ray: BitBoard) {
         2u64.pow(ray.popcount())
     );
 
-    let bit_positions = ray
-        .iter_pieces()
-        .map(|x| x.to_idx() as u8)
-        .collect::<Vec<_>>();
+    let bit_positions = ray.iter_pieces().map(|x| x.to_idx()).collect::<Vec<_>>();
 
     let bbds = bit_positions
         .iter()
         .powerset()
         .map(|x| x.iter().fold(0u64, |accum, x| accum | 1 << (**x) as u64))
-        .map(|x| BitBoard::new(x))
+        .map(BitBoard::new)
         .collect::<Vec<_>>();
 
     assert_eq!(bbds.len(), 1 << popcnt);
@@ -64,15 +61,15 @@ fn search_ray(ray: BitBoard) {
 
 fn main() {
     println!("const ROOK_MAGICS: [(usize, u64); 64] = {{");
-    for i in 0..64 {
-        search_ray(ROOK_OCC_MASK[i]);
+    for mask in ROOK_OCC_MASK.iter() {
+        search_ray(*mask);
     }
     println!("}}");
 
     println!("const BISHOP_MAGICS: [u64; 64] = {{");
-    for i in 0..64 {
+    for (i, mask) in BISHOP_OCC_MASK.iter().enumerate() {
         eprintln!("BISHOP_OCC_MASK[{}]", i);
-        search_ray(BISHOP_OCC_MASK[i]);
+        search_ray(*mask);
     }
     println!("}}");
 }
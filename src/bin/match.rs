@@ -0,0 +1,228 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use rmace::{
+    parsers::pgn::parse_pgn,
+    piece::Colour,
+    selfplay::{
+        play_game,
+        player::{InProcessPlayer, UciPlayer},
+        stats::{MatchScore, Sprt, SprtVerdict},
+        Adjudication, Opening, Player, TimeControl,
+    },
+};
+
+#[derive(clap::Parser)]
+/// Play a match between two engines, reporting the Elo difference between
+/// them. Each engine is either `builtin`, rmace's own search in this process,
+/// optionally limited with `builtin:depth=N` or `builtin:movetime=MS`, or the
+/// command line of a UCI engine.
+struct Args {
+    /// The first engine, whose strength relative to the second is measured.
+    engine1: String,
+
+    /// The second engine.
+    engine2: String,
+
+    #[arg(long)]
+    name1: Option<String>,
+
+    #[arg(long)]
+    name2: Option<String>,
+
+    /// The maximum number of games to play. Each opening is played twice,
+    /// with the engines swapping colours.
+    #[arg(short, long, default_value_t = 100)]
+    games: usize,
+
+    /// The time control, as `base+increment` in seconds.
+    #[arg(long, default_value = "10+0.1")]
+    tc: TimeControl,
+
+    /// The PGN file of openings to play, in order.
+    #[arg(long, default_value = "etc/silversuite.pgn")]
+    openings: PathBuf,
+
+    /// Write the games to this PGN file.
+    #[arg(long)]
+    pgnout: Option<PathBuf>,
+
+    /// Stop once a sequential probability ratio test, of whether the first
+    /// engine is `elo0` or `elo1` stronger, reaches a verdict.
+    #[arg(long, requires = "elo1")]
+    elo0: Option<f64>,
+
+    #[arg(long, requires = "elo0")]
+    elo1: Option<f64>,
+
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    #[arg(long, default_value_t = 0.05)]
+    beta: f64,
+
+    /// Adjudicate a loss once an engine's score has stayed below minus this
+    /// many centipawns for 4 moves.
+    #[arg(long, default_value_t = 1000)]
+    resign_score: i32,
+
+    /// Adjudicate a draw after move 40 once both engines' scores have stayed
+    /// within this many centipawns of zero for 8 moves.
+    #[arg(long, default_value_t = 10)]
+    draw_score: i32,
+
+    /// Adjudicate a draw after this many plies.
+    #[arg(long, default_value_t = 400)]
+    max_plies: usize,
+}
+
+fn make_player(spec: &str, name: Option<&str>) -> Result<Box<dyn Player>> {
+    if let Some(limit) = spec.strip_prefix("builtin") {
+        let name = name.unwrap_or(spec);
+
+        return Ok(match limit.strip_prefix(':').map(|x| x.split_once('=')) {
+            None if limit.is_empty() => Box::new(InProcessPlayer::new(name)),
+            Some(Some(("depth", n))) => Box::new(InProcessPlayer::with_depth(name, n.parse()?)),
+            Some(Some(("movetime", ms))) => Box::new(InProcessPlayer::with_move_time(
+                name,
+                Duration::from_millis(ms.parse()?),
+            )),
+            _ => bail!("Invalid engine {spec}"),
+        });
+    }
+
+    let mut words = spec.split_whitespace();
+    let program = words.next().context("Empty engine command")?;
+    let name = name.unwrap_or(program);
+
+    Ok(Box::new(UciPlayer::spawn(
+        name,
+        program,
+        &words.collect::<Vec<_>>(),
+    )?))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let pgn = fs::read_to_string(&args.openings)
+        .with_context(|| format!("Could not read {}", args.openings.display()))?;
+    let openings = parse_pgn(&pgn)?
+        .iter()
+        .map(Opening::try_from)
+        .collect::<Result<Vec<_>>>()?;
+
+    if openings.is_empty() {
+        bail!("No openings in {}", args.openings.display());
+    }
+
+    let mut engine1 = make_player(&args.engine1, args.name1.as_deref())?;
+    let mut engine2 = make_player(&args.engine2, args.name2.as_deref())?;
+    let mut pgnout = args
+        .pgnout
+        .as_ref()
+        .map(|x| File::create(x).with_context(|| format!("Could not create {}", x.display())))
+        .transpose()?;
+
+    let adjudication = Adjudication {
+        resign_score: args.resign_score,
+        draw_score: args.draw_score,
+        max_plies: args.max_plies,
+        ..Default::default()
+    };
+
+    let sprt = args.elo0.zip(args.elo1).map(|(elo0, elo1)| Sprt {
+        elo0,
+        elo1,
+        alpha: args.alpha,
+        beta: args.beta,
+    });
+
+    let mut score = MatchScore::default();
+
+    for n in 0..args.games {
+        let opening = &openings[(n / 2) % openings.len()];
+
+        let (game, colour) = if n % 2 == 0 {
+            let game = play_game(
+                engine1.as_mut(),
+                engine2.as_mut(),
+                opening,
+                args.tc,
+                &adjudication,
+            )?;
+            (game, Colour::White)
+        } else {
+            let game = play_game(
+                engine2.as_mut(),
+                engine1.as_mut(),
+                opening,
+                args.tc,
+                &adjudication,
+            )?;
+            (game, Colour::Black)
+        };
+
+        score.add(game.outcome, colour);
+
+        println!(
+            "Finished game {} ({} vs {}): {} {{{}}}",
+            n + 1,
+            game.white,
+            game.black,
+            game.outcome,
+            game.reason
+        );
+        println!(
+            "Score of {} vs {}: {}",
+            engine1.name(),
+            engine2.name(),
+            score
+        );
+
+        if let Some(ref mut f) = pgnout {
+            f.write_all(game.to_pgn(n + 1).as_bytes())?;
+        }
+
+        if let Some(sprt) = sprt {
+            if sprt.verdict(&score).is_some() {
+                break;
+            }
+        }
+    }
+
+    println!("===========");
+    println!("Score: {}", score);
+    println!(
+        "Elo difference: {:.1} +/- {:.1}",
+        score.elo(),
+        score.elo_error()
+    );
+
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        let verdict = match sprt.verdict(&score) {
+            Some(SprtVerdict::H0) => "H0 accepted",
+            Some(SprtVerdict::H1) => "H1 accepted",
+            None => "inconclusive",
+        };
+
+        println!(
+            "SPRT: llr {:.2} ({:.2}, {:.2}) [{:.1}, {:.1}]: {}",
+            sprt.llr(&score),
+            lower,
+            upper,
+            sprt.elo0,
+            sprt.elo1,
+            verdict
+        );
+    }
+
+    Ok(())
+}
@@ -1,16 +1,16 @@
-use std::{
-    io::{self, BufRead},
-    time::Instant,
-};
+use std::time::Instant;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use nom::{
-    bytes::complete::tag, character::complete::digit1, combinator::map_res, sequence::tuple, Finish,
-};
 use rmace::{
-    parsers::uci_move::{parse_uci_move, UciMove},
-    position::{movegen::MoveGen, Position},
+    parsers::uci_move::UciMove,
+    position::{
+        movegen::perft::{
+            divide::{find_divergence, EngineDivide, StdinDivide},
+            Perft,
+        },
+        Position,
+    },
 };
 
 #[derive(clap::Parser)]
@@ -28,69 +28,69 @@ struct Args {
     /// debugging.
     #[arg(short, long)]
     debug: bool,
-}
 
-fn debug(
-    original_pos: String,
-    mut moves_made: Vec<UciMove>,
-    mut pos: Position,
-    depth: u32,
-) -> Result<()> {
-    print!("position fen \"{}\" moves ", original_pos);
-    moves_made.iter().for_each(|m| print!("{} ", m));
-    println!();
-    println!("Depth: {}", depth);
+    /// A reference engine, and its arguments, to run perft split with
+    /// instead of entering its output by hand, e.g. `--engine stockfish`.
+    /// Implies `--debug`.
+    #[arg(short, long)]
+    engine: Option<String>,
 
-    let other_moves = read_other_perft_output()?;
+    /// The size, in megabytes, of the hash table used to cache the node
+    /// counts of transpositions.
+    #[arg(long)]
+    hash: Option<usize>,
 
-    let perft: Vec<_> = MoveGen::perft(&mut pos, depth)
-        .iter()
-        .map(|(m, x)| (*m, UciMove::from(*m), *x))
-        .collect();
+    /// The number of threads to split the root moves across.
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
 
-    for other_move in other_moves.iter() {
-        if !perft.iter().any(|x| other_move.0 == x.1) {
-            println!("Move {} exists in other engine, but not us.", other_move.0);
-            return Ok(());
-        }
-    }
+    /// Report the number of captures, en passants, castles, promotions,
+    /// checks and checkmates at each depth.
+    #[arg(long)]
+    detailed: bool,
+}
 
-    for our_moves in perft {
-        if let Some(x) = other_moves.iter().find(|x| our_moves.1 == x.0) {
-            if x.1 != our_moves.2 {
-                println!(
-                    "Move {} contains differing perft result. Making move.",
-                    our_moves.1
-                );
-                pos.make_move(our_moves.0).consume();
-                moves_made.push(our_moves.1);
-                return debug(original_pos, moves_made, pos.clone(), depth - 1);
-            }
-        } else {
-            println!(
-                "Move {} exists in our engine, but not the other.",
-                our_moves.1
-            );
-            return Ok(());
+impl Args {
+    fn perft(&self, depth: u32) -> Perft {
+        let perft = Perft::new(depth).with_threads(self.threads);
+
+        match self.hash {
+            Some(mb) => perft.with_hash(mb),
+            None => perft,
         }
     }
+}
 
-    println!("Perft results match.");
+fn detailed(args: &Args, position: &Position) {
+    println!(
+        "{:>5} {:>12} {:>12} {:>6} {:>10} {:>12} {:>10} {:>11}",
+        "Depth", "Nodes", "Captures", "E.p.", "Castles", "Promotions", "Checks", "Checkmates"
+    );
 
-    Ok(())
+    for depth in 1..=args.depth {
+        println!("{:>5} {}", depth, args.perft(depth).stats(position));
+    }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    let mut position = Position::from_fen(args.fen.clone())
+    let position = Position::from_fen(args.fen.clone())
         .context("Could not create position from FEN string")?;
 
+    if args.detailed {
+        detailed(&args, &position);
+        return Ok(());
+    }
+
     let now = Instant::now();
-    let perft = MoveGen::perft(&mut position, args.depth);
+    let perft = args.perft(args.depth).split(&position);
     let time_taken = now.elapsed();
 
-    let perft: Vec<_> = perft.iter().map(|(m, x)| (UciMove::from(*m), x)).collect();
+    let perft: Vec<_> = perft
+        .iter()
+        .map(|(m, x)| (UciMove::from_move(*m, &position), x))
+        .collect();
 
     for (m, n) in perft.iter() {
         println!("{}: {}", m, n);
@@ -106,38 +106,18 @@ fn main() -> Result<()> {
         total_nodes as f32 / time_taken.as_secs_f32()
     );
 
-    if args.debug {
-        debug(args.fen, Vec::new(), position, args.depth)?;
+    if let Some(engine) = args.engine.as_deref() {
+        let mut engine = EngineDivide::from_command(engine)?;
+        println!(
+            "{}",
+            find_divergence(&mut engine, &args.fen, args.depth, args.threads)?
+        );
+    } else if args.debug {
+        println!(
+            "{}",
+            find_divergence(&mut StdinDivide, &args.fen, args.depth, args.threads)?
+        );
     }
 
     Ok(())
 }
-
-fn parse_perft_line(input: &str) -> Result<(UciMove, u32)> {
-    Ok(map_res(
-        tuple((parse_uci_move, tag(": "), digit1)),
-        |(m, _, n)| -> Result<(UciMove, u32)> { Ok((m, n.parse()?)) },
-    )(input)
-    .map_err(|e| e.to_owned())
-    .finish()
-    .map(|x| x.1)?)
-}
-
-fn read_other_perft_output() -> Result<Vec<(UciMove, u32)>> {
-    println!("Enter the perft output from another engine, followed by a blank newline:");
-    let stdin = io::stdin();
-    let mut ret = Vec::new();
-
-    for line in stdin.lock().lines() {
-        let line = line.context("Could not read line")?;
-
-        if line.is_empty() {
-            break;
-        }
-
-        let perft_result = parse_perft_line(&line).context("Could not parse perft line")?;
-        ret.push(perft_result);
-    }
-
-    Ok(ret)
-}
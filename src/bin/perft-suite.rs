@@ -0,0 +1,196 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use nom::Finish;
+use rmace::{
+    parsers::epd::{parse_epd, Epd},
+    position::{
+        movegen::perft::{
+            divide::{find_divergence, EngineDivide, StdinDivide},
+            Perft,
+        },
+        Position,
+    },
+};
+
+#[derive(clap::Parser)]
+/// Run every position of an EPD perft suite, where each position is annotated
+/// with the expected node counts, e.g. `;D1 20 ;D2 400`.
+struct Args {
+    /// The EPD file containing the perft suite.
+    suite: PathBuf,
+
+    /// The maximum depth to run each position to.
+    #[arg(short, long, default_value_t = 5)]
+    depth: u32,
+
+    /// The size, in megabytes, of the hash table used to cache the node
+    /// counts of transpositions.
+    #[arg(long)]
+    hash: Option<usize>,
+
+    /// The number of threads to split the root moves across.
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// When a position fails, divide it down to the first differing move by
+    /// entering the perft split output of another engine.
+    #[arg(long)]
+    debug: bool,
+
+    /// A reference engine, and its arguments, to automatically divide failing
+    /// positions with, e.g. `--engine stockfish`.
+    #[arg(short, long)]
+    engine: Option<String>,
+}
+
+struct Failure {
+    fen: String,
+    depth: u32,
+    expected: u64,
+    got: u64,
+}
+
+// The expected node counts of a position, in order of depth.
+fn expected_counts(epd: &Epd, max_depth: u32) -> Result<Vec<(u32, u64)>> {
+    let mut ret = Vec::new();
+
+    for op in epd.ops.iter() {
+        let Some(depth) = op.opcode.strip_prefix('D') else {
+            continue;
+        };
+
+        let depth: u32 = depth
+            .parse()
+            .with_context(|| format!("Invalid perft opcode {}", op.opcode))?;
+
+        if depth > max_depth {
+            continue;
+        }
+
+        let nodes = op
+            .operands
+            .first()
+            .ok_or_else(|| anyhow!("No node count for {}", op.opcode))?
+            .parse()
+            .with_context(|| format!("Invalid node count for {}", op.opcode))?;
+
+        ret.push((depth, nodes));
+    }
+
+    ret.sort();
+
+    Ok(ret)
+}
+
+fn run_position(args: &Args, line: &str) -> Result<Option<Failure>> {
+    let fen = line
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let epd = parse_epd(line)
+        .finish()
+        .map_err(|e| anyhow!("Could not parse EPD: {e}"))?
+        .1;
+    let counts = expected_counts(&epd, args.depth)?;
+    let pos = Position::try_from(epd.fen)?;
+
+    println!("{fen}");
+
+    for (depth, expected) in counts {
+        let perft = Perft::new(depth).with_threads(args.threads);
+        let perft = match args.hash {
+            Some(mb) => perft.with_hash(mb),
+            None => perft,
+        };
+
+        let now = Instant::now();
+        let got = perft.nodes(&pos);
+
+        println!(
+            "  D{}: {} ({:.2}s) {}",
+            depth,
+            got,
+            now.elapsed().as_secs_f32(),
+            if got == expected { "OK" } else { "FAIL" }
+        );
+
+        if got != expected {
+            return Ok(Some(Failure {
+                fen,
+                depth,
+                expected,
+                got,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let file = File::open(&args.suite).context("Could not open perft suite")?;
+    let mut engine = args.engine.as_deref().map(EngineDivide::from_command).transpose()?;
+    let mut failures = Vec::new();
+    let mut total = 0;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.context("Could not read perft suite")?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        total += 1;
+
+        if let Some(failure) = run_position(&args, &line)? {
+            let divergence = match engine.as_mut() {
+                Some(engine) => Some(find_divergence(
+                    engine,
+                    &failure.fen,
+                    failure.depth,
+                    args.threads,
+                )?),
+                None if args.debug => Some(find_divergence(
+                    &mut StdinDivide,
+                    &failure.fen,
+                    failure.depth,
+                    args.threads,
+                )?),
+                None => None,
+            };
+
+            if let Some(divergence) = divergence {
+                println!("{divergence}");
+            }
+
+            failures.push(failure);
+        }
+    }
+
+    println!("===========");
+    println!("{} of {} positions passed", total - failures.len(), total);
+
+    for f in failures.iter() {
+        println!(
+            "FAIL D{} expected {} got {}: {}",
+            f.depth, f.expected, f.got, f.fen
+        );
+    }
+
+    if !failures.is_empty() {
+        bail!("{} positions failed", failures.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use std::{
+    io::{stdin, stdout, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use nom::Finish;
+use rmace::{
+    game::Game,
+    mmove::Move,
+    parsers::{
+        san::{move_to_san, parse_san},
+        uci_move::{parse_uci_move, UciMove},
+    },
+    piece::Colour,
+    position::{eval::Evaluator, movegen::MoveGen, Position},
+    search::{SearchBuilder, SearchResults, MATE},
+};
+
+#[derive(clap::Parser)]
+/// Play against rmace, or step through and analyse a game, in the terminal.
+/// Moves may be entered in SAN or UCI notation; type `help` for the other
+/// commands.
+struct Args {
+    /// The FEN string of the starting position.
+    fen: Option<String>,
+
+    /// The side to play against the engine. Without this, moves for both
+    /// sides are entered by hand.
+    #[arg(short, long)]
+    colour: Option<String>,
+
+    /// The time, in milliseconds, the engine thinks for each move.
+    #[arg(short, long, default_value_t = 2000)]
+    time: u64,
+}
+
+const HELP: &str = "\
+<move>          play a move, in SAN (Nf3) or UCI (g1f3) notation
+go              let the engine play the side to move
+play <colour>   play white or black against the engine, or none
+undo            take back a move, or a move each when playing the engine
+redo            play a move taken back again
+flip            turn the board around
+moves           list the legal moves
+eval            show the breakdown of the static evaluation
+analyze         search until enter is pressed, printing each new PV
+time <ms>       set the engine's time per move
+fen <fen>       set up a new position
+new             start a new game
+quit            exit";
+
+struct Session {
+    game: Game,
+    engine: Option<Colour>,
+    flipped: bool,
+    move_time: Duration,
+}
+
+fn parse_colour(s: &str) -> Result<Option<Colour>> {
+    match s {
+        "white" | "w" => Ok(Some(Colour::White)),
+        "black" | "b" => Ok(Some(Colour::Black)),
+        "none" => Ok(None),
+        _ => Err(anyhow!("Unknown colour {s}")),
+    }
+}
+
+// The moves of `pv` from `pos`, in SAN.
+fn pv_to_san(pos: &Position, pv: &[Move]) -> String {
+    let mut pos = pos.clone();
+    let mut ret = Vec::new();
+
+    for m in pv {
+        ret.push(move_to_san(&mut pos, *m));
+        pos.make_move(*m).consume();
+    }
+
+    ret.join(" ")
+}
+
+fn format_score(results: &SearchResults) -> String {
+    let moves = results.pv.len().div_ceil(2);
+
+    match results.eval {
+        MATE => format!("#{moves}"),
+        x if x == -MATE => format!("#-{moves}"),
+        x => format!("{:+.2}", x as f32 / 100.0),
+    }
+}
+
+impl Session {
+    fn pos(&self) -> &Position {
+        self.game.position()
+    }
+
+    fn print(&self) {
+        if self.flipped {
+            println!("{}", self.pos().flipped());
+        } else {
+            println!("{}", self.pos());
+        }
+    }
+
+    fn parse_move(&self, input: &str) -> Option<Move> {
+        let mut pos = self.pos().clone();
+        let legal = MoveGen::legal_moves(&mut pos);
+
+        if let Ok(("", m)) = parse_uci_move(input).finish() {
+            return legal
+                .iter()
+                .find(|x| UciMove::from_move(**x, &pos) == m)
+                .copied();
+        }
+
+        let (_, san) = parse_san(input).finish().ok()?;
+        san.to_move(&mut pos)
+    }
+
+    fn make_move(&mut self, m: Move) {
+        self.game.make_move(m).expect("Only legal moves are played");
+    }
+
+    // Announce the end of the game, if it has ended.
+    fn game_over(&mut self) -> bool {
+        let Some(t) = self.game.termination() else {
+            return false;
+        };
+
+        match t.winner() {
+            Some(Colour::White) => println!("White wins by {t}."),
+            Some(Colour::Black) => println!("Black wins by {t}."),
+            None => println!("Draw by {t}."),
+        }
+
+        true
+    }
+
+    fn engine_move(&mut self) {
+        if self.game_over() {
+            return;
+        }
+
+        let mut pos = self.pos().clone();
+        let results = SearchBuilder::new(pos.clone())
+            .with_move_time(self.move_time)
+            .build()
+            .go();
+
+        let Some(m) = results
+            .pv
+            .first()
+            .copied()
+            .or_else(|| MoveGen::legal_moves(&mut pos).first().copied())
+        else {
+            return;
+        };
+
+        println!(
+            "Engine plays {} ({}, depth {})",
+            move_to_san(&mut pos, m),
+            format_score(&results),
+            results.depth
+        );
+
+        self.make_move(m);
+        self.print();
+        self.game_over();
+    }
+
+    fn analyze(&self) {
+        println!("Analysing, press enter to stop.");
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let pos = self.pos().clone();
+
+        thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let _ = stdin().lock().read_line(&mut String::new());
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+
+        let results = SearchBuilder::new(self.pos().clone())
+            .with_infinite()
+            .with_stop_flag(stop.clone())
+            .with_report_callback(move |results| {
+                println!(
+                    "depth {:>2} score {:>6} nodes {:>10} pv {}",
+                    results.depth,
+                    format_score(results),
+                    results.nodes,
+                    pv_to_san(&pos, &results.pv)
+                )
+            })
+            .build()
+            .go();
+
+        if let Some(m) = results.pv.first() {
+            println!("Best move: {}", move_to_san(&mut self.pos().clone(), *m));
+        }
+    }
+
+    // Run a command, returning whether to carry on.
+    fn command(&mut self, line: &str) -> Result<bool> {
+        let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        match cmd {
+            "quit" | "exit" => return Ok(false),
+            "help" => println!("{HELP}"),
+            "go" => {
+                self.engine = Some(self.pos().to_play());
+                self.engine_move();
+            }
+            "play" => {
+                self.engine = parse_colour(arg)?.map(Colour::next);
+
+                if self.engine == Some(self.pos().to_play()) {
+                    self.engine_move();
+                }
+            }
+            "undo" => {
+                self.game.undo();
+
+                if self.engine == Some(self.pos().to_play()) {
+                    self.game.undo();
+                }
+
+                self.print();
+            }
+            "redo" => {
+                self.game.redo();
+
+                if self.engine == Some(self.pos().to_play()) {
+                    self.game.redo();
+                }
+
+                self.print();
+            }
+            "flip" => {
+                self.flipped = !self.flipped;
+                self.print();
+            }
+            "moves" => {
+                let mut pos = self.pos().clone();
+                let moves: Vec<_> = MoveGen::legal_moves(&mut pos)
+                    .iter()
+                    .map(|m| move_to_san(&mut pos, *m))
+                    .collect();
+                println!("{}", moves.join(" "));
+            }
+            "eval" => print!("{}", Evaluator::trace(self.pos())),
+            "analyze" | "analyse" => self.analyze(),
+            "time" => self.move_time = Duration::from_millis(arg.parse()?),
+            "fen" => {
+                self.game = Game::from_fen(arg)?;
+                self.print();
+            }
+            "new" => {
+                self.game = Game::default();
+                self.print();
+            }
+            _ => {
+                let Some(m) = self.parse_move(line) else {
+                    println!("Unknown command or illegal move: {line}");
+                    return Ok(true);
+                };
+
+                self.make_move(m);
+                self.print();
+
+                if !self.game_over() && self.engine == Some(self.pos().to_play()) {
+                    self.engine_move();
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let game = match args.fen {
+        Some(ref fen) => Game::from_fen(fen)?,
+        None => Game::default(),
+    };
+
+    let human = args.colour.as_deref().map(parse_colour).transpose()?;
+
+    let mut session = Session {
+        game,
+        engine: human.flatten().map(Colour::next),
+        flipped: human.flatten() == Some(Colour::Black),
+        move_time: Duration::from_millis(args.time),
+    };
+
+    session.print();
+
+    if session.engine == Some(session.pos().to_play()) {
+        session.engine_move();
+    }
+
+    loop {
+        print!("> ");
+        stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match session.command(line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => println!("Error: {e}"),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rmace::{
+    parsers::pgn::parse_pgn,
+    piece::Colour,
+    search::SearchParams,
+    selfplay::{
+        play_game, player::InProcessPlayer, spsa::Spsa, stats::MatchScore, Adjudication, Opening,
+        TimeControl,
+    },
+};
+
+#[derive(clap::Parser)]
+/// Tune rmace's search parameters with SPSA, by playing short games between
+/// perturbed copies of its own search.
+struct Args {
+    /// The number of iterations to run.
+    #[arg(short, long, default_value_t = 1000)]
+    iterations: usize,
+
+    /// The number of game pairs, each opening played with both colours, per
+    /// iteration.
+    #[arg(short, long, default_value_t = 1)]
+    pairs: usize,
+
+    /// The time control, as `base+increment` in seconds.
+    #[arg(long, default_value = "2+0.02")]
+    tc: TimeControl,
+
+    /// The PGN file of openings to play, in turn.
+    #[arg(long, default_value = "etc/silversuite.pgn")]
+    openings: PathBuf,
+
+    /// The size of each engine's transposition table, in megabytes.
+    #[arg(long, default_value_t = 16)]
+    hash: usize,
+
+    /// The first perturbation of each parameter, as a fraction of its range.
+    #[arg(short, long, default_value_t = 0.05)]
+    perturbation: f64,
+
+    /// The learning rate, relative to the square of the perturbation.
+    #[arg(short, long, default_value_t = 0.002)]
+    learning_rate: f64,
+
+    /// The seed of the random perturbations.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+fn print_params(params: &SearchParams) {
+    for spec in SearchParams::SPECS {
+        println!("{} {}", spec.name, params.get(spec.name).unwrap());
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let pgn = fs::read_to_string(&args.openings)
+        .with_context(|| format!("Could not read {}", args.openings.display()))?;
+    let openings = parse_pgn(&pgn)?
+        .iter()
+        .map(Opening::try_from)
+        .collect::<Result<Vec<_>>>()?;
+
+    if openings.is_empty() {
+        bail!("No openings in {}", args.openings.display());
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(args.seed);
+    let mut spsa = Spsa::new(SearchParams::default(), args.iterations)
+        .with_perturbation(args.perturbation)
+        .with_learning_rate(args.learning_rate);
+    let adjudication = Adjudication::default();
+    let mut next_opening = 0;
+
+    for _ in 0..args.iterations {
+        spsa.step(&mut rng, |plus, minus| {
+            let mut plus = InProcessPlayer::new("plus")
+                .with_params(*plus)
+                .with_hash_size(args.hash);
+            let mut minus = InProcessPlayer::new("minus")
+                .with_params(*minus)
+                .with_hash_size(args.hash);
+            let mut score = MatchScore::default();
+
+            for _ in 0..args.pairs {
+                let opening = &openings[next_opening % openings.len()];
+                next_opening += 1;
+
+                let game = play_game(&mut plus, &mut minus, opening, args.tc, &adjudication)?;
+                score.add(game.outcome, Colour::White);
+
+                let game = play_game(&mut minus, &mut plus, opening, args.tc, &adjudication)?;
+                score.add(game.outcome, Colour::Black);
+            }
+
+            Ok(score)
+        })?;
+
+        println!("Iteration {}", spsa.iteration());
+        print_params(&spsa.params());
+    }
+
+    Ok(())
+}
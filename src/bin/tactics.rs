@@ -0,0 +1,240 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use nom::Finish;
+use rmace::{
+    mmove::Move,
+    parsers::{
+        epd::{parse_epd, Epd},
+        san::{move_to_san, parse_san},
+    },
+    position::Position,
+    search::{SearchBuilder, SearchResults, MATE},
+};
+
+#[derive(clap::Parser)]
+/// Run tactical test suites. Each position of an EPD file is searched and
+/// checked against its best move (bm), avoid move (am) and direct mate (dm)
+/// operations.
+struct Args {
+    /// The EPD files containing the test suites.
+    #[arg(required = true)]
+    suites: Vec<PathBuf>,
+
+    /// Search each position to this depth, rather than for a fixed time.
+    #[arg(short, long)]
+    depth: Option<usize>,
+
+    /// The time, in milliseconds, to search each position for. Positions with
+    /// an `acs` or `acd` operation use that limit instead.
+    #[arg(short, long, default_value_t = 1000)]
+    time: u64,
+}
+
+// What a search result must satisfy to pass.
+#[derive(Clone)]
+struct Goal {
+    best: Vec<Move>,
+    avoid: Vec<Move>,
+    mate: Option<usize>,
+}
+
+impl Goal {
+    fn solved(&self, results: &SearchResults) -> bool {
+        let Some(m) = results.pv.first() else {
+            return false;
+        };
+
+        (self.best.is_empty() || self.best.contains(m))
+            && !self.avoid.contains(m)
+            && self
+                .mate
+                .is_none_or(|n| results.eval == MATE && results.pv.len().div_ceil(2) <= n)
+    }
+}
+
+struct Test {
+    id: String,
+    pos: Position,
+    goal: Goal,
+    depth: Option<usize>,
+    time: Duration,
+}
+
+impl Test {
+    fn from_epd(args: &Args, epd: Epd, n: usize) -> Result<Self> {
+        let id = epd
+            .operands("id")
+            .first()
+            .cloned()
+            .unwrap_or_else(|| format!("line {n}"));
+
+        let moves = |opcode: &str, pos: &mut Position| -> Result<Vec<Move>> {
+            epd.operands(opcode)
+                .iter()
+                .map(|x| {
+                    parse_san(x)
+                        .finish()
+                        .ok()
+                        .and_then(|(_, san)| san.to_move(pos))
+                        .ok_or_else(|| anyhow!("{id}: invalid {opcode} move {x}"))
+                })
+                .collect()
+        };
+
+        let mut pos = Position::try_from(epd.fen.clone())?;
+        let best = moves("bm", &mut pos)?;
+        let avoid = moves("am", &mut pos)?;
+
+        let number = |opcode: &str| -> Result<Option<u64>> {
+            epd.operands(opcode)
+                .first()
+                .map(|x| x.parse())
+                .transpose()
+                .with_context(|| format!("{id}: invalid {opcode} operation"))
+        };
+
+        let mate = number("dm")?.map(|x| x as usize);
+        let depth = number("acd")?.map(|x| x as usize).or(args.depth);
+        let time = number("acs")?
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_millis(args.time));
+
+        if best.is_empty() && avoid.is_empty() && mate.is_none() {
+            bail!("{id}: no bm, am or dm operation");
+        }
+
+        Ok(Self {
+            id,
+            pos,
+            goal: Goal { best, avoid, mate },
+            depth,
+            time,
+        })
+    }
+
+    fn expected(&self) -> String {
+        let mut pos = self.pos.clone();
+        let mut ret = Vec::new();
+
+        for (opcode, moves) in [("bm", &self.goal.best), ("am", &self.goal.avoid)] {
+            if !moves.is_empty() {
+                let moves: Vec<_> = moves.iter().map(|m| move_to_san(&mut pos, *m)).collect();
+                ret.push(format!("{opcode} {}", moves.join(" ")));
+            }
+        }
+
+        if let Some(n) = self.goal.mate {
+            ret.push(format!("dm {n}"));
+        }
+
+        ret.join(", ")
+    }
+
+    // Search the position, returning the best move found and, if the test
+    // passed, the time at which the search settled on a solution.
+    fn run(&self) -> (Option<Move>, Option<Duration>) {
+        let start = Instant::now();
+        let found = Rc::new(RefCell::new(None));
+
+        let search = SearchBuilder::new(self.pos.clone()).with_report_callback({
+            let found = found.clone();
+            let goal = self.goal.clone();
+            move |results: &SearchResults| {
+                let mut found = found.borrow_mut();
+
+                if !goal.solved(results) {
+                    *found = None;
+                } else if found.is_none() {
+                    *found = Some(start.elapsed());
+                }
+            }
+        });
+
+        let search = match self.depth {
+            Some(depth) => search.with_depth(depth),
+            None => search.with_move_time(self.time),
+        };
+
+        let results = search.build().go();
+        let time = self
+            .goal
+            .solved(&results)
+            .then(|| found.borrow().unwrap_or_else(|| start.elapsed()));
+
+        (results.pv.first().copied(), time)
+    }
+}
+
+fn load_suite(args: &Args, path: &PathBuf) -> Result<Vec<Test>> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let mut ret = Vec::new();
+
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.context("Could not read test suite")?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let epd = parse_epd(&line)
+            .finish()
+            .map_err(|e| anyhow!("Could not parse line {}: {}", n + 1, e))?
+            .1;
+
+        ret.push(Test::from_epd(args, epd, n + 1)?);
+    }
+
+    Ok(ret)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut total = 0;
+    let mut times = Vec::new();
+
+    for path in args.suites.iter() {
+        for test in load_suite(&args, path)? {
+            total += 1;
+
+            let (best, time) = test.run();
+            let got = best
+                .map(|m| move_to_san(&mut test.pos.clone(), m))
+                .unwrap_or("none".to_string());
+
+            match time {
+                Some(t) => {
+                    println!(
+                        "PASS {} ({}, found {} in {:.2}s)",
+                        test.id,
+                        test.expected(),
+                        got,
+                        t.as_secs_f32()
+                    );
+                    times.push(t);
+                }
+                None => println!("FAIL {} ({}, got {})", test.id, test.expected(), got),
+            }
+        }
+    }
+
+    println!("===========");
+    println!("Score: {}/{}", times.len(), total);
+
+    if !times.is_empty() {
+        println!(
+            "Average time to solution: {:.2}s",
+            times.iter().sum::<Duration>().as_secs_f32() / times.len() as f32
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use nom::Finish;
+use rmace::{
+    parsers::epd::parse_epd,
+    piece::PieceKind,
+    position::{
+        eval::{endgame, EvalParams, Evaluator, PsqtTable, DEFAULT_EVAL_PARAMS, NUM_PARAMS},
+        Position,
+    },
+};
+use strum::IntoEnumIterator;
+
+#[derive(clap::Parser)]
+/// Tune rmace's evaluation parameters with Texel's method, using a set of
+/// quiet, labelled positions.
+struct Args {
+    /// An EPD file containing quiet positions, each labelled with the result of
+    /// the game it came from via a `c9` opcode, e.g. `c9 "1-0";`.
+    dataset: PathBuf,
+
+    /// The number of gradient descent iterations to run.
+    #[arg(short, long, default_value_t = 1000)]
+    epochs: usize,
+
+    /// The learning rate of the optimiser.
+    #[arg(short, long, default_value_t = 1.0)]
+    learning_rate: f64,
+
+    /// The sigmoid scaling constant. If not given, it is fitted to the
+    /// dataset using the current evaluation parameters.
+    #[arg(short, long)]
+    k: Option<f64>,
+
+    /// Write the tuned tables to this file rather than stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+struct Entry {
+    coeffs: Vec<(usize, f64)>,
+    result: f64,
+}
+
+fn parse_result(s: &str) -> Result<f64> {
+    match s {
+        "1-0" => Ok(1.0),
+        "0-1" => Ok(0.0),
+        "1/2-1/2" => Ok(0.5),
+        _ => bail!("Unknown game result {s}"),
+    }
+}
+
+fn load_dataset(path: &PathBuf) -> Result<Vec<Entry>> {
+    let file = File::open(path).context("Could not open dataset")?;
+    let mut ret = Vec::new();
+
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.context("Could not read dataset line")?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let epd = parse_epd(&line)
+            .finish()
+            .map_err(|e| anyhow!("Could not parse line {}: {}", n + 1, e))?
+            .1;
+
+        let result = epd
+            .operands("c9")
+            .first()
+            .ok_or_else(|| anyhow!("Line {} has no c9 result", n + 1))
+            .and_then(|x| parse_result(x))?;
+
+        let pos = Position::try_from(epd.fen)?;
+
+        // Specialised endgames don't use the tunable parameters.
+        if endgame::probe(&pos, &DEFAULT_EVAL_PARAMS).is_some() {
+            continue;
+        }
+
+        ret.push(Entry {
+            coeffs: Evaluator::coefficients(&pos)
+                .into_iter()
+                .map(|(i, c)| (i, c as f64))
+                .collect(),
+            result,
+        });
+    }
+
+    Ok(ret)
+}
+
+fn sigmoid(k: f64, eval: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+fn linear_eval(weights: &[f64], entry: &Entry) -> f64 {
+    entry.coeffs.iter().map(|(i, c)| weights[*i] * c).sum()
+}
+
+fn mean_error(k: f64, weights: &[f64], data: &[Entry]) -> f64 {
+    data.iter()
+        .map(|e| (e.result - sigmoid(k, linear_eval(weights, e))).powi(2))
+        .sum::<f64>()
+        / data.len() as f64
+}
+
+// Golden section search for the scaling constant which minimises the error of
+// the current parameters.
+fn fit_k(weights: &[f64], data: &[Entry]) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut lo, mut hi) = (0.0, 10.0);
+
+    while hi - lo > 1e-4 {
+        let a = hi - ratio * (hi - lo);
+        let b = lo + ratio * (hi - lo);
+
+        if mean_error(a, weights, data) < mean_error(b, weights, data) {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+
+    (lo + hi) / 2.0
+}
+
+fn gradient(k: f64, weights: &[f64], data: &[Entry]) -> Vec<f64> {
+    let mut grad = vec![0.0; NUM_PARAMS];
+    let scale = k * 10f64.ln() / 400.0;
+
+    for entry in data {
+        let s = sigmoid(k, linear_eval(weights, entry));
+        let d = -2.0 * (entry.result - s) * s * (1.0 - s) * scale;
+
+        for (i, c) in entry.coeffs.iter() {
+            grad[*i] += d * c;
+        }
+    }
+
+    grad.iter_mut().for_each(|x| *x /= data.len() as f64);
+
+    grad
+}
+
+fn tune(args: &Args, k: f64, data: &[Entry]) -> Vec<f64> {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    let mut weights: Vec<f64> = DEFAULT_EVAL_PARAMS
+        .weights()
+        .iter()
+        .map(|x| *x as f64)
+        .collect();
+    let mut m = vec![0.0; NUM_PARAMS];
+    let mut v = vec![0.0; NUM_PARAMS];
+
+    for epoch in 1..=args.epochs {
+        let grad = gradient(k, &weights, data);
+
+        for i in 0..NUM_PARAMS {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * grad[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * grad[i] * grad[i];
+            let m_hat = m[i] / (1.0 - BETA1.powi(epoch as i32));
+            let v_hat = v[i] / (1.0 - BETA2.powi(epoch as i32));
+            weights[i] -= args.learning_rate * m_hat / (v_hat.sqrt() + EPSILON);
+        }
+
+        if epoch % 50 == 0 || epoch == args.epochs {
+            eprintln!(
+                "Epoch {}: error {:.6}",
+                epoch,
+                mean_error(k, &weights, data)
+            );
+        }
+    }
+
+    weights
+}
+
+fn emit_table(out: &mut String, name: &str, table: &[i32]) -> std::fmt::Result {
+    writeln!(out, "#[rustfmt::skip]")?;
+    writeln!(out, "const {name}: [i32; 64] = [")?;
+
+    for (rank, row) in table.chunks(8).enumerate() {
+        let row: Vec<_> = row.iter().map(|x| format!("{x:>4}")).collect();
+        writeln!(out, "  {}, // {}", row.join(","), rank + 1)?;
+    }
+
+    writeln!(out, "  //  A    B    C    D    E    F    G    H")?;
+    writeln!(out, "];")?;
+    writeln!(out)
+}
+
+fn emit_tables(params: &EvalParams) -> Result<String> {
+    let mut out = String::new();
+
+    writeln!(out, "#[rustfmt::skip]")?;
+    writeln!(out, "const MATERIAL: [i32; PieceKind::COUNT] = [")?;
+    for kind in PieceKind::iter() {
+        writeln!(
+            out,
+            "    {:<6} // {:?}",
+            format!("{},", params.material(kind)),
+            kind
+        )?;
+    }
+    writeln!(out, "];")?;
+    writeln!(out)?;
+
+    for table in PsqtTable::iter() {
+        let name = match table {
+            PsqtTable::Pawn => "PSQT_PAWN",
+            PsqtTable::Knight => "PSQT_KNIGHT",
+            PsqtTable::Bishop => "PSQT_BISHOP",
+            PsqtTable::Rook => "PSQT_ROOK",
+            PsqtTable::Queen => "PSQT_QUEEN",
+            PsqtTable::KingMiddle => "PSQT_KING_MIDDLE",
+            PsqtTable::KingEnd => "PSQT_KING_END",
+        };
+
+        emit_table(&mut out, name, params.psqt(table))?;
+    }
+
+    Ok(out)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let data = load_dataset(&args.dataset)?;
+
+    if data.is_empty() {
+        bail!("Dataset contains no positions");
+    }
+
+    eprintln!("Loaded {} positions", data.len());
+
+    let initial: Vec<f64> = DEFAULT_EVAL_PARAMS
+        .weights()
+        .iter()
+        .map(|x| *x as f64)
+        .collect();
+
+    let k = args.k.unwrap_or_else(|| fit_k(&initial, &data));
+
+    eprintln!(
+        "Using K = {:.4}, initial error {:.6}",
+        k,
+        mean_error(k, &initial, &data)
+    );
+
+    let weights = tune(&args, k, &data);
+
+    let mut tuned = [0; NUM_PARAMS];
+    tuned
+        .iter_mut()
+        .zip(weights.iter())
+        .for_each(|(x, w)| *x = w.round() as i32);
+
+    let tables = emit_tables(&EvalParams::from_weights(tuned))?;
+
+    match args.output {
+        Some(path) => fs::write(path, tables).context("Could not write tables")?,
+        None => print!("{tables}"),
+    }
+
+    Ok(())
+}
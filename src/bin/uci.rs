@@ -1,252 +1,265 @@
 use std::{
     io::{self, BufRead},
-    time::Duration,
+    path::Path,
 };
 
 use anyhow::{Context, Result};
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{digit1, multispace0},
-    combinator::{map, map_res, opt, recognize},
-    error::ParseError,
-    multi::{many0, many1},
-    sequence::{delimited, tuple},
-    Finish, IResult, Parser,
-};
 use rmace::{
-    mmove::MoveType,
+    bench::{self, BENCH_DEPTH},
+    mmove::Move,
+    options::{Options, CHESS960, PONDER, TRACE_FILE},
     parsers::{
-        fen::{parse_fen, Fen},
-        uci_move::{parse_uci_move, UciMove},
+        uci::{parse_uci_cmd, GoSpecifier, PosSpecifier, UciCmd},
+        uci_move::UciMove,
+    },
+    position::{
+        eval::Evaluator,
+        movegen::{perft::Perft, MoveGen},
+        Position,
     },
-    piece::Colour,
-    position::{movegen::MoveGen, Position},
-    search::{SearchBuilder, SearchResults, MATE},
+    search::{Engine, Limits, PvLine, SearchEvent, SearchHandle, SearchResults, MATE},
 };
 
-#[derive(Debug)]
-enum PosSpecifier {
-    Starpos,
-    Fen(Fen),
-}
-
-#[derive(Debug)]
-enum GoSpecifier {
-    Time(Colour, Duration),
-    Inc(Colour, Duration),
-    Depth(usize),
-}
+fn main() -> Result<()> {
+    // Allow `uci bench [depth]` to be run from the command line, e.g. by CI.
+    let args: Vec<_> = std::env::args().skip(1).collect();
 
-#[derive(Debug)]
-enum UciCmd {
-    Uci,
-    IsReady,
-    NewGame,
-    Position(PosSpecifier, Option<Vec<UciMove>>),
-    Go(Vec<GoSpecifier>),
-    Display,
-}
+    if args.first().map(String::as_str) == Some("bench") {
+        let depth = args.get(1).map(|x| x.parse()).transpose()?;
+        handle_cmd_bench(depth);
+        return Ok(());
+    }
 
-fn parse_cmd_uci(input: &str) -> IResult<&str, UciCmd> {
-    map(tag("uci"), |_| UciCmd::Uci)(input)
-}
+    let mut pos = Position::default();
+    let mut engine = Engine::new();
+    let mut search: Option<SearchHandle> = None;
+    let mut debug = false;
+    loop {
+        let mut line = String::new();
+        let read = io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read UCI line")?;
 
-fn parse_cmd_isready(input: &str) -> IResult<&str, UciCmd> {
-    map(tag("isready"), |_| UciCmd::IsReady)(input)
-}
+        // The GUI has gone away.
+        if read == 0 {
+            handle_cmd_stop(&mut search);
+            return Ok(());
+        }
 
-fn parse_cmd_newgame(input: &str) -> IResult<&str, UciCmd> {
-    map(tag("ucinewgame"), |_| UciCmd::NewGame)(input)
-}
+        let cmd = match parse_uci_cmd(&line) {
+            Ok(Some(x)) => x,
+            Ok(None) => continue,
+            Err(e) => {
+                println!("info string {e}");
+                continue;
+            }
+        };
 
-fn ws<'a, O, E: ParseError<&'a str>, F>(inner: F) -> impl Parser<&'a str, O, E>
-where
-    F: Parser<&'a str, O, E>,
-{
-    delimited(multispace0, inner, multispace0)
+        match cmd {
+            UciCmd::Uci => handle_cmd_uci(engine.options()),
+            UciCmd::IsReady => handle_cmd_isready(),
+            UciCmd::NewGame => {
+                handle_cmd_stop(&mut search);
+                handle_cmd_newgame(&mut pos, &engine);
+            }
+            UciCmd::Position(f, m) => handle_cmd_position(&mut pos, f, m, engine.options()),
+            UciCmd::Go(specs) => {
+                handle_cmd_stop(&mut search);
+                search = Some(handle_cmd_go(&pos, specs, &engine, debug));
+            }
+            UciCmd::Stop => handle_cmd_stop(&mut search),
+            UciCmd::PonderHit => {
+                if let Some(ref x) = search {
+                    x.ponderhit();
+                }
+            }
+            UciCmd::Perft(depth) => handle_cmd_perft(&pos, depth),
+            UciCmd::Bench(depth) => handle_cmd_bench(depth),
+            UciCmd::Display => println!("{}", pos),
+            UciCmd::Eval => print!(
+                "{}",
+                Evaluator::trace_with_params(&pos, &engine.options().eval_params())
+            ),
+            UciCmd::Debug(on) => debug = on,
+            // rmace is free, so needs no registration.
+            UciCmd::Register => {}
+            UciCmd::Quit => {
+                handle_cmd_stop(&mut search);
+                return Ok(());
+            }
+            UciCmd::SetOption(name, value) => {
+                if let Err(e) = engine.set_option(&name, value.as_deref()) {
+                    println!("info string {e}");
+                }
+            }
+        }
+    }
 }
 
-fn parse_cmd_position(input: &str) -> IResult<&str, UciCmd> {
-    map(
-        tuple((
-            ws(tag("position")),
-            alt((
-                map(tuple((ws(tag("fen")), parse_fen)), |(_, fen)| {
-                    PosSpecifier::Fen(fen)
-                }),
-                map(ws(tag("startpos")), |_| PosSpecifier::Starpos),
-            )),
-            opt(tuple((
-                ws(tag("moves")),
-                many1(delimited(multispace0, parse_uci_move, multispace0)),
-            ))),
-        )),
-        |(_, pos, moves)| UciCmd::Position(pos, moves.map(|x| x.1)),
-    )(input)
+fn handle_cmd_newgame(pos: &mut Position, engine: &Engine) {
+    *pos = Position::default();
+    engine.new_game();
 }
 
-fn parse_msec(input: &str) -> IResult<&str, Duration> {
-    map_res(recognize(digit1), |x| -> Result<Duration> {
-        let milies: u64 = str::parse(x)?;
+// The moves of `pv` from `pos`, in UCI notation.
+fn pv_to_uci(pos: &Position, pv: &[Move]) -> String {
+    let mut pos = pos.clone();
+    let mut ret = String::new();
 
-        Ok(Duration::from_millis(milies as u64))
-    })(input)
-}
+    for m in pv {
+        ret.push_str(&format!(" {}", UciMove::from_move(*m, &pos)));
+        pos.make_move(*m).consume();
+    }
 
-fn parse_time_spec(input: &str) -> IResult<&str, GoSpecifier> {
-    map(
-        tuple((alt((ws(tag("wtime")), ws(tag("btime")))), parse_msec)),
-        |(tm, msec)| {
-            let colour = if tm == "wtime" {
-                Colour::White
-            } else {
-                Colour::Black
-            };
-
-            GoSpecifier::Time(colour, msec)
-        },
-    )(input)
+    ret
 }
 
-fn parse_time_inc(input: &str) -> IResult<&str, GoSpecifier> {
-    map(
-        tuple((alt((ws(tag("winc")), ws(tag("binc")))), parse_msec)),
-        |(tm, msec)| {
-            let colour = if tm == "winc" {
-                Colour::White
-            } else {
-                Colour::Black
-            };
-
-            GoSpecifier::Inc(colour, msec)
-        },
-    )(input)
+fn format_score(line: &PvLine) -> String {
+    if line.eval == MATE {
+        format!("mate {}", line.pv.len().div_ceil(2))
+    } else if line.eval == -MATE {
+        format!("mate -{}", line.pv.len().div_ceil(2))
+    } else {
+        format!("cp {}", line.eval)
+    }
 }
 
-fn parse_depth_spec(input: &str) -> IResult<&str, GoSpecifier> {
-    map_res(
-        tuple((ws(tag("depth")), digit1)),
-        |(_, n)| -> Result<GoSpecifier> { Ok(GoSpecifier::Depth(n.parse()?)) },
-    )(input)
-}
+fn report_results(pos: &Position, results: &SearchResults, debug: bool) {
+    for (idx, line) in results.lines.iter().enumerate() {
+        println!(
+            "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv{}",
+            results.depth,
+            results.seldepth,
+            idx + 1,
+            format_score(line),
+            results.total_nodes,
+            results.nps(),
+            results.hashfull,
+            results.time.as_millis(),
+            pv_to_uci(pos, &line.pv),
+        );
+    }
 
-fn parse_go_specs(input: &str) -> IResult<&str, Vec<GoSpecifier>> {
-    many0(alt((parse_time_spec, parse_time_inc, parse_depth_spec)))(input)
+    if debug {
+        println!(
+            "info string qnodes {} tthits {} cutoffs {} alphainc {}",
+            results.qnodes, results.ttable_hits, results.beta_cutoffs, results.alpha_increases
+        );
+    }
 }
 
-fn parse_cmd_go(input: &str) -> IResult<&str, UciCmd> {
-    map(tuple((tag("go"), parse_go_specs)), |(_, specs)| {
-        UciCmd::Go(specs)
-    })(input)
-}
+fn report_bestmove(pos: &Position, results: &SearchResults, options: &Options) {
+    if let (Some(trace), Some(path)) = (&results.trace, options.string(TRACE_FILE)) {
+        if let Err(e) = trace.save(Path::new(path)) {
+            println!("info string {e:#}");
+        }
+    }
 
-fn parse_uci_cmd(input: &str) -> Result<UciCmd> {
-    Ok(alt((
-        parse_cmd_uci,
-        parse_cmd_isready,
-        parse_cmd_newgame,
-        parse_cmd_position,
-        parse_cmd_go,
-        map(tag("d"), |_| UciCmd::Display),
-    ))(input)
-    .map_err(|e| e.to_owned())
-    .finish()
-    .map(|x| x.1)?)
-}
+    // A search stopped before its first iteration completed has no PV.
+    let mut pos = pos.clone();
+    let Some(best) = results
+        .pv
+        .first()
+        .copied()
+        .or_else(|| MoveGen::legal_moves(&mut pos).first().copied())
+    else {
+        println!("bestmove 0000");
+        return;
+    };
 
-fn main() -> Result<()> {
-    let mut pos = Position::default();
-    loop {
-        let mut line = String::new();
-        io::stdin()
-            .lock()
-            .read_line(&mut line)
-            .context("Failed to read UCI line")?;
+    let mut line = format!("bestmove {}", UciMove::from_move(best, &pos));
 
-        let cmd = parse_uci_cmd(&line)?;
-        match cmd {
-            UciCmd::Uci => handle_cmd_uci(),
-            UciCmd::IsReady => handle_cmd_isready(),
-            UciCmd::NewGame => handle_cmd_newgame(&mut pos),
-            UciCmd::Position(f, m) => handle_cmd_position(&mut pos, f, m),
-            UciCmd::Go(specs) => handle_cmd_go(&mut pos, specs),
-            UciCmd::Display => println!("{}", pos),
+    if options.check(PONDER) == Some(true) {
+        if let Some(reply) = results.pv.get(1) {
+            pos.make_move(best).consume();
+            line += &format!(" ponder {}", UciMove::from_move(*reply, &pos));
         }
     }
-}
 
-fn handle_cmd_newgame(pos: &mut Position) {
-    *pos = Position::default();
+    println!("{line}");
 }
 
-fn report_results(results: &SearchResults) {
-    println!(
-        "info depth {} pv{} score {} nodes {} qnodes {} tthits {} cutoffs {} alphainc {}",
-        results.depth,
-        results
-            .pv
-            .iter()
-            .map(|x| UciMove::from(*x))
-            .fold(String::new(), |mut accum, x| {
-                accum.push_str(&format!(" {}", x).to_owned());
-                accum
-            }),
-        if results.eval == MATE {
-            format!("mate {}", results.pv.len().div_ceil(2))
-        } else if results.eval == -MATE {
-            format!("mate -{}", results.pv.len().div_ceil(2))
-        } else {
-            format!("cp {}", results.eval)
-        },
-        results.nodes,
-        results.qnodes,
-        results.ttable_hits,
-        results.beta_cutoffs,
-        results.alpha_increases
-    );
-}
-
-fn handle_cmd_go(pos: &mut Position, specs: Vec<GoSpecifier>) {
-    let mut search = SearchBuilder::new(pos.clone()).with_report_callback(report_results);
+// Start searching in the background, reporting the best move once the search
+// finishes.
+fn handle_cmd_go(
+    pos: &Position,
+    specs: Vec<GoSpecifier>,
+    engine: &Engine,
+    debug: bool,
+) -> SearchHandle {
+    let mut limits = Limits::default();
 
-    for spec in specs.iter() {
+    for spec in specs {
         match spec {
-            GoSpecifier::Time(colour, deadline) if *colour == pos.to_play() => {
-                search = search.with_deadline(*deadline)
+            GoSpecifier::Time(colour, deadline) if colour == pos.to_play() => {
+                limits.time_left = Some(deadline)
+            }
+            GoSpecifier::Inc(colour, increment) if colour == pos.to_play() => {
+                limits.increment = Some(increment)
             }
-            GoSpecifier::Depth(d) => search = search.with_depth(*d),
+            GoSpecifier::Depth(d) => limits.depth = Some(d),
+            GoSpecifier::MovesToGo(n) => limits.moves_to_go = Some(n),
+            GoSpecifier::MoveTime(t) => limits.move_time = Some(t),
+            GoSpecifier::Infinite => limits.infinite = true,
+            GoSpecifier::Ponder => limits.ponder = true,
             _ => {}
         }
     }
 
-    let results = search.build().go();
+    let options = engine.options().clone();
+    let pos = pos.clone();
 
-    println!("bestmove {}", UciMove::from(*results.pv.first().unwrap()))
+    engine.go_with_callback(pos.clone(), limits, move |event| match event {
+        SearchEvent::Iteration(results) => report_results(&pos, results, debug),
+        SearchEvent::CurrMove(m, n) => println!(
+            "info currmove {} currmovenumber {n}",
+            UciMove::from_move(m, &pos)
+        ),
+        SearchEvent::Finished(results) => report_bestmove(&pos, results, &options),
+    })
 }
 
-fn handle_cmd_position(pos: &mut Position, p: PosSpecifier, m: Option<Vec<UciMove>>) {
-    match p {
-        PosSpecifier::Fen(fen) => {
-            *pos = Position::try_from(fen).expect("Could not create position from FEN")
-        }
-        PosSpecifier::Starpos => *pos = Position::default(),
+// Stop any search still running, waiting for it to report its best move.
+fn handle_cmd_stop(search: &mut Option<SearchHandle>) {
+    if let Some(x) = search.take() {
+        x.stop();
+        x.join();
     }
+}
 
-    if let Some(moves) = m {
-        for m in moves.iter() {
-            match MoveGen::new(pos).gen().iter().find(|x| {
-                x.src == m.src
-                    && x.dst == m.dst
-                    && match x.kind {
-                        MoveType::Promote(p) => Some(p.kind()),
-                        _ => None,
-                    } == m.promote
-            }) {
-                Some(x) => pos.make_move(*x).consume(),
-                None => panic!("Move {} is not a valid move", m),
-            }
-        }
+fn handle_cmd_perft(pos: &Position, depth: u32) {
+    let split = Perft::new(depth).split(pos);
+
+    for (m, n) in split.iter() {
+        println!("{}: {}", UciMove::from_move(*m, pos), n);
+    }
+
+    println!();
+    println!(
+        "Nodes searched: {}",
+        split.iter().map(|(_, n)| n).sum::<u64>()
+    );
+}
+
+fn handle_cmd_bench(depth: Option<usize>) {
+    let results = bench::run(depth.unwrap_or(BENCH_DEPTH));
+
+    println!("Time: {} ms", results.time.as_millis());
+    println!("Nodes searched: {}", results.nodes);
+    println!("Nodes/second: {}", results.nps());
+}
+
+// Set up the position, leaving the old one alone if the new one isn't legal.
+fn handle_cmd_position(
+    pos: &mut Position,
+    p: PosSpecifier,
+    moves: Vec<UciMove>,
+    options: &Options,
+) {
+    match p.to_position(&moves, options.check(CHESS960).unwrap_or(false)) {
+        Ok(x) => *pos = x,
+        Err(e) => println!("info string {e}"),
     }
 }
 
@@ -254,8 +267,9 @@ fn handle_cmd_isready() {
     println!("readyok");
 }
 
-fn handle_cmd_uci() {
+fn handle_cmd_uci(options: &Options) {
     println!("id rmace");
     println!("id author Matthew Leach");
+    print!("{options}");
     println!("uciok");
 }
@@ -0,0 +1,370 @@
+use std::{
+    io::{self, BufRead},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till},
+    character::complete::{digit1, multispace1},
+    combinator::{map, map_res, opt, recognize, rest},
+    sequence::{preceded, tuple},
+    Finish, IResult,
+};
+use rmace::{
+    game::{Game, Termination},
+    parsers::{
+        fen::parse_fen,
+        uci_move::{parse_uci_move, UciMove},
+    },
+    piece::Colour,
+    position::{movegen::MoveGen, Position},
+    search::{SearchBuilder, SearchResults, MATE},
+};
+
+#[derive(Debug)]
+enum XbCmd {
+    Xboard,
+    Protover,
+    Accepted,
+    New,
+    Force,
+    Go,
+    PlayOther,
+    UserMove(String),
+    /// Moves per time control, or zero for the whole game, base time and
+    /// increment.
+    Level(u32, Duration, Duration),
+    MoveTime(Duration),
+    Depth(usize),
+    Time(Duration),
+    OpponentTime,
+    Post(bool),
+    Undo(usize),
+    SetBoard(String),
+    Ping(String),
+    Result,
+    Ignored,
+    Quit,
+}
+
+fn centiseconds(input: &str) -> IResult<&str, Duration> {
+    map_res(digit1, |x: &str| -> Result<Duration> {
+        Ok(Duration::from_millis(x.parse::<u64>()? * 10))
+    })(input)
+}
+
+fn seconds(input: &str) -> IResult<&str, Duration> {
+    map_res(
+        recognize(tuple((digit1, opt(tuple((tag("."), digit1)))))),
+        |x: &str| -> Result<Duration> { Ok(Duration::try_from_secs_f64(x.parse()?)?) },
+    )(input)
+}
+
+// The base time of `level`, either minutes or `minutes:seconds`.
+fn parse_level_base(input: &str) -> IResult<&str, Duration> {
+    map_res(
+        tuple((digit1, opt(preceded(tag(":"), digit1)))),
+        |(m, s): (&str, Option<&str>)| -> Result<Duration> {
+            let secs = m.parse::<u64>()? * 60 + s.map(str::parse).transpose()?.unwrap_or(0);
+            Ok(Duration::from_secs(secs))
+        },
+    )(input)
+}
+
+fn parse_cmd_level(input: &str) -> IResult<&str, XbCmd> {
+    map(
+        tuple((
+            tag("level"),
+            multispace1,
+            map_res(digit1, str::parse),
+            multispace1,
+            parse_level_base,
+            multispace1,
+            seconds,
+        )),
+        |(_, _, mps, _, base, _, inc)| XbCmd::Level(mps, base, inc),
+    )(input)
+}
+
+fn arg<'a, O>(
+    name: &'static str,
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    preceded(tuple((tag(name), multispace1)), parser)
+}
+
+fn parse_xboard_cmd(input: &str) -> Result<XbCmd> {
+    let word = |x: &'static str, cmd: fn() -> XbCmd| map(tag(x), move |_| cmd());
+
+    Ok(alt((
+        alt((
+            map(arg("protover", digit1), |_| XbCmd::Protover),
+            map(arg("accepted", rest), |_| XbCmd::Accepted),
+            map(arg("rejected", rest), |_| XbCmd::Accepted),
+            map(
+                arg("usermove", take_till(char::is_whitespace)),
+                |x: &str| XbCmd::UserMove(x.to_string()),
+            ),
+            parse_cmd_level,
+            map(arg("st", seconds), XbCmd::MoveTime),
+            map(arg("sd", map_res(digit1, str::parse)), XbCmd::Depth),
+            map(arg("time", centiseconds), XbCmd::Time),
+            map(arg("otim", centiseconds), |_| XbCmd::OpponentTime),
+            map(arg("setboard", rest), |x: &str| {
+                XbCmd::SetBoard(x.trim().to_string())
+            }),
+            map(arg("ping", rest), |x: &str| {
+                XbCmd::Ping(x.trim().to_string())
+            }),
+            map(tag("result"), |_| XbCmd::Result),
+        )),
+        alt((
+            word("xboard", || XbCmd::Xboard),
+            word("new", || XbCmd::New),
+            word("force", || XbCmd::Force),
+            word("go", || XbCmd::Go),
+            word("playother", || XbCmd::PlayOther),
+            word("post", || XbCmd::Post(true)),
+            word("nopost", || XbCmd::Post(false)),
+            word("undo", || XbCmd::Undo(1)),
+            word("remove", || XbCmd::Undo(2)),
+            word("quit", || XbCmd::Quit),
+        )),
+        map(
+            alt((
+                tag("random"),
+                tag("hard"),
+                tag("easy"),
+                tag("computer"),
+                tag("name"),
+                tag("rating"),
+                tag("ics"),
+                tag("draw"),
+                tag("white"),
+                tag("black"),
+                tag("."),
+            )),
+            |_| XbCmd::Ignored,
+        ),
+    ))(input.trim())
+    .map_err(|e| e.to_owned())
+    .finish()
+    .map(|x| x.1)?)
+}
+
+struct Engine {
+    game: Game,
+    /// The side the engine plays, or `None` in force mode.
+    colour: Option<Colour>,
+    post: bool,
+    time_left: Option<Duration>,
+    increment: Duration,
+    /// The number of moves in each time control, if the clock is reset
+    /// after them.
+    moves_per_session: Option<u32>,
+    move_time: Option<Duration>,
+    depth: Option<usize>,
+}
+
+impl Engine {
+    fn new() -> Self {
+        Self {
+            game: Game::default(),
+            colour: Some(Colour::Black),
+            post: false,
+            time_left: None,
+            increment: Duration::ZERO,
+            moves_per_session: None,
+            move_time: None,
+            depth: None,
+        }
+    }
+
+    fn make_move(&mut self, m: UciMove) -> bool {
+        self.game.make_uci_move(m).is_ok()
+    }
+
+    // Announce the result if the game has ended.
+    fn check_result(&mut self) -> bool {
+        let Some(t) = self.game.termination() else {
+            return false;
+        };
+
+        let result = match t {
+            Termination::Checkmate(Colour::White) => "0-1 {Black mates}",
+            Termination::Checkmate(Colour::Black) => "1-0 {White mates}",
+            Termination::Stalemate => "1/2-1/2 {Stalemate}",
+            Termination::Repetition => "1/2-1/2 {Draw by repetition}",
+            Termination::FiftyMoves => "1/2-1/2 {Draw by fifty move rule}",
+            Termination::InsufficientMaterial => "1/2-1/2 {Insufficient material}",
+        };
+
+        println!("{result}");
+        self.colour = None;
+
+        true
+    }
+
+    fn think(&mut self) {
+        if self.check_result() {
+            return;
+        }
+
+        let start = Instant::now();
+        let post = self.post;
+        let mut search =
+            SearchBuilder::new(self.game.position().clone()).with_report_callback(move |results| {
+                if post {
+                    report_results(results, start.elapsed())
+                }
+            });
+
+        if let Some(depth) = self.depth {
+            search = search.with_depth(depth);
+        }
+
+        if let Some(move_time) = self.move_time {
+            search = search.with_move_time(move_time);
+        } else if let Some(time_left) = self.time_left {
+            search = search
+                .with_deadline(time_left)
+                .with_increment(self.increment);
+
+            // Each side has made half of the moves of the game.
+            if let Some(mps) = self.moves_per_session {
+                search = search.with_moves_to_go(mps - (self.game.ply() / 2) as u32 % mps);
+            }
+        }
+
+        let results = search.build().go();
+        let Some(m) = results.pv.first().copied().or_else(|| {
+            MoveGen::legal_moves(&mut self.game.position().clone())
+                .first()
+                .copied()
+        }) else {
+            return;
+        };
+
+        println!("move {}", UciMove::from(m));
+        self.make_move(m.into());
+        self.check_result();
+    }
+}
+
+// Thinking output, as `ply score time nodes pv` with the time in centiseconds.
+fn report_results(results: &SearchResults, elapsed: Duration) {
+    let moves = results.pv.len().div_ceil(2) as i32;
+    let score = match results.eval {
+        MATE => 100000 + moves,
+        x if x == -MATE => -100000 - moves,
+        x => x,
+    };
+
+    println!(
+        "{} {} {} {}{}",
+        results.depth,
+        score,
+        elapsed.as_millis() / 10,
+        results.total_nodes,
+        results
+            .pv
+            .iter()
+            .map(|x| format!(" {}", UciMove::from(*x)))
+            .collect::<String>()
+    );
+}
+
+fn handle_cmd(engine: &mut Engine, cmd: XbCmd) -> bool {
+    match cmd {
+        XbCmd::Xboard | XbCmd::Accepted | XbCmd::OpponentTime | XbCmd::Ignored => {}
+        XbCmd::Protover => {
+            println!(
+                "feature myname=\"rmace\" setboard=1 usermove=1 time=1 ping=1 playother=1 \
+                 colors=0 sigint=0 sigterm=0 analyze=0 draw=0 reuse=1 san=0"
+            );
+            println!("feature done=1");
+        }
+        XbCmd::New => {
+            *engine = Engine {
+                post: engine.post,
+                ..Engine::new()
+            }
+        }
+        XbCmd::Force | XbCmd::Result => engine.colour = None,
+        XbCmd::Go => {
+            engine.colour = Some(engine.game.position().to_play());
+            engine.think();
+        }
+        XbCmd::PlayOther => engine.colour = Some(engine.game.position().to_play().next()),
+        XbCmd::UserMove(m) => match parse_uci_move(&m).finish() {
+            Ok((_, x)) if engine.make_move(x) => {
+                if engine.colour == Some(engine.game.position().to_play()) {
+                    engine.think();
+                } else {
+                    engine.check_result();
+                }
+            }
+            _ => println!("Illegal move: {m}"),
+        },
+        XbCmd::Level(mps, base, inc) => {
+            engine.time_left = Some(base);
+            engine.increment = inc;
+            engine.moves_per_session = (mps > 0).then_some(mps);
+            engine.move_time = None;
+        }
+        XbCmd::MoveTime(t) => engine.move_time = Some(t),
+        XbCmd::Depth(d) => engine.depth = Some(d),
+        XbCmd::Time(t) => engine.time_left = Some(t),
+        XbCmd::Post(post) => engine.post = post,
+        XbCmd::Undo(n) => {
+            for _ in 0..n {
+                engine.game.undo();
+            }
+        }
+        XbCmd::SetBoard(fen) => match parse_fen(&fen).finish() {
+            Ok((_, fen)) => match Position::try_from(fen).and_then(|x| {
+                x.check_legal()?;
+                Ok(x)
+            }) {
+                Ok(pos) => engine.game = Game::new(pos),
+                Err(e) => println!("tellusererror Illegal position: {e}"),
+            },
+            Err(_) => println!("tellusererror Illegal position"),
+        },
+        XbCmd::Ping(n) => println!("pong {n}"),
+        XbCmd::Quit => return false,
+    }
+
+    true
+}
+
+fn main() -> Result<()> {
+    let mut engine = Engine::new();
+
+    loop {
+        let mut line = String::new();
+        let n = io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read xboard line")?;
+
+        if n == 0 {
+            return Ok(());
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_xboard_cmd(&line) {
+            Ok(cmd) => {
+                if !handle_cmd(&mut engine, cmd) {
+                    return Ok(());
+                }
+            }
+            Err(_) => println!("Error (unknown command): {}", line.trim()),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! A whole game: its starting position and the moves played from it, which
+//! can be stepped back and forth through, however long the game is.
+
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+use nom::Finish;
+
+use crate::{
+    mmove::Move,
+    parsers::{
+        san::{move_to_san, parse_san},
+        uci_move::UciMove,
+    },
+    piece::{Colour, Piece, PieceKind},
+    position::{bitboard::BitBoard, movegen::MoveGen, Position},
+};
+
+// The dark squares of the board, such as a1.
+const DARK_SQUARES: BitBoard = BitBoard::new(0xaa55_aa55_aa55_aa55);
+
+// Whether neither side could ever checkmate, however badly the other played:
+// with only the kings, one minor piece, or bishops all on squares of the same
+// colour.
+fn is_dead_position(pos: &Position) -> bool {
+    let pieces = |kind| pos[Piece::new(kind, Colour::White)] | pos[Piece::new(kind, Colour::Black)];
+
+    if [PieceKind::Pawn, PieceKind::Rook, PieceKind::Queen]
+        .into_iter()
+        .any(|k| !pieces(k).is_empty())
+    {
+        return false;
+    }
+
+    let knights = pieces(PieceKind::Knight).popcount();
+    let bishops = pieces(PieceKind::Bishop);
+
+    match knights {
+        0 => (bishops & DARK_SQUARES).is_empty() || (bishops & !DARK_SQUARES).is_empty(),
+        1 => bishops.is_empty(),
+        _ => false,
+    }
+}
+
+/// Why a game is over.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Termination {
+    /// The given side has been mated.
+    Checkmate(Colour),
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+}
+
+impl Termination {
+    pub fn winner(self) -> Option<Colour> {
+        match self {
+            Termination::Checkmate(c) => Some(c.next()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Termination::Checkmate(_) => write!(f, "checkmate"),
+            Termination::Stalemate => write!(f, "stalemate"),
+            Termination::Repetition => write!(f, "threefold repetition"),
+            Termination::FiftyMoves => write!(f, "fifty move rule"),
+            Termination::InsufficientMaterial => write!(f, "insufficient material"),
+        }
+    }
+}
+
+// How many plies apart the positions kept to go back to are.
+const CHECKPOINT_PLIES: usize = 64;
+
+#[derive(Clone)]
+pub struct Game {
+    start: Position,
+    moves: Vec<Move>,
+    /// The number of moves played to reach `pos`. Any moves after that were
+    /// undone, and can be redone.
+    ply: usize,
+    pos: Position,
+    /// The positions after every `CHECKPOINT_PLIES` moves played, to go
+    /// back to when moves are undone, rather than keeping every position.
+    checkpoints: Vec<Position>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Position::default())
+    }
+}
+
+impl Game {
+    pub fn new(start: Position) -> Self {
+        Self {
+            pos: start.clone(),
+            start,
+            moves: Vec::new(),
+            ply: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self> {
+        let pos = Position::from_fen(fen)?;
+        pos.check_legal()?;
+
+        Ok(Self::new(pos))
+    }
+
+    pub fn start(&self) -> &Position {
+        &self.start
+    }
+
+    /// The position after the moves played so far.
+    pub fn position(&self) -> &Position {
+        &self.pos
+    }
+
+    /// Every move of the game, including any which were undone.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Play the legal move `m`, replacing any moves which were undone.
+    pub fn make_move(&mut self, m: Move) -> Result<()> {
+        if !MoveGen::legal_moves(&mut self.pos).contains(&m) {
+            bail!("{} is not a legal move", UciMove::from_move(m, &self.pos));
+        }
+
+        self.moves.truncate(self.ply);
+        self.moves.push(m);
+        self.play(m);
+
+        Ok(())
+    }
+
+    pub fn make_uci_move(&mut self, m: UciMove) -> Result<()> {
+        match m.to_move(&self.pos) {
+            Some(x) => self.make_move(x),
+            None => bail!("{m} is not a legal move"),
+        }
+    }
+
+    pub fn make_san_move(&mut self, san: &str) -> Result<()> {
+        match parse_san(san)
+            .finish()
+            .ok()
+            .and_then(|(_, x)| x.to_move(&mut self.pos))
+        {
+            Some(x) => self.make_move(x),
+            None => bail!("{san} is not a legal move"),
+        }
+    }
+
+    fn play(&mut self, m: Move) {
+        self.pos.make_move(m).consume();
+        self.pos.forget_history();
+        self.ply += 1;
+
+        if self.ply.is_multiple_of(CHECKPOINT_PLIES) {
+            self.checkpoints.push(self.pos.clone());
+        }
+    }
+
+    /// Take back the last move played, returning whether there was one.
+    pub fn undo(&mut self) -> bool {
+        if self.ply == 0 {
+            return false;
+        }
+
+        self.goto(self.ply - 1).is_ok()
+    }
+
+    /// Play the next of the moves undone, returning whether there was one.
+    pub fn redo(&mut self) -> bool {
+        match self.moves.get(self.ply) {
+            Some(&m) => {
+                self.play(m);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go to the position after `ply` moves of the game.
+    pub fn goto(&mut self, ply: usize) -> Result<()> {
+        if ply > self.moves.len() {
+            bail!("The game is only {} plies long", self.moves.len());
+        }
+
+        // Moves can't be undone on the position itself, since it forgets
+        // them, so go back to the last checkpoint before them and play on
+        // from there.
+        if ply < self.ply {
+            let n = ply / CHECKPOINT_PLIES;
+
+            self.checkpoints.truncate(n);
+            self.pos = self.checkpoints.last().unwrap_or(&self.start).clone();
+            self.ply = n * CHECKPOINT_PLIES;
+        }
+
+        while self.ply < ply {
+            self.play(self.moves[self.ply]);
+        }
+
+        Ok(())
+    }
+
+    /// Why the game is over at the current position, if it is.
+    pub fn termination(&mut self) -> Option<Termination> {
+        let mover = self.pos.to_play();
+
+        if MoveGen::legal_moves(&mut self.pos).is_empty() {
+            return Some(if MoveGen::new(&self.pos).in_check(mover) {
+                Termination::Checkmate(mover)
+            } else {
+                Termination::Stalemate
+            });
+        }
+
+        if self.pos.has_repeated() {
+            Some(Termination::Repetition)
+        } else if self.pos.is_fifty_move_draw() {
+            Some(Termination::FiftyMoves)
+        } else if is_dead_position(&self.pos) {
+            Some(Termination::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Every move of the game, in UCI notation.
+    pub fn to_uci(&self) -> Vec<UciMove> {
+        self.replay(|pos, m| UciMove::from_move(m, pos))
+    }
+
+    /// Every move of the game, in SAN.
+    pub fn to_san(&self) -> Vec<String> {
+        self.replay(move_to_san)
+    }
+
+    fn replay<T>(&self, mut f: impl FnMut(&mut Position, Move) -> T) -> Vec<T> {
+        let mut pos = self.start.clone();
+
+        self.moves
+            .iter()
+            .map(|m| {
+                let ret = f(&mut pos, *m);
+                pos.make_move(*m).consume();
+                pos.forget_history();
+                ret
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parsers::uci_move::{parse_uci_move, UciMove},
+        piece::Colour,
+        position::Position,
+    };
+
+    use super::{Game, Termination};
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::default();
+
+        for m in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            game.make_san_move(m).unwrap();
+        }
+        assert!(game.make_san_move("Bb5").is_err());
+
+        let after_nf3 =
+            Position::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+                .unwrap();
+
+        game.goto(3).unwrap();
+        assert_eq!(game.position().hash(), after_nf3.hash());
+        assert!(game.undo());
+        assert!(game.redo());
+        assert_eq!(game.position().hash(), after_nf3.hash());
+        assert!(game.goto(6).is_err());
+
+        // A new move replaces those undone.
+        game.make_san_move("d6").unwrap();
+        assert_eq!(game.to_san(), ["e4", "e5", "Nf3", "d6"]);
+        assert!(!game.redo());
+
+        while game.undo() {}
+        assert_eq!(game.ply(), 0);
+        assert_eq!(game.to_uci()[0], UciMove::from(game.moves()[0]));
+    }
+
+    #[test]
+    fn termination() {
+        let mut game = Game::default();
+
+        for m in ["f3", "e5", "g4", "Qh4#"] {
+            assert_eq!(game.termination(), None);
+            game.make_san_move(m).unwrap();
+        }
+
+        let t = game.termination().unwrap();
+        assert_eq!(t, Termination::Checkmate(Colour::White));
+        assert_eq!(t.winner(), Some(Colour::Black));
+
+        let mut game = Game::default();
+        for m in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"] {
+            game.make_san_move(m).unwrap();
+        }
+        assert_eq!(game.termination(), Some(Termination::Repetition));
+
+        for (fen, dead) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/4KB2 w - - 0 1", true),
+            ("4kn2/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("2b1k3/8/8/8/8/8/8/3BKB2 w - - 0 1", true),
+            // Mate is possible, if unlikely.
+            ("4k3/8/8/8/8/8/8/4KNN1 w - - 0 1", false),
+            ("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1", false),
+            ("4kn2/8/8/8/8/8/8/4KB2 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/P7/4K3 w - - 0 1", false),
+        ] {
+            let mut game = Game::from_fen(fen).unwrap();
+            let expected = dead.then_some(Termination::InsufficientMaterial);
+            assert_eq!(game.termination(), expected, "{fen}");
+        }
+
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn long_game() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut hashes = vec![game.position().hash()];
+
+        // Far longer than the position's own history could hold.
+        for _ in 0..200 {
+            for m in ["a1a2", "e8d8", "a2a1", "d8e8"] {
+                game.make_uci_move(parse_uci_move(m).unwrap().1).unwrap();
+                hashes.push(game.position().hash());
+            }
+        }
+
+        assert_eq!(game.ply(), 800);
+        assert_eq!(game.termination(), Some(Termination::Repetition));
+        assert_eq!(game.to_san().len(), 800);
+
+        let end = game.position().hash();
+        assert!(game.undo());
+        assert_eq!(game.ply(), 799);
+        assert_eq!(game.termination(), Some(Termination::Repetition));
+        assert!(game.redo());
+        assert_eq!(game.position().hash(), end);
+
+        // Back to, and either side of, the positions kept along the way.
+        for ply in [641, 640, 639, 700, 63, 64, 0] {
+            game.goto(ply).unwrap();
+            assert_eq!(game.position().hash(), hashes[ply], "{ply}");
+        }
+
+        game.goto(1).unwrap();
+        assert_eq!(game.termination(), None);
+        assert!(game.redo());
+    }
+}
@@ -1,5 +1,9 @@
+pub mod bench;
+pub mod game;
 pub mod mmove;
+pub mod options;
 pub mod parsers;
 pub mod piece;
 pub mod position;
 pub mod search;
+pub mod selfplay;
@@ -1,8 +1,11 @@
 use std::{fmt::Debug, marker::PhantomData, mem::MaybeUninit, ptr::addr_of_mut};
 
-use crate::{piece::Piece, position::locus::Locus};
+use crate::{
+    piece::{Piece, PieceKind},
+    position::{locus::Locus, Position},
+};
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum CastlingMoveType {
     Queenside,
     Kingside,
@@ -47,7 +50,7 @@ impl Move {
     // Compute MVV-LVA for a given move.
     pub fn mvv_lva(self) -> i32 {
         match self.capture {
-            Some(captured) => MVV_LVA[self.piece.to_idx() as usize][captured.to_idx() as usize],
+            Some(captured) => MVV_LVA[self.piece.to_idx()][captured.to_idx()],
             None => 0,
         }
     }
@@ -69,6 +72,98 @@ impl Debug for Move {
     }
 }
 
+/// A move packed into 16 bits: the source square in the low six, then the
+/// destination square, then four bits of flags. The pieces moved and taken
+/// are left out, so a position is needed to unpack it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    const QUIET: u16 = 0;
+    const DOUBLE_PUSH: u16 = 1;
+    const KINGSIDE_CASTLE: u16 = 2;
+    const QUEENSIDE_CASTLE: u16 = 3;
+    const CAPTURE: u16 = 4;
+    const EN_PASSANT: u16 = 5;
+    // Promotions add the kind promoted to, less a knight, and may also be
+    // captures.
+    const PROMOTION: u16 = 8;
+
+    pub fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn src(self) -> Locus {
+        Locus::from_idx((self.0 & 0x3f) as u8).unwrap()
+    }
+
+    pub fn dst(self) -> Locus {
+        Locus::from_idx((self.0 >> 6 & 0x3f) as u8).unwrap()
+    }
+
+    fn flags(self) -> u16 {
+        self.0 >> 12
+    }
+
+    /// Unpack the move as a move of `pos`, returning `None` if there's no
+    /// piece of the side to move on the source square, or nothing to take on
+    /// the destination of a capture. The move may still not be legal.
+    pub fn to_move(self, pos: &Position) -> Option<Move> {
+        let (src, dst, flags) = (self.src(), self.dst(), self.flags());
+        let piece = pos
+            .piece_at_loc(src)
+            .filter(|x| x.colour() == pos.to_play())?;
+        let mut builder = MoveBuilder::new(piece, src).with_dst(dst);
+
+        if flags & Self::CAPTURE != 0 && flags != Self::EN_PASSANT {
+            builder = builder.with_capture(pos.piece_at_loc(dst)?);
+        }
+
+        Some(
+            match flags {
+                Self::QUIET | Self::CAPTURE => builder,
+                Self::DOUBLE_PUSH => builder.is_double_pawn_push(),
+                Self::KINGSIDE_CASTLE => builder.is_castling_move(CastlingMoveType::Kingside),
+                Self::QUEENSIDE_CASTLE => builder.is_castling_move(CastlingMoveType::Queenside),
+                Self::EN_PASSANT => builder.is_en_passant_capture(),
+                x if x & Self::PROMOTION != 0 => {
+                    let kind = PieceKind::try_from((x & 3) as u8 + PieceKind::Knight as u8).ok()?;
+                    builder.with_pawn_promotion(Piece::new(kind, piece.colour()))
+                }
+                _ => return None,
+            }
+            .build(),
+        )
+    }
+}
+
+impl From<Move> for PackedMove {
+    fn from(m: Move) -> Self {
+        let capture = if m.capture.is_some() {
+            Self::CAPTURE
+        } else {
+            0
+        };
+
+        let flags = match m.kind {
+            MoveType::Normal => capture,
+            MoveType::DoublePPush => Self::DOUBLE_PUSH,
+            MoveType::EnPassant => Self::EN_PASSANT,
+            MoveType::Castle(CastlingMoveType::Kingside) => Self::KINGSIDE_CASTLE,
+            MoveType::Castle(CastlingMoveType::Queenside) => Self::QUEENSIDE_CASTLE,
+            MoveType::Promote(p) => {
+                Self::PROMOTION | capture | (p.kind() as u16 - PieceKind::Knight as u16)
+            }
+        };
+
+        Self(m.src.to_idx() as u16 | (m.dst.to_idx() as u16) << 6 | flags << 12)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct NeedsDst;
 
@@ -170,3 +265,36 @@ impl MoveBuilder<HasDst> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::position::{movegen::MoveGen, Position};
+
+    use super::PackedMove;
+
+    #[test]
+    fn packed_move() {
+        // Castling both ways, en passant, and promotions with and without
+        // captures.
+        for fen in [
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "1r2k3/P1P5/8/8/8/8/1p1p4/R3K3 w Q - 0 1",
+            "r3k3/6P1/8/8/8/8/1pp5/R3K3 b Qq - 0 1",
+            "nrbbqkrn/pppppppp/8/8/8/8/PPPPPPPP/NRBBQKRN w GBgb - 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+
+            for m in MoveGen::legal_moves(&mut pos) {
+                let packed = PackedMove::from(m);
+
+                assert!(packed.to_move(&pos) == Some(m), "{fen}: {m:?}");
+                assert_eq!(PackedMove::from_bits(packed.to_bits()), packed);
+            }
+        }
+
+        // Nothing of the side to move's on e4.
+        let e4 = PackedMove::from_bits(28 | 36 << 6);
+        assert!(e4.to_move(&Position::default()).is_none());
+    }
+}
@@ -0,0 +1,367 @@
+//! Options a GUI can set with UCI's `setoption`, each advertised in the reply
+//! to `uci` along with its type, default and bounds.
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Result};
+use strum::IntoEnumIterator;
+
+use crate::{
+    piece::PieceKind,
+    position::eval::{EvalParams, DEFAULT_EVAL_PARAMS},
+    search::{SearchParams, DEFAULT_HASH_MB, DEFAULT_MOVE_OVERHEAD},
+};
+
+pub const HASH: &str = "Hash";
+pub const MULTI_PV: &str = "MultiPV";
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+pub const CHESS960: &str = "UCI_Chess960";
+/// Whether the GUI may ask the engine to ponder, and so whether the engine
+/// suggests a move to ponder on with its best move.
+pub const PONDER: &str = "Ponder";
+pub const CONTEMPT: &str = "Contempt";
+pub const ANALYSE_MODE: &str = "UCI_AnalyseMode";
+/// Where to write the tree searched by the last iteration, as Graphviz if it
+/// ends in `.dot` or `.gv`, or as JSON. Nothing is traced when it's empty.
+pub const TRACE_FILE: &str = "Trace File";
+pub const TRACE_DEPTH: &str = "Trace Depth";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    Combo { default: String, vars: Vec<String> },
+    String { default: String },
+    Button,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    /// The value of a combo or string option.
+    String(String),
+    Button,
+}
+
+#[derive(Clone, Debug)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+    value: OptionValue,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Options {
+    options: Vec<EngineOption>,
+}
+
+// The option holding the material value of `kind`, e.g. `KnightValue`.
+fn material_option(kind: PieceKind) -> String {
+    format!("{kind:?}Value")
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The options rmace itself understands.
+    pub fn engine() -> Self {
+        let mut ret = Self::new()
+            .with_spin(HASH, DEFAULT_HASH_MB as i64, 1, 4096)
+            .with_spin(MULTI_PV, 1, 1, 256)
+            .with_spin(
+                MOVE_OVERHEAD,
+                DEFAULT_MOVE_OVERHEAD.as_millis() as i64,
+                0,
+                5000,
+            )
+            .with_check(CHESS960, false)
+            .with_check(PONDER, false)
+            .with_spin(CONTEMPT, 0, -100, 100)
+            .with_check(ANALYSE_MODE, false)
+            .with_string(TRACE_FILE, "")
+            .with_spin(TRACE_DEPTH, 4, 0, 99);
+
+        // The king's value never matters, as it's never captured.
+        for kind in PieceKind::iter().filter(|x| *x != PieceKind::King) {
+            ret = ret.with_spin(
+                &material_option(kind),
+                DEFAULT_EVAL_PARAMS.material(kind) as i64,
+                0,
+                2000,
+            );
+        }
+
+        // Every search parameter, under its own name, for an SPSA tuner to
+        // set.
+        #[cfg(feature = "spsa")]
+        for spec in SearchParams::SPECS {
+            ret = ret.with_spin(
+                spec.name,
+                spec.default as i64,
+                spec.min as i64,
+                spec.max as i64,
+            );
+        }
+
+        ret
+    }
+
+    fn with_option(mut self, name: &str, kind: OptionKind) -> Self {
+        let value = match kind {
+            OptionKind::Spin { default, .. } => OptionValue::Spin(default),
+            OptionKind::Check { default } => OptionValue::Check(default),
+            OptionKind::Combo { ref default, .. } | OptionKind::String { ref default } => {
+                OptionValue::String(default.clone())
+            }
+            OptionKind::Button => OptionValue::Button,
+        };
+
+        self.options.push(EngineOption {
+            name: name.to_string(),
+            kind,
+            value,
+        });
+        self
+    }
+
+    pub fn with_spin(self, name: &str, default: i64, min: i64, max: i64) -> Self {
+        self.with_option(name, OptionKind::Spin { default, min, max })
+    }
+
+    pub fn with_check(self, name: &str, default: bool) -> Self {
+        self.with_option(name, OptionKind::Check { default })
+    }
+
+    pub fn with_combo(self, name: &str, default: &str, vars: &[&str]) -> Self {
+        self.with_option(
+            name,
+            OptionKind::Combo {
+                default: default.to_string(),
+                vars: vars.iter().map(|x| x.to_string()).collect(),
+            },
+        )
+    }
+
+    pub fn with_string(self, name: &str, default: &str) -> Self {
+        self.with_option(
+            name,
+            OptionKind::String {
+                default: default.to_string(),
+            },
+        )
+    }
+
+    pub fn with_button(self, name: &str) -> Self {
+        self.with_option(name, OptionKind::Button)
+    }
+
+    // Option names aren't case sensitive.
+    fn find(&self, name: &str) -> Option<&EngineOption> {
+        self.options
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Set the option `name` from the `value` of a `setoption` command,
+    /// which buttons don't have.
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        let option = self
+            .options
+            .iter_mut()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("No such option {name}"))?;
+
+        option.value = match (&option.kind, value) {
+            (OptionKind::Button, _) => OptionValue::Button,
+            (_, None) => bail!("Option {} needs a value", option.name),
+            (OptionKind::Spin { min, max, .. }, Some(x)) => {
+                let v: i64 = x
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid value {x} for {}", option.name))?;
+
+                if v < *min || v > *max {
+                    bail!("{} must be between {min} and {max}", option.name);
+                }
+
+                OptionValue::Spin(v)
+            }
+            (OptionKind::Check { .. }, Some(x)) => match x {
+                "true" => OptionValue::Check(true),
+                "false" => OptionValue::Check(false),
+                _ => bail!("Invalid value {x} for {}", option.name),
+            },
+            (OptionKind::Combo { vars, .. }, Some(x)) => {
+                let var = vars
+                    .iter()
+                    .find(|v| v.eq_ignore_ascii_case(x))
+                    .ok_or_else(|| anyhow!("Invalid value {x} for {}", option.name))?;
+
+                OptionValue::String(var.clone())
+            }
+            (OptionKind::String { .. }, Some(x)) => {
+                OptionValue::String(if x == "<empty>" { "" } else { x }.to_string())
+            }
+        };
+
+        Ok(())
+    }
+
+    pub fn spin(&self, name: &str) -> Option<i64> {
+        match self.find(name)?.value {
+            OptionValue::Spin(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn check(&self, name: &str) -> Option<bool> {
+        match self.find(name)?.value {
+            OptionValue::Check(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The value of a combo or string option.
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.find(name)?.value {
+            OptionValue::String(ref x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The evaluation parameters, with the material values these options
+    /// give.
+    pub fn eval_params(&self) -> EvalParams {
+        let mut params = EvalParams::default();
+
+        for kind in PieceKind::iter() {
+            if let Some(x) = self.spin(&material_option(kind)) {
+                params.set_material(kind, x as i32);
+            }
+        }
+
+        params
+    }
+
+    /// The search parameters, with the values of any of them these options
+    /// give.
+    pub fn search_params(&self) -> SearchParams {
+        let mut params = SearchParams::default();
+
+        for spec in SearchParams::SPECS {
+            if let Some(x) = self.spin(spec.name) {
+                params.set(spec.name, x as i32).unwrap();
+            }
+        }
+
+        params
+    }
+}
+
+impl Display for OptionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "type spin default {default} min {min} max {max}")
+            }
+            OptionKind::Check { default } => write!(f, "type check default {default}"),
+            OptionKind::Combo { default, vars } => {
+                write!(f, "type combo default {default}")?;
+                vars.iter().try_for_each(|x| write!(f, " var {x}"))
+            }
+            OptionKind::String { default } if default.is_empty() => {
+                write!(f, "type string default <empty>")
+            }
+            OptionKind::String { default } => write!(f, "type string default {default}"),
+            OptionKind::Button => write!(f, "type button"),
+        }
+    }
+}
+
+/// Every option as UCI's `option` command, one per line.
+impl Display for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.options
+            .iter()
+            .try_for_each(|x| writeln!(f, "option name {} {}", x.name, x.kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::piece::PieceKind;
+
+    use super::{Options, HASH, MULTI_PV};
+
+    fn options() -> Options {
+        Options::new()
+            .with_spin("Threads", 1, 1, 64)
+            .with_check("Ponder", false)
+            .with_combo("Style", "Normal", &["Solid", "Normal", "Risky"])
+            .with_string("SyzygyPath", "")
+            .with_button("Clear Hash")
+    }
+
+    #[test]
+    fn advertise() {
+        assert_eq!(
+            options().to_string(),
+            "option name Threads type spin default 1 min 1 max 64\n\
+             option name Ponder type check default false\n\
+             option name Style type combo default Normal var Solid var Normal var Risky\n\
+             option name SyzygyPath type string default <empty>\n\
+             option name Clear Hash type button\n"
+        );
+    }
+
+    #[test]
+    fn set() {
+        let mut opts = options();
+
+        opts.set("threads", Some("8")).unwrap();
+        opts.set("Ponder", Some("true")).unwrap();
+        opts.set("Style", Some("risky")).unwrap();
+        opts.set("SyzygyPath", Some("/tb/wdl")).unwrap();
+        opts.set("Clear Hash", None).unwrap();
+
+        assert_eq!(opts.spin("Threads"), Some(8));
+        assert_eq!(opts.check("Ponder"), Some(true));
+        assert_eq!(opts.string("Style"), Some("Risky"));
+        assert_eq!(opts.string("SyzygyPath"), Some("/tb/wdl"));
+
+        // Invalid values leave the option as it was.
+        assert!(opts.set("Threads", Some("65")).is_err());
+        assert!(opts.set("Threads", Some("two")).is_err());
+        assert!(opts.set("Threads", None).is_err());
+        assert!(opts.set("Ponder", Some("yes")).is_err());
+        assert!(opts.set("Style", Some("Wild")).is_err());
+        assert!(opts.set("Hash", Some("16")).is_err());
+        assert_eq!(opts.spin("Threads"), Some(8));
+        assert_eq!(opts.string("Style"), Some("Risky"));
+    }
+
+    #[test]
+    fn engine_options() {
+        let mut opts = Options::engine();
+
+        assert!(opts.spin(HASH).is_some());
+        assert_eq!(opts.spin(MULTI_PV), Some(1));
+        assert_eq!(opts.eval_params(), Default::default());
+
+        opts.set("KnightValue", Some("325")).unwrap();
+        assert_eq!(opts.eval_params().material(PieceKind::Knight), 325);
+
+        assert_eq!(opts.search_params(), Default::default());
+    }
+
+    #[cfg(feature = "spsa")]
+    #[test]
+    fn search_params() {
+        let mut opts = Options::engine();
+
+        opts.set("moves_to_go", Some("40")).unwrap();
+        assert_eq!(opts.search_params().moves_to_go, 40);
+    }
+}
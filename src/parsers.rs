@@ -1,2 +1,7 @@
+pub mod epd;
 pub mod fen;
+pub mod perft;
+pub mod pgn;
+pub mod san;
+pub mod uci;
 pub mod uci_move;
@@ -0,0 +1,139 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_while},
+    character::complete::{alpha1, char, multispace0, multispace1},
+    combinator::{map, opt, recognize},
+    multi::many0,
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+use super::fen::{parse_fen, Fen};
+
+#[derive(Debug, PartialEq)]
+pub struct EpdOp {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Epd {
+    pub fen: Fen,
+    pub ops: Vec<EpdOp>,
+}
+
+impl Epd {
+    /// Find the first operation with the given opcode.
+    pub fn op(&self, opcode: &str) -> Option<&EpdOp> {
+        self.ops.iter().find(|x| x.opcode == opcode)
+    }
+
+    /// The operands of the first operation with the given opcode, or an empty
+    /// slice if there is no such operation.
+    pub fn operands(&self, opcode: &str) -> &[String] {
+        self.op(opcode)
+            .map(|x| x.operands.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+fn parse_opcode(input: &str) -> IResult<&str, String> {
+    map(
+        recognize(tuple((
+            alpha1,
+            take_while(|c: char| c.is_alphanumeric() || c == '_'),
+        ))),
+        String::from,
+    )(input)
+}
+
+fn parse_operand(input: &str) -> IResult<&str, String> {
+    alt((
+        map(
+            delimited(char('"'), take_while(|c| c != '"'), char('"')),
+            String::from,
+        ),
+        map(
+            take_till1(|c: char| c.is_whitespace() || c == ';'),
+            String::from,
+        ),
+    ))(input)
+}
+
+// Operations are normally terminated by a ';', however perft suites commonly
+// place the ';' before each operation instead, so accept either.
+fn parse_op(input: &str) -> IResult<&str, EpdOp> {
+    map(
+        terminated(
+            tuple((
+                preceded(
+                    tuple((multispace0, opt(tag(";")), multispace0)),
+                    parse_opcode,
+                ),
+                many0(preceded(multispace1, parse_operand)),
+            )),
+            tuple((multispace0, opt(tag(";")))),
+        ),
+        |(opcode, operands)| EpdOp { opcode, operands },
+    )(input)
+}
+
+pub fn parse_epd(input: &str) -> IResult<&str, Epd> {
+    map(
+        terminated(tuple((parse_fen, many0(parse_op))), multispace0),
+        |(fen, ops)| Epd { fen, ops },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::locus::loc;
+
+    use super::{parse_epd, EpdOp};
+
+    #[test]
+    fn standard_ops() {
+        let (_, epd) = parse_epd(
+            "r1b1k2r/pp3p2/6p1/3pq3/1P4P1/P2BPQp1/5PP1/R4RK1 b kq - bm Qh2+; id \"mate.001\";",
+        )
+        .unwrap();
+
+        assert_eq!(epd.operands("bm"), ["Qh2+"]);
+        assert_eq!(epd.operands("id"), ["mate.001"]);
+        assert!(epd.op("am").is_none());
+    }
+
+    #[test]
+    fn perft_ops() {
+        let (rest, epd) =
+            parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400\n")
+                .unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(
+            epd.ops,
+            vec![
+                EpdOp {
+                    opcode: "D1".to_string(),
+                    operands: vec!["20".to_string()]
+                },
+                EpdOp {
+                    opcode: "D2".to_string(),
+                    operands: vec!["400".to_string()]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn en_passant_and_multiple_operands() {
+        let (_, epd) = parse_epd(
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 bm exd6 Nf3; c9 \"1-0\";",
+        )
+        .unwrap();
+
+        assert_eq!(epd.fen.en_passant, Some(loc!(d 6)));
+        assert_eq!(epd.operands("bm"), ["exd6", "Nf3"]);
+        assert_eq!(epd.operands("c9"), ["1-0"]);
+    }
+}
@@ -3,29 +3,46 @@ use std::num::ParseIntError;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::one_of,
-    combinator::{map, map_res},
+    character::complete::{digit1, one_of},
+    combinator::{map, map_res, opt},
     multi::{many1, separated_list1},
-    sequence::tuple,
+    sequence::{preceded, tuple},
     IResult,
 };
 
+use strum::IntoEnumIterator;
+
 use crate::{
+    mmove::CastlingMoveType,
     piece::{Colour, Piece, PieceKind},
-    position::castling_rights::CastlingRights,
+    position::locus::{File, Locus},
 };
 
-#[derive(Debug)]
+use super::uci_move::parse_locus;
+
+#[derive(Clone, Debug)]
 pub enum FenElement {
     Piece(Piece),
     Space(u8),
 }
 
-#[derive(Debug)]
+/// A castling availability of a FEN string. `K` and `Q` give the outermost
+/// rook on that side of the king, as in X-FEN, and a file letter gives the
+/// rook's file, as in Shredder-FEN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CastlingFlag {
+    Outermost(Colour, CastlingMoveType),
+    Rook(Colour, File),
+}
+
+#[derive(Clone, Debug)]
 pub struct Fen {
     pub board: Vec<Vec<FenElement>>,
     pub colour: Colour,
-    pub castling_rights: CastlingRights,
+    pub castling: Vec<CastlingFlag>,
+    pub en_passant: Option<Locus>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
 }
 
 fn parse_space(input: &str) -> IResult<&str, FenElement> {
@@ -78,25 +95,35 @@ fn parse_colour(input: &str) -> IResult<&str, Colour> {
     })(input)
 }
 
-fn parse_castling_rights(input: &str) -> IResult<&str, CastlingRights> {
-    let mut rights = CastlingRights::empty();
-
+fn parse_castling_rights(input: &str) -> IResult<&str, Vec<CastlingFlag>> {
     alt((
-        map(tag("-"), move |_| rights),
-        map(many1(one_of("kqKQ")), move |x| {
-            x.iter().for_each(|r| match *r {
-                'k' => rights[Colour::Black].set_king_side(),
-                'q' => rights[Colour::Black].set_queen_side(),
-                'K' => rights[Colour::White].set_king_side(),
-                'Q' => rights[Colour::White].set_queen_side(),
-                _ => unreachable!("Should only parse 'kqKQ'"),
-            });
-
-            rights
-        }),
+        map(tag("-"), |_| Vec::new()),
+        many1(map(one_of("kqKQabcdefghABCDEFGH"), |x| {
+            let colour = if x.is_ascii_uppercase() {
+                Colour::White
+            } else {
+                Colour::Black
+            };
+
+            match x.to_ascii_lowercase() {
+                'k' => CastlingFlag::Outermost(colour, CastlingMoveType::Kingside),
+                'q' => CastlingFlag::Outermost(colour, CastlingMoveType::Queenside),
+                f => {
+                    CastlingFlag::Rook(colour, File::iter().nth((f as u8 - b'a') as usize).unwrap())
+                }
+            }
+        })),
     ))(input)
 }
 
+fn parse_en_passant(input: &str) -> IResult<&str, Option<Locus>> {
+    alt((map(tag("-"), |_| None), map(parse_locus, Some)))(input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, u32> {
+    map_res(digit1, str::parse)(input)
+}
+
 pub fn parse_fen(input: &str) -> IResult<&str, Fen> {
     map(
         tuple((
@@ -105,11 +132,19 @@ pub fn parse_fen(input: &str) -> IResult<&str, Fen> {
             parse_colour,
             tag(" "),
             parse_castling_rights,
+            opt(preceded(tag(" "), parse_en_passant)),
+            opt(tuple((
+                preceded(tag(" "), parse_number),
+                preceded(tag(" "), parse_number),
+            ))),
         )),
-        |(b, _, c, _, cr)| Fen {
+        |(b, _, c, _, cr, ep, clocks)| Fen {
             board: b,
             colour: c,
-            castling_rights: cr,
+            castling: cr,
+            en_passant: ep.flatten(),
+            halfmove_clock: clocks.map(|x| x.0).unwrap_or(0),
+            fullmove_number: clocks.map(|x| x.1).unwrap_or(1),
         },
     )(input)
 }
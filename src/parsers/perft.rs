@@ -0,0 +1,40 @@
+use nom::{
+    bytes::complete::tag,
+    character::complete::{digit1, space0},
+    combinator::map_res,
+    sequence::{terminated, tuple},
+    IResult,
+};
+
+use super::uci_move::{parse_uci_move, UciMove};
+
+/// Parse a line of perft divide output, e.g. `e2e4: 13160`.
+pub fn parse_divide_line(input: &str) -> IResult<&str, (UciMove, u64)> {
+    map_res(
+        tuple((parse_uci_move, tag(":"), space0, terminated(digit1, space0))),
+        |(m, _, _, n)| -> Result<(UciMove, u64), std::num::ParseIntError> { Ok((m, n.parse()?)) },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{piece::PieceKind, position::locus::loc};
+
+    use super::parse_divide_line;
+
+    #[test]
+    fn divide_line() {
+        let (_, (m, n)) = parse_divide_line("e2e4: 13160").unwrap();
+
+        assert_eq!(m.src, loc!(e 2));
+        assert_eq!(m.dst, loc!(e 4));
+        assert_eq!(n, 13160);
+
+        let (_, (m, n)) = parse_divide_line("a7a8q:1").unwrap();
+
+        assert_eq!(m.promote, Some(PieceKind::Queen));
+        assert_eq!(n, 1);
+
+        assert!(parse_divide_line("Nodes searched: 20").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, tag, take_until, take_while1},
+    character::complete::{char, digit1, multispace1, not_line_ending},
+    combinator::{map, recognize, value},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, separated_pair},
+    Finish, IResult,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line, in SAN.
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone)]
+enum Token {
+    Move(String),
+    Result(String),
+    Skip,
+}
+
+// Whitespace, comments and escaped lines.
+fn skip(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), delimited(char('{'), take_until("}"), char('}'))),
+            value((), pair(char(';'), not_line_ending)),
+            value((), pair(char('%'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn parse_tag_value(input: &str) -> IResult<&str, String> {
+    alt((
+        escaped_transform(
+            is_not("\\\""),
+            '\\',
+            alt((value("\\", tag("\\")), value("\"", tag("\"")))),
+        ),
+        map(tag(""), String::from),
+    ))(input)
+}
+
+fn parse_tag(input: &str) -> IResult<&str, (String, String)> {
+    delimited(
+        pair(char('['), skip),
+        separated_pair(
+            map(
+                take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+                String::from,
+            ),
+            skip,
+            delimited(char('"'), parse_tag_value, char('"')),
+        ),
+        pair(skip, char(']')),
+    )(input)
+}
+
+// Variations may be nested, and are skipped entirely.
+fn parse_variation(input: &str) -> IResult<&str, Token> {
+    value(
+        Token::Skip,
+        delimited(
+            char('('),
+            many0(preceded(skip, parse_movetext_token)),
+            pair(skip, char(')')),
+        ),
+    )(input)
+}
+
+fn parse_movetext_token(input: &str) -> IResult<&str, Token> {
+    alt((
+        map(
+            alt((tag("1-0"), tag("0-1"), tag("1/2-1/2"), tag("*"))),
+            |x: &str| Token::Result(x.to_string()),
+        ),
+        value(Token::Skip, recognize(pair(digit1, many1(char('.'))))),
+        value(Token::Skip, preceded(char('$'), digit1)),
+        parse_variation,
+        map(
+            take_while1(|c: char| !c.is_whitespace() && !"(){};[".contains(c)),
+            |x: &str| Token::Move(x.to_string()),
+        ),
+    ))(input)
+}
+
+fn parse_game(input: &str) -> IResult<&str, PgnGame> {
+    let (mut input, tags) = many0(preceded(skip, parse_tag))(input)?;
+    let mut moves = Vec::new();
+    let mut result = None;
+
+    // Read moves until the result, or the start of the next game.
+    while result.is_none() {
+        let (rest, _) = skip(input)?;
+
+        if rest.is_empty() || rest.starts_with('[') {
+            input = rest;
+            break;
+        }
+
+        let (rest, token) = parse_movetext_token(rest)?;
+        input = rest;
+
+        match token {
+            Token::Move(m) => moves.push(m),
+            Token::Result(r) => result = Some(r),
+            Token::Skip => {}
+        }
+    }
+
+    Ok((
+        input,
+        PgnGame {
+            tags,
+            moves,
+            result: result.unwrap_or("*".to_string()),
+        },
+    ))
+}
+
+/// Parse every game of a PGN database.
+pub fn parse_pgn(input: &str) -> Result<Vec<PgnGame>> {
+    let mut games = Vec::new();
+    let mut input = input;
+
+    loop {
+        let (rest, _) = skip(input)
+            .finish()
+            .map_err(|e| anyhow!("Could not parse PGN: {e}"))?;
+
+        if rest.is_empty() {
+            return Ok(games);
+        }
+
+        let (rest, game) = parse_game(rest).finish().map_err(|_| {
+            anyhow!(
+                "Could not parse PGN near: {}",
+                rest.chars().take(40).collect::<String>()
+            )
+        })?;
+
+        if rest.len() == input.len() {
+            return Err(anyhow!(
+                "Could not parse PGN near: {}",
+                rest.chars().take(40).collect::<String>()
+            ));
+        }
+
+        games.push(game);
+        input = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pgn;
+
+    #[test]
+    fn silver_suite_game() {
+        let games = parse_pgn(
+            r#"[Event "?"]
+[White "Silver Suite"]
+[Black "Bird's Opening"]
+[Result "*"]
+
+1. f4 d5 2. Nf3 Nf6 3. b3 g6 4. Bb2 Bg7 5. e3 O-O 6. Be2 c5 7. O-O Nc6 8. Ne5 *
+
+[Event "?"]
+[Black "Reti Opening"]
+
+1. Nf3 Nf6 2. g3 g6 *
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Black"), Some("Bird's Opening"));
+        assert_eq!(games[0].moves.len(), 15);
+        assert_eq!(games[0].moves[9], "O-O");
+        assert_eq!(games[0].result, "*");
+        assert_eq!(games[1].moves, ["Nf3", "Nf6", "g3", "g6"]);
+    }
+
+    #[test]
+    fn comments_and_variations() {
+        let games = parse_pgn(
+            r#"[Event "Escaped \"quotes\""]
+1.e4 {best by test} e5 (1...c5 2.Nf3 (2.c3) d6) 2.Nf3 $1 Nc6 ; a comment
+3.Bb5 a6 1/2-1/2"#,
+        )
+        .unwrap();
+
+        assert_eq!(games[0].tag("Event"), Some("Escaped \"quotes\""));
+        assert_eq!(games[0].moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(games[0].result, "1/2-1/2");
+    }
+}
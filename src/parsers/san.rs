@@ -0,0 +1,313 @@
+//! Standard algebraic notation, as used by EPD and PGN.
+
+use std::fmt::Display;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, one_of},
+    combinator::{map, opt, value},
+    multi::many0,
+    sequence::{preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::{
+    mmove::{CastlingMoveType, Move, MoveType},
+    piece::PieceKind,
+    position::{
+        locus::{File, Locus, Rank},
+        movegen::MoveGen,
+        Position,
+    },
+};
+
+use super::uci_move::{parse_file, parse_locus, parse_rank};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum San {
+    Castle(CastlingMoveType),
+    Move {
+        kind: PieceKind,
+        src_file: Option<File>,
+        src_rank: Option<Rank>,
+        capture: bool,
+        dst: Locus,
+        promote: Option<PieceKind>,
+    },
+}
+
+fn parse_piece(input: &str) -> IResult<&str, PieceKind> {
+    map(one_of("NBRQK"), |x| match x {
+        'N' => PieceKind::Knight,
+        'B' => PieceKind::Bishop,
+        'R' => PieceKind::Rook,
+        'Q' => PieceKind::Queen,
+        'K' => PieceKind::King,
+        _ => unreachable!("Should only parse 'NBRQK'"),
+    })(input)
+}
+
+fn parse_castle(input: &str) -> IResult<&str, San> {
+    map(
+        alt((
+            value(
+                CastlingMoveType::Queenside,
+                alt((tag("O-O-O"), tag("0-0-0"))),
+            ),
+            value(CastlingMoveType::Kingside, alt((tag("O-O"), tag("0-0")))),
+        )),
+        San::Castle,
+    )(input)
+}
+
+fn parse_piece_move(input: &str) -> IResult<&str, San> {
+    map(
+        tuple((
+            opt(parse_piece),
+            opt(parse_file),
+            opt(parse_rank),
+            opt(char('x')),
+            parse_locus,
+            opt(preceded(opt(char('=')), parse_piece)),
+        )),
+        |(kind, src_file, src_rank, capture, dst, promote)| San::Move {
+            kind: kind.unwrap_or(PieceKind::Pawn),
+            src_file,
+            src_rank,
+            capture: capture.is_some(),
+            dst,
+            promote,
+        },
+    )(input)
+}
+
+// Without a disambiguation, the file and rank of the destination are parsed as
+// the source, so try the longest form first and fall back to the shortest.
+fn parse_plain_move(input: &str) -> IResult<&str, San> {
+    map(
+        tuple((
+            opt(parse_piece),
+            opt(char('x')),
+            parse_locus,
+            opt(preceded(opt(char('=')), parse_piece)),
+        )),
+        |(kind, capture, dst, promote)| San::Move {
+            kind: kind.unwrap_or(PieceKind::Pawn),
+            src_file: None,
+            src_rank: None,
+            capture: capture.is_some(),
+            dst,
+            promote,
+        },
+    )(input)
+}
+
+/// Parse a move in SAN, ignoring any check, mate or annotation suffix.
+pub fn parse_san(input: &str) -> IResult<&str, San> {
+    terminated(
+        alt((parse_castle, parse_piece_move, parse_plain_move)),
+        many0(one_of("+#!?")),
+    )(input)
+}
+
+impl San {
+    /// Find the legal move of `pos` that this SAN describes. Returns `None`
+    /// if no move or more than one move matches.
+    pub fn to_move(self, pos: &mut Position) -> Option<Move> {
+        let mut matches = MoveGen::legal_moves(pos)
+            .into_iter()
+            .filter(|m| match self {
+                San::Castle(kind) => m.kind == MoveType::Castle(kind),
+                San::Move {
+                    kind,
+                    src_file,
+                    src_rank,
+                    dst,
+                    promote,
+                    ..
+                } => {
+                    let (rank, file) = m.src.to_rank_file();
+
+                    m.piece.kind() == kind
+                        && m.dst == dst
+                        && !matches!(m.kind, MoveType::Castle(_))
+                        && src_file.is_none_or(|f| f == file)
+                        && src_rank.is_none_or(|r| r == rank)
+                        && promote
+                            == match m.kind {
+                                MoveType::Promote(p) => Some(p.kind()),
+                                _ => None,
+                            }
+                }
+            });
+
+        let m = matches.next()?;
+
+        matches.next().is_none().then_some(m)
+    }
+
+    /// Describe the legal move `m` of `pos` in SAN, disambiguating it from
+    /// other moves of the same kind of piece only as much as is needed.
+    pub fn from_move(pos: &mut Position, m: Move) -> Self {
+        if let MoveType::Castle(kind) = m.kind {
+            return San::Castle(kind);
+        }
+
+        let kind = m.piece.kind();
+        let (rank, file) = m.src.to_rank_file();
+        let others: Vec<_> = MoveGen::legal_moves(pos)
+            .into_iter()
+            .filter(|x| x.piece == m.piece && x.dst == m.dst && x.src != m.src)
+            .map(|x| x.src.to_rank_file())
+            .collect();
+
+        let capture = m.capture.is_some() || m.kind == MoveType::EnPassant;
+
+        let (src_file, src_rank) = if kind == PieceKind::Pawn {
+            (capture.then_some(file), None)
+        } else if others.is_empty() {
+            (None, None)
+        } else if others.iter().all(|(_, f)| *f != file) {
+            (Some(file), None)
+        } else if others.iter().all(|(r, _)| *r != rank) {
+            (None, Some(rank))
+        } else {
+            (Some(file), Some(rank))
+        };
+
+        San::Move {
+            kind,
+            src_file,
+            src_rank,
+            capture,
+            dst: m.dst,
+            promote: match m.kind {
+                MoveType::Promote(p) => Some(p.kind()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Display for San {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            San::Castle(CastlingMoveType::Kingside) => write!(f, "O-O"),
+            San::Castle(CastlingMoveType::Queenside) => write!(f, "O-O-O"),
+            San::Move {
+                kind,
+                src_file,
+                src_rank,
+                capture,
+                dst,
+                promote,
+            } => {
+                if *kind != PieceKind::Pawn {
+                    write!(f, "{}", kind.to_string().to_ascii_uppercase())?;
+                }
+
+                if let Some(file) = src_file {
+                    write!(f, "{file}")?;
+                }
+
+                if let Some(rank) = src_rank {
+                    write!(f, "{rank}")?;
+                }
+
+                if *capture {
+                    write!(f, "x")?;
+                }
+
+                write!(f, "{dst}")?;
+
+                if let Some(p) = promote {
+                    write!(f, "={}", p.to_string().to_ascii_uppercase())?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Format the legal move `m` of `pos` in SAN, including a check or mate
+/// suffix.
+pub fn move_to_san(pos: &mut Position, m: Move) -> String {
+    let san = San::from_move(pos, m);
+    let token = pos.make_move(m);
+
+    let suffix = if MoveGen::new(pos).in_check(pos.to_play()) {
+        if MoveGen::legal_moves(pos).is_empty() {
+            "#"
+        } else {
+            "+"
+        }
+    } else {
+        ""
+    };
+
+    pos.undo_move(token);
+
+    format!("{san}{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position;
+
+    use super::{move_to_san, parse_san};
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn resolve(fen: &str, san: &str) -> Option<String> {
+        let mut pos = Position::from_fen(fen).unwrap();
+        let (rest, san) = parse_san(san).ok()?;
+
+        assert!(rest.is_empty());
+
+        let m = san.to_move(&mut pos)?;
+
+        Some(move_to_san(&mut pos, m))
+    }
+
+    #[test]
+    fn round_trip() {
+        for san in [
+            "O-O", "O-O-O", "Qxf6", "Bxa6", "Nxf7", "d6", "gxh3", "Nb1", "Kd1", "Rb1",
+        ] {
+            assert_eq!(resolve(KIWIPETE, san).as_deref(), Some(san));
+        }
+
+        // Promotions, with and without '='.
+        let fen = "2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(resolve(fen, "bxc8=Q").as_deref(), Some("bxc8=Q+"));
+        assert_eq!(resolve(fen, "b8N").as_deref(), Some("b8=N"));
+    }
+
+    #[test]
+    fn disambiguation() {
+        // Both knights can reach d2.
+        let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(resolve(fen, "Nd2"), None);
+        assert_eq!(resolve(fen, "Nbd2").as_deref(), Some("Nbd2"));
+        assert_eq!(resolve(fen, "Nfd2").as_deref(), Some("Nfd2"));
+
+        // Both rooks are on the same file.
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(resolve(fen, "R1a3").as_deref(), Some("R1a3"));
+        assert_eq!(resolve(fen, "R5a3").as_deref(), Some("R5a3"));
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(
+            resolve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "Ra8#").as_deref(),
+            Some("Ra8#")
+        );
+        assert_eq!(
+            resolve("6k1/5pp1/8/8/8/8/8/R5K1 w - - 0 1", "Ra8+!?").as_deref(),
+            Some("Ra8+")
+        );
+    }
+}
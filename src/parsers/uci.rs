@@ -0,0 +1,407 @@
+//! Commands sent to an engine by a UCI GUI. As the protocol asks, tokens
+//! which aren't understood are skipped, so a line is only rejected when a
+//! command it names has arguments which don't make sense.
+
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nom::Finish;
+
+use crate::{game::Game, piece::Colour, position::Position};
+
+use super::{
+    fen::{parse_fen, Fen},
+    uci_move::{parse_uci_move, UciMove},
+};
+
+#[derive(Debug)]
+pub enum PosSpecifier {
+    Startpos,
+    Fen(Fen),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GoSpecifier {
+    Time(Colour, Duration),
+    Inc(Colour, Duration),
+    Depth(usize),
+    MovesToGo(u32),
+    MoveTime(Duration),
+    Infinite,
+    Ponder,
+}
+
+#[derive(Debug)]
+pub enum UciCmd {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption(String, Option<String>),
+    Register,
+    NewGame,
+    Position(PosSpecifier, Vec<UciMove>),
+    Go(Vec<GoSpecifier>),
+    Stop,
+    PonderHit,
+    Quit,
+    Perft(u32),
+    Bench(Option<usize>),
+    Display,
+    Eval,
+}
+
+const COMMANDS: &[&str] = &[
+    "uci",
+    "debug",
+    "isready",
+    "setoption",
+    "register",
+    "ucinewgame",
+    "position",
+    "go",
+    "stop",
+    "ponderhit",
+    "quit",
+    "bench",
+    "d",
+    "eval",
+];
+
+impl PosSpecifier {
+    /// The position reached by playing `moves` from this one, checking that
+    /// the position is legal and that each move is legal in turn.
+    pub fn to_position(&self, moves: &[UciMove], chess960: bool) -> Result<Position> {
+        let mut pos = match self {
+            PosSpecifier::Startpos => Position::default(),
+            PosSpecifier::Fen(fen) => Position::try_from(fen.clone())?,
+        };
+
+        pos.check_legal()?;
+
+        // A Shredder-FEN may already have marked the position as Chess960.
+        pos.set_chess960(chess960 || pos.is_chess960());
+
+        // Play the moves as a game, so that however many there are, the
+        // position only keeps those which matter for repetitions.
+        let mut game = Game::new(pos);
+        for m in moves {
+            game.make_uci_move(*m)?;
+        }
+
+        Ok(game.position().clone())
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: Option<&str>) -> Result<T> {
+    let value = value.with_context(|| format!("Missing value for {name}"))?;
+
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid value for {name}: {value}"))
+}
+
+// Some GUIs send a negative time once a clock has run out.
+fn parse_msec(name: &str, value: Option<&str>) -> Result<Duration> {
+    let msec: i64 = parse_number(name, value)?;
+
+    Ok(Duration::from_millis(msec.max(0) as u64))
+}
+
+fn parse_position<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<UciCmd> {
+    let pos = match args.next() {
+        Some("startpos") => {
+            // Anything up to the moves is ignored.
+            args.by_ref().find(|x| *x == "moves");
+            PosSpecifier::Startpos
+        }
+        Some("fen") => {
+            let fen = args
+                .by_ref()
+                .take_while(|x| *x != "moves")
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (rest, parsed) = parse_fen(&fen)
+                .finish()
+                .map_err(|_| anyhow!("Invalid FEN: {fen}"))?;
+
+            if !rest.is_empty() {
+                bail!("Invalid FEN: {fen}");
+            }
+
+            PosSpecifier::Fen(parsed)
+        }
+        _ => bail!("Expected startpos or fen after position"),
+    };
+
+    let moves = args
+        .map(|x| match parse_uci_move(x).finish() {
+            Ok(("", m)) => Ok(m),
+            _ => Err(anyhow!("Invalid move: {x}")),
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(UciCmd::Position(pos, moves))
+}
+
+fn parse_go<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<UciCmd> {
+    let mut specs = Vec::new();
+
+    while let Some(token) = args.next() {
+        specs.push(match token {
+            "wtime" => GoSpecifier::Time(Colour::White, parse_msec(token, args.next())?),
+            "btime" => GoSpecifier::Time(Colour::Black, parse_msec(token, args.next())?),
+            "winc" => GoSpecifier::Inc(Colour::White, parse_msec(token, args.next())?),
+            "binc" => GoSpecifier::Inc(Colour::Black, parse_msec(token, args.next())?),
+            "movetime" => GoSpecifier::MoveTime(parse_msec(token, args.next())?),
+            "depth" => GoSpecifier::Depth(parse_number(token, args.next())?),
+            "movestogo" => GoSpecifier::MovesToGo(parse_number(token, args.next())?),
+            "infinite" => GoSpecifier::Infinite,
+            "ponder" => GoSpecifier::Ponder,
+            "perft" => return Ok(UciCmd::Perft(parse_number(token, args.next())?)),
+            // Including searchmoves, nodes and mate, which aren't supported.
+            _ => continue,
+        });
+    }
+
+    Ok(UciCmd::Go(specs))
+}
+
+fn parse_setoption(args: &[&str]) -> Result<UciCmd> {
+    let Some(("name", args)) = args.split_first().map(|(x, rest)| (*x, rest)) else {
+        bail!("Expected name after setoption");
+    };
+
+    // Both the name and value may contain spaces.
+    let (name, value) = match args.iter().position(|x| *x == "value") {
+        Some(idx) => (&args[..idx], Some(args[idx + 1..].join(" "))),
+        None => (args, None),
+    };
+
+    if name.is_empty() {
+        bail!("Missing option name");
+    }
+
+    Ok(UciCmd::SetOption(name.join(" "), value))
+}
+
+/// Parse a line sent by the GUI, skipping any unknown tokens before the
+/// command. Returns `None` if the line holds no command.
+pub fn parse_uci_cmd(input: &str) -> Result<Option<UciCmd>> {
+    let mut tokens = input.split_whitespace();
+
+    let Some(cmd) = tokens.find(|x| COMMANDS.contains(x)) else {
+        return Ok(None);
+    };
+
+    let args: Vec<_> = tokens.collect();
+
+    Ok(Some(match cmd {
+        "uci" => UciCmd::Uci,
+        "debug" => match args.first() {
+            Some(&"on") => UciCmd::Debug(true),
+            Some(&"off") => UciCmd::Debug(false),
+            _ => bail!("Expected on or off after debug"),
+        },
+        "isready" => UciCmd::IsReady,
+        "setoption" => parse_setoption(&args)?,
+        "register" => UciCmd::Register,
+        "ucinewgame" => UciCmd::NewGame,
+        "position" => parse_position(args.into_iter())?,
+        "go" => parse_go(args.into_iter())?,
+        "stop" => UciCmd::Stop,
+        "ponderhit" => UciCmd::PonderHit,
+        "quit" => UciCmd::Quit,
+        "bench" => UciCmd::Bench(
+            args.first()
+                .map(|x| parse_number(cmd, Some(x)))
+                .transpose()?,
+        ),
+        "d" => UciCmd::Display,
+        "eval" => UciCmd::Eval,
+        _ => unreachable!("Only known commands are found"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::{seq::SliceRandom, Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::piece::Colour;
+
+    use super::{parse_uci_cmd, GoSpecifier, PosSpecifier, UciCmd};
+
+    fn parse(input: &str) -> UciCmd {
+        parse_uci_cmd(input).unwrap().unwrap()
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse("uci\n"), UciCmd::Uci));
+        assert!(matches!(parse("ucinewgame"), UciCmd::NewGame));
+        assert!(matches!(parse("joho debug on"), UciCmd::Debug(true)));
+        assert!(matches!(parse("  debug   off "), UciCmd::Debug(false)));
+        assert!(matches!(parse("register later"), UciCmd::Register));
+        assert!(matches!(parse("bench 3"), UciCmd::Bench(Some(3))));
+
+        assert!(parse_uci_cmd("").unwrap().is_none());
+        assert!(parse_uci_cmd("hello world").unwrap().is_none());
+        assert!(parse_uci_cmd("debug maybe").is_err());
+        assert!(parse_uci_cmd("bench deep").is_err());
+    }
+
+    #[test]
+    fn position() {
+        let UciCmd::Position(pos, moves) = parse("position startpos moves e2e4 e7e5 g1f3") else {
+            panic!("Not a position command");
+        };
+        assert!(matches!(pos, PosSpecifier::Startpos));
+        assert_eq!(moves.len(), 3);
+        assert!(pos.to_position(&moves, false).is_ok());
+
+        let UciCmd::Position(pos, moves) =
+            parse("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1")
+        else {
+            panic!("Not a position command");
+        };
+        assert!(matches!(pos, PosSpecifier::Fen(_)));
+        assert_eq!(
+            pos.to_position(&moves, false).unwrap().to_play(),
+            Colour::Black
+        );
+
+        // Parsed, but not legal.
+        let UciCmd::Position(pos, moves) = parse("position startpos moves e2e5") else {
+            panic!("Not a position command");
+        };
+        assert!(pos.to_position(&moves, false).is_err());
+
+        assert!(parse_uci_cmd("position").is_err());
+        assert!(parse_uci_cmd("position fen 8/8 w").is_err());
+        assert!(parse_uci_cmd("position startpos moves e2e4 nonsense").is_err());
+    }
+
+    #[test]
+    fn setoption() {
+        let UciCmd::SetOption(name, value) = parse("setoption name Trace File value /tmp/a b.dot")
+        else {
+            panic!("Not a setoption command");
+        };
+        assert_eq!(name, "Trace File");
+        assert_eq!(value.as_deref(), Some("/tmp/a b.dot"));
+
+        let UciCmd::SetOption(name, value) = parse("setoption name Clear Hash") else {
+            panic!("Not a setoption command");
+        };
+        assert_eq!(name, "Clear Hash");
+        assert_eq!(value, None);
+
+        assert!(parse_uci_cmd("setoption Hash 16").is_err());
+    }
+
+    #[test]
+    fn go() {
+        let UciCmd::Go(specs) =
+            parse("go wtime -10 btime 1000 searchmoves e2e4 nodes 5 movetime 50 depth 3 ponder")
+        else {
+            panic!("Not a go command");
+        };
+        assert_eq!(
+            specs,
+            [
+                GoSpecifier::Time(Colour::White, Duration::ZERO),
+                GoSpecifier::Time(Colour::Black, Duration::from_millis(1000)),
+                GoSpecifier::MoveTime(Duration::from_millis(50)),
+                GoSpecifier::Depth(3),
+                GoSpecifier::Ponder,
+            ]
+        );
+
+        assert!(matches!(parse("go perft 4"), UciCmd::Perft(4)));
+        assert!(parse_uci_cmd("go depth").is_err());
+        assert!(parse_uci_cmd("go wtime soon").is_err());
+    }
+
+    #[test]
+    fn fuzz() {
+        const TOKENS: &[&str] = &[
+            "uci",
+            "ucinewgame",
+            "debug",
+            "on",
+            "off",
+            "isready",
+            "setoption",
+            "name",
+            "value",
+            "register",
+            "position",
+            "startpos",
+            "fen",
+            "moves",
+            "go",
+            "wtime",
+            "btime",
+            "winc",
+            "binc",
+            "movetime",
+            "depth",
+            "movestogo",
+            "infinite",
+            "ponder",
+            "perft",
+            "stop",
+            "ponderhit",
+            "bench",
+            "d",
+            "eval",
+            "e2e4",
+            "e7e5",
+            "e1g1",
+            "a7a8q",
+            "h9h1",
+            "w",
+            "b",
+            "-",
+            "KQkq",
+            "e3",
+            "0",
+            "1",
+            "-5",
+            "99999999999999999999",
+            "8/8/8/8/8/8/8/8",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            "4k3/8/8/8/8/8/8/4K3",
+            "k7/8/8/8",
+            "8p/8/8/8/8/8/8/8",
+            "\t",
+            "",
+        ];
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        for _ in 0..20000 {
+            let len = rng.gen_range(0..12);
+            let line = (0..len)
+                .map(|_| {
+                    if rng.gen_ratio(1, 10) {
+                        (0..rng.gen_range(1..6))
+                            .map(|_| rng.gen_range(' '..='~'))
+                            .collect()
+                    } else {
+                        TOKENS.choose(&mut rng).unwrap().to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            // Whatever the line, parsing it and setting up any position it
+            // gives must fail gracefully rather than panic.
+            if let Ok(Some(UciCmd::Position(pos, moves))) = parse_uci_cmd(&line) {
+                let _ = pos.to_position(&moves, rng.gen());
+            }
+        }
+    }
+}
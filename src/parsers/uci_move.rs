@@ -10,24 +10,28 @@ use nom::{
 use crate::{
     mmove::{Move, MoveType},
     piece::PieceKind,
-    position::locus::{File, Locus, Rank},
+    position::{
+        locus::{File, Locus, Rank},
+        movegen::MoveGen,
+        Position,
+    },
 };
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct UciMove {
     pub src: Locus,
     pub dst: Locus,
     pub promote: Option<PieceKind>,
 }
 
-fn parse_rank(input: &str) -> IResult<&str, Rank> {
+pub(crate) fn parse_rank(input: &str) -> IResult<&str, Rank> {
     map_res(one_of("12345678"), |x| -> Result<Rank, anyhow::Error> {
         let value: u32 = x.to_string().parse()?;
         Rank::try_from(value)
     })(input)
 }
 
-fn parse_file(input: &str) -> IResult<&str, File> {
+pub(crate) fn parse_file(input: &str) -> IResult<&str, File> {
     map(one_of("abcdefgh"), |x| -> File {
         match x {
             'a' => File::A,
@@ -43,7 +47,7 @@ fn parse_file(input: &str) -> IResult<&str, File> {
     })(input)
 }
 
-fn parse_locus(input: &str) -> IResult<&str, Locus> {
+pub(crate) fn parse_locus(input: &str) -> IResult<&str, Locus> {
     map(tuple((parse_file, parse_rank)), |(f, r)| {
         Locus::from_rank_file(r, f)
     })(input)
@@ -66,6 +70,30 @@ pub fn parse_uci_move(input: &str) -> IResult<&str, UciMove> {
     )(input)
 }
 
+impl UciMove {
+    /// Find the move of `pos` that this UCI move describes.
+    pub fn to_move(self, pos: &Position) -> Option<Move> {
+        MoveGen::new(pos)
+            .gen()
+            .into_iter()
+            .find(|x| Self::from_move(*x, pos) == self)
+    }
+
+    /// Write `m`, a move of `pos`, in UCI notation. In Chess960, castling is
+    /// written as the king capturing its own rook, since the king may not
+    /// move at all, or move where it could also go without castling.
+    pub fn from_move(m: Move, pos: &Position) -> Self {
+        match m.kind {
+            MoveType::Castle(kind) if pos.is_chess960() => UciMove {
+                src: m.src,
+                dst: pos.castling_rook(m.piece.colour(), kind).unwrap(),
+                promote: None,
+            },
+            _ => m.into(),
+        }
+    }
+}
+
 impl Display for UciMove {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.src)?;
@@ -3,6 +3,7 @@ use std::{
     ops::{Index, IndexMut},
 };
 
+use anyhow::{bail, Result};
 use arrayvec::ArrayVec;
 use bitboard::BitBoard;
 use builder::PositionBuilder;
@@ -24,6 +25,7 @@ pub mod eval;
 pub mod fen;
 pub mod locus;
 pub mod movegen;
+pub mod packed;
 pub mod zobrist;
 
 #[must_use = "Moves must either be undone, or made permanent"]
@@ -38,6 +40,8 @@ struct UndoMove {
     mmove: Move,
     ep_state: Option<Locus>,
     castling_rights: CastlingRights,
+    material_count: u8,
+    halfmove_clock: u16,
     hash: ZobristKey,
 }
 
@@ -48,9 +52,12 @@ pub struct Position {
     en_passant: Option<Locus>,
     castling_rights: CastlingRights,
     material_count: u8,
+    /// The number of plies since the last capture or pawn move.
+    halfmove_clock: u16,
     move_stack: ArrayVec<UndoMove, 512>,
     zobrist: Zobrist,
     hash: ZobristKey,
+    chess960: bool,
 }
 
 impl Position {
@@ -79,6 +86,24 @@ impl Position {
         self.hash
     }
 
+    /// Whether castling moves are written king-takes-rook, as in Chess960.
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// Whether either side may claim a draw by the fifty move rule.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
     pub fn all_pieces_for_colour(&self, colour: Colour) -> BitBoard {
         let mut b = BitBoard::empty();
 
@@ -131,25 +156,69 @@ impl Position {
         }
     }
 
-    #[inline(always)]
-    fn get_castling_rook_positions(c: Colour, kind: CastlingMoveType) -> (Locus, Locus) {
-        match (c, kind) {
-            (Colour::White, CastlingMoveType::Kingside) => (loc!(h 1), loc!(f 1)),
-            (Colour::White, CastlingMoveType::Queenside) => (loc!(a 1), loc!(d 1)),
-            (Colour::Black, CastlingMoveType::Kingside) => (loc!(h 8), loc!(f 8)),
-            (Colour::Black, CastlingMoveType::Queenside) => (loc!(a 8), loc!(d 8)),
+    /// Forget the moves made before the last capture or pawn move, or more
+    /// than fifty moves ago, so that a game of any length can be played out
+    /// on one position. They can't be repeated, or undone afterwards.
+    pub fn forget_history(&mut self) {
+        let keep = (self.halfmove_clock as usize).min(100);
+        let len = self.move_stack.len();
+
+        if len > keep {
+            self.move_stack.drain(..len - keep);
         }
     }
 
+    /// The square of the rook `c` castles with in the direction of `kind`,
+    /// if it still has the right to.
+    pub fn castling_rook(&self, c: Colour, kind: CastlingMoveType) -> Option<Locus> {
+        let file = self.castling_rights[c].rook_file(kind)?;
+
+        Some(Locus::from_rank_file(Self::back_rank(c), file))
+    }
+
+    fn back_rank(c: Colour) -> Rank {
+        match c {
+            Colour::White => Rank::One,
+            Colour::Black => Rank::Eight,
+        }
+    }
+
+    #[inline(always)]
+    fn get_castling_rook_positions(
+        rights: CastlingRights,
+        c: Colour,
+        kind: CastlingMoveType,
+    ) -> (Locus, Locus) {
+        let rank = Self::back_rank(c);
+        let src = rights[c].rook_file(kind).unwrap();
+        let dst = match kind {
+            CastlingMoveType::Kingside => File::F,
+            CastlingMoveType::Queenside => File::D,
+        };
+
+        (
+            Locus::from_rank_file(rank, src),
+            Locus::from_rank_file(rank, dst),
+        )
+    }
+
     #[inline(always)]
     pub fn make_move(&mut self, mmove: Move) -> UndoToken {
         let undo = UndoMove {
             mmove,
             ep_state: self.en_passant,
             castling_rights: self.castling_rights,
+            material_count: self.material_count,
+            halfmove_clock: self.halfmove_clock,
             hash: self.hash,
         };
 
+        if mmove.capture.is_some() || mmove.piece.kind() == PieceKind::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
         if let Some(ep_loc) = self.en_passant {
             self.hash ^= self.zobrist.ep_key(ep_loc);
             self.en_passant = None;
@@ -160,6 +229,7 @@ impl Position {
         match mmove.kind {
             MoveType::Normal => {
                 if let Some(p) = mmove.capture {
+                    self.material_count -= 1;
                     self.clr_piece_at(p, mmove.dst);
                 }
                 self.set_piece_at(mmove.piece, mmove.dst);
@@ -175,6 +245,7 @@ impl Position {
             }
             MoveType::EnPassant => {
                 let c = self.to_play;
+                self.material_count -= 1;
                 self.clr_piece_at(
                     Piece::new(PieceKind::Pawn, c.next()),
                     match c {
@@ -186,14 +257,21 @@ impl Position {
             }
             MoveType::Promote(promo_piece) => {
                 if let Some(cap_piece) = mmove.capture {
+                    self.material_count -= 1;
                     self.clr_piece_at(cap_piece, mmove.dst);
                 }
                 self.set_piece_at(promo_piece, mmove.dst);
             }
             MoveType::Castle(castle_kind) => {
-                let (rook_src, rook_dst) =
-                    Self::get_castling_rook_positions(self.to_play, castle_kind);
+                let (rook_src, rook_dst) = Self::get_castling_rook_positions(
+                    self.castling_rights,
+                    self.to_play,
+                    castle_kind,
+                );
 
+                // In Chess960 the king or rook may not move, or may land on
+                // the other's square, so the rook is lifted before it's put
+                // back down.
                 let rook = Piece::new(PieceKind::Rook, self.to_play);
                 self.set_piece_at(mmove.piece, mmove.dst);
                 self.clr_piece_at(rook, rook_src);
@@ -204,15 +282,8 @@ impl Position {
         if self.castling_rights[self.to_play].has_any() {
             // Clear castling rights.
             match mmove.piece.kind() {
-                PieceKind::King => {
-                    self.castling_rights[self.to_play].clear_all();
-                    self.hash ^= self.zobrist.castling_rights_key(self.to_play(), loc!(a 1));
-                    self.hash ^= self.zobrist.castling_rights_key(self.to_play(), loc!(h 1));
-                }
-                PieceKind::Rook => {
-                    self.castling_rights.clear(self.to_play, mmove.src);
-                    self.hash ^= self.zobrist.castling_rights_key(self.to_play(), mmove.src);
-                }
+                PieceKind::King => self.castling_rights[self.to_play].clear_all(),
+                PieceKind::Rook => self.castling_rights.clear(self.to_play, mmove.src),
                 _ => {}
             }
         }
@@ -221,11 +292,19 @@ impl Position {
             self.castling_rights.clear(self.to_play().next(), mmove.dst);
         }
 
+        if self.castling_rights != undo.castling_rights {
+            self.hash ^= self.zobrist.castling_key(undo.castling_rights);
+            self.hash ^= self.zobrist.castling_key(self.castling_rights);
+        }
+
         self.to_play = self.to_play.next();
 
         self.hash ^= self.zobrist.btm_key();
         self.move_stack.push(undo);
 
+        #[cfg(feature = "verify")]
+        self.verify().unwrap();
+
         UndoToken
     }
 
@@ -267,24 +346,162 @@ impl Position {
                 }
             }
             MoveType::Castle(castle_kind) => {
-                let (rook_src, rook_dst) =
-                    Self::get_castling_rook_positions(self.to_play, castle_kind);
+                let (rook_src, rook_dst) = Self::get_castling_rook_positions(
+                    undo.castling_rights,
+                    self.to_play,
+                    castle_kind,
+                );
 
                 let rook = Piece::new(PieceKind::Rook, self.to_play);
                 self.clr_piece_at(mmove.piece, mmove.dst);
-                self.set_piece_at(rook, rook_src);
                 self.clr_piece_at(rook, rook_dst);
+                self.set_piece_at(rook, rook_src);
             }
         }
         self.set_piece_at(mmove.piece, mmove.src);
 
-        self[mmove.piece] = self[mmove.piece]
-            .set_piece_at(mmove.src)
-            .clear_piece_at(mmove.dst);
-
         self.en_passant = undo.ep_state;
         self.castling_rights = undo.castling_rights;
+        self.material_count = undo.material_count;
+        self.halfmove_clock = undo.halfmove_clock;
         self.hash = undo.hash;
+
+        #[cfg(feature = "verify")]
+        self.verify().unwrap();
+    }
+
+    /// Check that the position could arise in a game, as far as the move
+    /// generator relies on it: each side has one king, no pawn is on the
+    /// first or last rank, the castling rights and en passant square are
+    /// backed by the pieces they need, and the side not to move isn't in
+    /// check.
+    pub fn check_legal(&self) -> Result<()> {
+        for c in Colour::iter() {
+            let king = self[Piece::new(PieceKind::King, c)];
+
+            if king.popcount() != 1 {
+                bail!("{c:?} must have exactly one king");
+            }
+
+            if self[Piece::new(PieceKind::Pawn, c)]
+                .iter_pieces()
+                .any(|x| matches!(x.to_rank_file().0, Rank::One | Rank::Eight))
+            {
+                bail!("{c:?} has a pawn on the first or last rank");
+            }
+
+            for kind in [CastlingMoveType::Kingside, CastlingMoveType::Queenside] {
+                if let Some(rook) = self.castling_rook(c, kind) {
+                    if !self[Piece::new(PieceKind::Rook, c)].has_piece_at(rook)
+                        || !king
+                            .iter_pieces()
+                            .any(|x| x.to_rank_file().0 == Self::back_rank(c))
+                    {
+                        bail!("{c:?} can't castle with a rook on {rook}");
+                    }
+                }
+            }
+        }
+
+        if let Some(ep) = self.en_passant {
+            let (rank, pawn) = match self.to_play {
+                Colour::White => (Rank::Six, ep.south()),
+                Colour::Black => (Rank::Three, ep.north()),
+            };
+
+            if ep.to_rank_file().0 != rank
+                || !pawn.is_some_and(|x| {
+                    self[Piece::new(PieceKind::Pawn, self.to_play.next())].has_piece_at(x)
+                })
+            {
+                bail!("No pawn can be taken en passant on {ep}");
+            }
+        }
+
+        if movegen::MoveGen::new(self).in_check(self.to_play.next()) {
+            bail!(
+                "{:?} is in check, but it's not their move",
+                self.to_play.next()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check that the incrementally updated state of the position matches
+    /// the state recomputed from scratch.
+    pub fn verify(&self) -> Result<()> {
+        let mut occupied = BitBoard::empty();
+        let mut material_count = 0;
+
+        for bb in self.bboards.iter() {
+            if !occupied.and(*bb).is_empty() {
+                bail!("Multiple pieces occupy the same square");
+            }
+
+            occupied = occupied.or(*bb);
+            material_count += bb.popcount();
+        }
+
+        if material_count != self.material_count as u32 {
+            bail!(
+                "Material count is {}, expected {}",
+                self.material_count,
+                material_count
+            );
+        }
+
+        let hash = self.zobrist.from_position(self);
+
+        if hash != self.hash {
+            bail!("Hash is {:#018x}, expected {:#018x}", self.hash, hash);
+        }
+
+        for c in Colour::iter() {
+            let rights = self.castling_rights[c];
+            let rook = self[Piece::new(PieceKind::Rook, c)];
+
+            if !rights.has_any() {
+                continue;
+            }
+
+            let Some(king) = self[Piece::new(PieceKind::King, c)]
+                .iter_pieces()
+                .next()
+                .filter(|x| x.to_rank_file().0 == Self::back_rank(c))
+            else {
+                bail!("{c:?} has castling rights but the king has moved");
+            };
+
+            for kind in [CastlingMoveType::Kingside, CastlingMoveType::Queenside] {
+                let Some(loc) = self.castling_rook(c, kind) else {
+                    continue;
+                };
+
+                let side = (loc.to_rank_file().1 as u8).cmp(&(king.to_rank_file().1 as u8));
+                let expected = match kind {
+                    CastlingMoveType::Kingside => std::cmp::Ordering::Greater,
+                    CastlingMoveType::Queenside => std::cmp::Ordering::Less,
+                };
+
+                if !rook.has_piece_at(loc) || side != expected {
+                    bail!("{c:?} can castle {kind:?} without a rook");
+                }
+            }
+        }
+
+        if let Some(ep) = self.en_passant {
+            let expected = match self.to_play {
+                Colour::White => Rank::Six,
+                Colour::Black => Rank::Three,
+            };
+
+            if ep.to_rank_file().0 != expected {
+                bail!("En passant square {ep} is on the wrong rank");
+            }
+        }
+
+        Ok(())
     }
 
     pub fn empty() -> Self {
@@ -295,12 +512,14 @@ impl Position {
             castling_rights: CastlingRights::empty(),
             move_stack: ArrayVec::new(),
             material_count: 0,
+            halfmove_clock: 0,
             zobrist: Zobrist::new(),
             hash: 0,
+            chess960: false,
         }
     }
 
-    pub fn iter_opponent_bbds(&self) -> OpponentBbIter {
+    pub fn iter_opponent_bbds(&self) -> OpponentBbIter<'_> {
         OpponentBbIter {
             pos: self,
             p: PieceKind::iter(),
@@ -324,15 +543,32 @@ impl Iterator for OpponentBbIter<'_> {
     }
 }
 
-impl Display for Position {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for rank in Rank::iter().rev() {
+impl Position {
+    /// Draw the board from black's side, where `Display` draws it from
+    /// white's.
+    pub fn flipped(&self) -> Flipped<'_> {
+        Flipped(self)
+    }
+
+    fn fmt_board(&self, f: &mut std::fmt::Formatter<'_>, flipped: bool) -> std::fmt::Result {
+        let ranks: Vec<_> = if flipped {
+            Rank::iter().collect()
+        } else {
+            Rank::iter().rev().collect()
+        };
+        let files: Vec<_> = if flipped {
+            File::iter().rev().collect()
+        } else {
+            File::iter().collect()
+        };
+
+        for rank in ranks {
             write!(f, "{rank} ")?;
-            for file in File::iter() {
+            for file in files.iter() {
                 write!(
                     f,
                     "{}",
-                    self.piece_at_loc(Locus::from_rank_file(rank, file))
+                    self.piece_at_loc(Locus::from_rank_file(rank, *file))
                         .map(|x| format!("{x} "))
                         .unwrap_or(". ".to_string())
                 )?;
@@ -341,7 +577,7 @@ impl Display for Position {
         }
 
         write!(f, "  ")?;
-        for file in File::iter() {
+        for file in files {
             write!(f, "{file:?} ")?;
         }
 
@@ -351,6 +587,20 @@ impl Display for Position {
     }
 }
 
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_board(f, false)
+    }
+}
+
+pub struct Flipped<'a>(&'a Position);
+
+impl Display for Flipped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_board(f, true)
+    }
+}
+
 impl Index<Piece> for Position {
     type Output = BitBoard;
 
@@ -393,12 +643,65 @@ impl Default for Position {
 
 #[cfg(test)]
 mod tests {
-    use super::{locus::loc, Position};
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::{locus::loc, movegen::MoveGen, Position};
     use crate::{
         mmove::{CastlingMoveType, MoveBuilder},
         piece::{mkp, Colour, Piece, PieceKind},
     };
 
+    // Play random legal moves from the start position, verifying the position
+    // after every move, and then unwind the game back to the start.
+    fn play_random_games(seed: u64, games: usize, max_plies: usize) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        for _ in 0..games {
+            let mut pos = Position::default();
+            let start = pos.clone();
+            let mut tokens = Vec::new();
+
+            for _ in 0..max_plies {
+                let moves: Vec<_> = MoveGen::new(&pos)
+                    .gen()
+                    .into_iter()
+                    .filter(|m| {
+                        let token = pos.make_move(*m);
+                        let legal = !MoveGen::new(&pos).in_check(pos.to_play().next());
+                        pos.undo_move(token);
+                        legal
+                    })
+                    .collect();
+
+                let Some(m) = moves.choose(&mut rng) else {
+                    break;
+                };
+
+                tokens.push(pos.make_move(*m));
+                pos.verify().unwrap();
+            }
+
+            while let Some(token) = tokens.pop() {
+                pos.undo_move(token);
+                pos.verify().unwrap();
+            }
+
+            assert!(pos == start);
+        }
+    }
+
+    #[test]
+    fn random_games_verify() {
+        play_random_games(0x5eed, 20, 300);
+    }
+
+    #[test]
+    #[ignore = "Plays millions of moves, run with --release"]
+    fn random_games_verify_long() {
+        play_random_games(0xc0ffee, 10_000, 500);
+    }
+
     #[test]
     fn undo_move() {
         let mut pos = Position::default();
@@ -445,6 +748,73 @@ mod tests {
         assert_eq!(pos.en_passant.unwrap(), loc!(e 3));
     }
 
+    #[test]
+    fn check_legal() {
+        assert!(Position::default().check_legal().is_ok());
+        assert!(
+            Position::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3")
+                .unwrap()
+                .check_legal()
+                .is_ok()
+        );
+
+        for fen in [
+            // No black king.
+            "8/8/8/8/8/8/8/4K3 w - - 0 1",
+            // A pawn on the last rank.
+            "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            // Black is in check, with white to play.
+            "4k2R/8/8/8/8/8/8/4K3 w - - 0 1",
+            // Castling with a rook which isn't there.
+            "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+            // No pawn to take en passant.
+            "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+        ] {
+            assert!(
+                Position::from_fen(fen).unwrap().check_legal().is_err(),
+                "{fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn halfmove_clock() {
+        let mut pos = Position::from_fen("4k3/4p3/8/8/8/8/8/R3K3 w - - 98 80").unwrap();
+        let rook = MoveBuilder::new(mkp!(White, Rook), loc!(a 1))
+            .with_dst(loc!(a 2))
+            .build();
+
+        let token = pos.make_move(rook);
+        assert_eq!(pos.halfmove_clock(), 99);
+        assert!(!pos.is_fifty_move_draw());
+        pos.undo_move(token);
+        assert_eq!(pos.halfmove_clock(), 98);
+
+        pos.make_move(rook).consume();
+        pos.make_move(
+            MoveBuilder::new(mkp!(Black, King), loc!(e 8))
+                .with_dst(loc!(d 8))
+                .build(),
+        )
+        .consume();
+        assert!(pos.is_fifty_move_draw());
+
+        // A pawn move resets the count.
+        pos.make_move(
+            MoveBuilder::new(mkp!(White, Rook), loc!(a 2))
+                .with_dst(loc!(a 3))
+                .build(),
+        )
+        .consume();
+        pos.make_move(
+            MoveBuilder::new(mkp!(Black, Pawn), loc!(e 7))
+                .with_dst(loc!(e 6))
+                .build(),
+        )
+        .consume();
+        assert_eq!(pos.halfmove_clock(), 0);
+    }
+
     #[test]
     fn en_passant_capture_undo() {
         let mut pos =
@@ -516,6 +886,48 @@ mod tests {
         assert_eq!(pos, p2);
     }
 
+    #[test]
+    fn make_move_castle_chess960() {
+        // The king starts on its destination square and only the rook moves.
+        let mut pos = Position::from_fen("3r2kr/8/8/8/8/8/8/RK5R b HAhd - 0 1").unwrap();
+        let p2 = pos.clone();
+
+        let token = pos.make_move(
+            MoveBuilder::new(mkp!(Black, King), loc!(g 8))
+                .with_dst(loc!(g 8))
+                .is_castling_move(CastlingMoveType::Kingside)
+                .build(),
+        );
+
+        pos.verify().unwrap();
+        assert!(pos[mkp!(Black, King)].has_piece_at(loc!(g 8)));
+        assert!(pos[mkp!(Black, Rook)].has_piece_at(loc!(f 8)));
+        assert!(!pos[mkp!(Black, Rook)].has_piece_at(loc!(h 8)));
+
+        pos.undo_move(token);
+        assert_eq!(pos, p2);
+
+        // The king and rook swap sides of each other.
+        let mut pos = Position::from_fen("3r2kr/8/8/8/8/8/8/RK5R w HAhd - 0 1").unwrap();
+        let p2 = pos.clone();
+
+        let token = pos.make_move(
+            MoveBuilder::new(mkp!(White, King), loc!(b 1))
+                .with_dst(loc!(c 1))
+                .is_castling_move(CastlingMoveType::Queenside)
+                .build(),
+        );
+
+        pos.verify().unwrap();
+        assert!(pos[mkp!(White, King)].has_piece_at(loc!(c 1)));
+        assert!(pos[mkp!(White, Rook)].has_piece_at(loc!(d 1)));
+        assert!(!pos[mkp!(White, Rook)].has_piece_at(loc!(a 1)));
+        assert!(!pos.castling_rights[Colour::White].king_side());
+
+        pos.undo_move(token);
+        assert_eq!(pos, p2);
+    }
+
     #[test]
     fn castling_rights_clear() {
         let mut pos = Position::from_fen(
@@ -60,6 +60,11 @@ impl PositionBuilder {
         self
     }
 
+    pub fn with_chess960(mut self, chess960: bool) -> Self {
+        self.pos.chess960 = chess960;
+        self
+    }
+
     pub fn build(mut self) -> Position {
         let mut pieces = 0u8;
         PieceKind::iter().for_each(|k| {
@@ -1,20 +1,23 @@
 use std::ops::{Index, IndexMut};
 
-use crate::piece::Colour;
+use crate::{mmove::CastlingMoveType, piece::Colour};
 
 use super::locus::{File, Locus, Rank};
 
+/// The castling rights of one side, as the files of the rooks it may castle
+/// with. In standard chess these are always the a and h files, but in
+/// Chess960 they can be any file on either side of the king.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct CastlingRight {
-    king_side: bool,
-    queen_side: bool,
+    king_side: Option<File>,
+    queen_side: Option<File>,
 }
 
 impl Default for CastlingRight {
     fn default() -> Self {
         Self {
-            king_side: true,
-            queen_side: true,
+            king_side: Some(File::H),
+            queen_side: Some(File::A),
         }
     }
 }
@@ -22,18 +25,18 @@ impl Default for CastlingRight {
 impl CastlingRight {
     pub fn empty() -> Self {
         Self {
-            king_side: false,
-            queen_side: false,
+            king_side: None,
+            queen_side: None,
         }
     }
 
     pub fn has_any(self) -> bool {
-        self.king_side || self.queen_side
+        self.king_side.is_some() || self.queen_side.is_some()
     }
 
     pub fn clear_all(&mut self) {
-        self.king_side = false;
-        self.queen_side = false;
+        self.king_side = None;
+        self.queen_side = None;
     }
 
     pub fn clear_for_loc(&mut self, loc: Locus) {
@@ -41,27 +44,49 @@ impl CastlingRight {
         if !(r == Rank::One || r == Rank::Eight) {
             return;
         }
-        match f {
-            File::H => self.king_side = false,
-            File::A => self.queen_side = false,
-            _ => (),
+
+        self.clear_file(f);
+    }
+
+    fn clear_file(&mut self, f: File) {
+        if self.king_side == Some(f) {
+            self.king_side = None;
+        }
+
+        if self.queen_side == Some(f) {
+            self.queen_side = None;
         }
     }
 
     pub fn queen_side(self) -> bool {
-        self.queen_side
+        self.queen_side.is_some()
     }
 
     pub fn king_side(self) -> bool {
-        self.king_side
+        self.king_side.is_some()
+    }
+
+    /// The file of the rook used to castle in the direction of `kind`.
+    pub fn rook_file(self, kind: CastlingMoveType) -> Option<File> {
+        match kind {
+            CastlingMoveType::Kingside => self.king_side,
+            CastlingMoveType::Queenside => self.queen_side,
+        }
     }
 
     pub fn set_king_side(&mut self) {
-        self.king_side = true;
+        self.king_side = Some(File::H);
     }
 
     pub fn set_queen_side(&mut self) {
-        self.queen_side = true;
+        self.queen_side = Some(File::A);
+    }
+
+    pub fn set_rook_file(&mut self, kind: CastlingMoveType, file: File) {
+        match kind {
+            CastlingMoveType::Kingside => self.king_side = Some(file),
+            CastlingMoveType::Queenside => self.queen_side = Some(file),
+        }
     }
 }
 
@@ -78,11 +103,7 @@ impl CastlingRights {
             return;
         }
 
-        match f {
-            File::H => self[c].king_side = false,
-            File::A => self[c].queen_side = false,
-            _ => (),
-        }
+        self[c].clear_file(f);
     }
 }
 
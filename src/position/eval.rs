@@ -1,15 +1,59 @@
-use strum::IntoEnumIterator;
+use std::fmt::Display;
+
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
 
 use crate::piece::{Colour, Piece, PieceKind};
 
-use super::{bitboard::BitBoard, Position};
+use super::Position;
+
+use endgame::{Endgame, ScaleFactor, SCALE_NORMAL};
+
+pub mod endgame;
+mod kpk;
+
+/// The piece-square tables that make up part of the evaluation parameters.
+#[derive(Clone, Copy, PartialEq, Debug, EnumCount, EnumIter)]
+pub enum PsqtTable {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    KingMiddle,
+    KingEnd,
+}
+
+/// The total number of tunable evaluation parameters.
+pub const NUM_PARAMS: usize = PieceKind::COUNT + PsqtTable::COUNT * 64;
+
+const PSQT_OFFSET: usize = PieceKind::COUNT;
+
+/// A flat vector of every weight used by the [Evaluator]. The first
+/// `PieceKind::COUNT` entries hold the material value of each piece kind and
+/// are followed by one 64-entry table per [PsqtTable], laid out from white's
+/// point of view with a1 at index 0.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalParams {
+    weights: [i32; NUM_PARAMS],
+}
 
 pub struct Evaluator<'a> {
     pos: &'a Position,
+    params: &'a EvalParams,
 }
 
 #[rustfmt::skip]
-const PSQT_PAWN: [[i32; 64]; 2] = calc_table([
+const MATERIAL: [i32; PieceKind::COUNT] = [
+    100,   // Pawn
+    300,   // Knight
+    350,   // Bishop
+    500,   // Rook
+    1000,  // Queen
+    10000, // King
+];
+
+#[rustfmt::skip]
+const PSQT_PAWN: [i32; 64] = [
      0,  0,  0,  0,  0,  0,  0,  0, // 1
      5, 10, 10,-20,-20, 10, 10,  5, // 2
      5, -5,-10,  0,  0,-10, -5,  5, // 3
@@ -19,10 +63,10 @@ const PSQT_PAWN: [[i32; 64]; 2] = calc_table([
     50, 50, 50, 50, 50, 50, 50, 50, // 7
      0,  0,  0,  0,  0,  0,  0,  0, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_KNIGHT: [[i32; 64]; 2] = calc_table([
+const PSQT_KNIGHT: [i32; 64] = [
     -50,-40,-30,-30,-30,-30,-40,-50, // 1
     -40,-20,  0,  5,  5,  0,-20,-40, // 2
     -30,  5, 10, 15, 15, 10,  5,-30, // 3
@@ -32,10 +76,10 @@ const PSQT_KNIGHT: [[i32; 64]; 2] = calc_table([
     -40,-20,  0,  0,  0,  0,-20,-40, // 7
     -50,-40,-30,-30,-30,-30,-40,-50, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_BISHOP: [[i32; 64]; 2] = calc_table([
+const PSQT_BISHOP: [i32; 64] = [
     -20,-10,-10,-10,-10,-10,-10,-20, // 1
     -10,  5,  0,  0,  0,  0,  5,-10, // 2
     -10, 10, 10, 10, 10, 10, 10,-10, // 3
@@ -45,10 +89,10 @@ const PSQT_BISHOP: [[i32; 64]; 2] = calc_table([
     -10,  0,  0,  0,  0,  0,  0,-10, // 7
     -20,-10,-10,-10,-10,-10,-10,-20, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_ROOK: [[i32; 64]; 2] = calc_table([
+const PSQT_ROOK: [i32; 64] = [
      0,  0,  0,  5,  5,  0,  0,  0, // 1
     -5,  0,  0,  0,  0,  0,  0, -5, // 2
     -5,  0,  0,  0,  0,  0,  0, -5, // 3
@@ -58,10 +102,10 @@ const PSQT_ROOK: [[i32; 64]; 2] = calc_table([
      5, 10, 10, 10, 10, 10, 10,  5, // 7
      0,  0,  0,  0,  0,  0,  0,  0, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_QUEEN: [[i32; 64]; 2] = calc_table([
+const PSQT_QUEEN: [i32; 64] = [
     -20,-10,-10, -5, -5,-10,-10,-20, // 1
     -10,  0,  5,  0,  0,  0,  0,-10, // 2
     -10,  5,  5,  5,  5,  5,  0,-10, // 3
@@ -71,10 +115,10 @@ const PSQT_QUEEN: [[i32; 64]; 2] = calc_table([
     -10,  0,  0,  0,  0,  0,  0,-10, // 7
     -20,-10,-10, -5, -5,-10,-10,-20, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_KING_MIDDLE: [[i32; 64]; 2] = calc_table([
+const PSQT_KING_MIDDLE: [i32; 64] = [
      20, 30, 10,  0,  0, 10, 30, 20, // 1
      20, 20,  0,  0,  0,  0, 20, 20, // 2
     -10,-20,-20,-20,-20,-20,-20,-10, // 3
@@ -84,10 +128,10 @@ const PSQT_KING_MIDDLE: [[i32; 64]; 2] = calc_table([
     -30,-40,-40,-50,-50,-40,-40,-30, // 7
     -30,-40,-40,-50,-50,-40,-40,-30, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
 
 #[rustfmt::skip]
-const PSQT_KING_END: [[i32; 64]; 2] = calc_table([
+const PSQT_KING_END: [i32; 64] = [
     -50,-30,-30,-30,-30,-30,-30,-50, // 1
     -30,-30,  0,  0,  0,  0,-30,-30, // 2
     -30,-10, 20, 30, 30, 20,-10,-30, // 3
@@ -97,34 +141,268 @@ const PSQT_KING_END: [[i32; 64]; 2] = calc_table([
     -30,-20,-10,  0,  0,-10,-20,-30, // 7
     -50,-40,-30,-20,-20,-30,-40,-50, // 8
   // A     B     C     D     E     F     G     H
-]);
+];
+
+impl EvalParams {
+    const fn from_tables(
+        material: [i32; PieceKind::COUNT],
+        psqts: [[i32; 64]; PsqtTable::COUNT],
+    ) -> Self {
+        let mut weights = [0; NUM_PARAMS];
+        let mut i = 0;
+
+        while i < PieceKind::COUNT {
+            weights[i] = material[i];
+            i += 1;
+        }
+
+        let mut t = 0;
 
-const fn flip(x: [i32; 64]) -> [i32; 64] {
-    let mut ret = [0; 64];
-    let mut i = 0;
+        while t < PsqtTable::COUNT {
+            let mut sq = 0;
 
-    loop {
-        if i == 64 {
-            break;
+            while sq < 64 {
+                weights[PSQT_OFFSET + t * 64 + sq] = psqts[t][sq];
+                sq += 1;
+            }
+
+            t += 1;
         }
-        let col = i % 8;
-        let row = i / 8;
 
-        ret[(7 - row) * 8 + col] = x[i];
+        Self { weights }
+    }
+
+    pub fn from_weights(weights: [i32; NUM_PARAMS]) -> Self {
+        Self { weights }
+    }
+
+    pub fn weights(&self) -> &[i32; NUM_PARAMS] {
+        &self.weights
+    }
+
+    pub fn material_idx(kind: PieceKind) -> usize {
+        kind as usize
+    }
+
+    /// Index of the weight for square `sq` of `table`, where `sq` is given
+    /// from white's point of view.
+    pub fn psqt_idx(table: PsqtTable, sq: usize) -> usize {
+        PSQT_OFFSET + table as usize * 64 + sq
+    }
+
+    pub fn material(&self, kind: PieceKind) -> i32 {
+        self.weights[Self::material_idx(kind)]
+    }
+
+    pub fn set_material(&mut self, kind: PieceKind, value: i32) {
+        self.weights[Self::material_idx(kind)] = value;
+    }
+
+    pub fn psqt(&self, table: PsqtTable) -> &[i32] {
+        let idx = Self::psqt_idx(table, 0);
+        &self.weights[idx..idx + 64]
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_EVAL_PARAMS
+    }
+}
+
+pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams::from_tables(
+    MATERIAL,
+    [
+        PSQT_PAWN,
+        PSQT_KNIGHT,
+        PSQT_BISHOP,
+        PSQT_ROOK,
+        PSQT_QUEEN,
+        PSQT_KING_MIDDLE,
+        PSQT_KING_END,
+    ],
+);
+
+// Black's pieces use the white tables mirrored vertically.
+fn psqt_square(piece: Piece, sq: usize) -> usize {
+    match piece.colour() {
+        Colour::White => sq,
+        Colour::Black => sq ^ 56,
+    }
+}
+
+fn psqt_table(kind: PieceKind) -> PsqtTable {
+    match kind {
+        PieceKind::Pawn => PsqtTable::Pawn,
+        PieceKind::Knight => PsqtTable::Knight,
+        PieceKind::Bishop => PsqtTable::Bishop,
+        PieceKind::Rook => PsqtTable::Rook,
+        PieceKind::Queen => PsqtTable::Queen,
+        PieceKind::King => unreachable!("King uses tapered tables"),
+    }
+}
 
-        i += 1;
+/// An individual component of the evaluation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EvalTerm {
+    Material,
+    Psqt(PieceKind),
+    /// A specialised evaluation that replaces every other term.
+    Endgame,
+}
+
+impl Display for EvalTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalTerm::Material => write!(f, "Material"),
+            EvalTerm::Psqt(k) => write!(f, "{k:?} PSQT"),
+            EvalTerm::Endgame => write!(f, "Endgame"),
+        }
     }
+}
 
-    ret
+/// The middlegame and endgame contribution of a single [EvalTerm], for each
+/// colour (indexed by `Colour as usize`).
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceTerm {
+    pub term: EvalTerm,
+    pub mg: [i32; 2],
+    pub eg: [i32; 2],
 }
 
-const fn calc_table(x: [i32; 64]) -> [[i32; 64]; 2] {
-    [x, flip(x)]
+/// A breakdown of the evaluation of a position into its individual terms.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalTrace {
+    /// How far the position is towards the middlegame, where `1.0` is the
+    /// middlegame and `0.0` is the endgame.
+    pub phase: f32,
+    pub terms: Vec<TraceTerm>,
+    /// The name of the specialised endgame knowledge that applied, if any.
+    pub endgame: Option<&'static str>,
+    /// The scale factor applied to the evaluation, out of [SCALE_NORMAL].
+    pub scale: ScaleFactor,
+    /// The final evaluation, from white's point of view.
+    pub eval: i32,
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let taper = |mg: i32, eg: i32| taper(self.phase, mg, eg);
+        let w = Colour::White as usize;
+        let b = Colour::Black as usize;
+
+        writeln!(
+            f,
+            "{:<14}|{:^20}|{:^20}|{:^20}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:<14}|{:>6} {:>6} {:>6}|{:>6} {:>6} {:>6}|{:>6} {:>6} {:>6}",
+            "", "MG", "EG", "Tpr", "MG", "EG", "Tpr", "MG", "EG", "Tpr"
+        )?;
+        writeln!(f, "{:-<77}", "")?;
+
+        for t in self.terms.iter() {
+            let (mg, eg) = (t.mg[w] - t.mg[b], t.eg[w] - t.eg[b]);
+
+            writeln!(
+                f,
+                "{:<14}|{:>6} {:>6} {:>6}|{:>6} {:>6} {:>6}|{:>6} {:>6} {:>6}",
+                t.term.to_string(),
+                t.mg[w],
+                t.eg[w],
+                taper(t.mg[w], t.eg[w]),
+                t.mg[b],
+                t.eg[b],
+                taper(t.mg[b], t.eg[b]),
+                mg,
+                eg,
+                taper(t.mg[w], t.eg[w]) - taper(t.mg[b], t.eg[b]),
+            )?;
+        }
+
+        writeln!(f, "{:-<77}", "")?;
+        writeln!(f, "Phase: {:.2} (1.0 = middlegame)", self.phase)?;
+
+        if let Some(name) = self.endgame {
+            writeln!(f, "Endgame: {name}")?;
+        }
+
+        if self.scale != SCALE_NORMAL {
+            writeln!(f, "Scale factor: {}/{}", self.scale, SCALE_NORMAL)?;
+        }
+
+        writeln!(f, "Evaluation: {} (white's perspective)", self.eval)
+    }
+}
+
+// Each half of a tapered term is truncated on its own, as the evaluation has
+// always done, so that tracing it gives exactly the same score.
+fn taper(phase: f32, mg: i32, eg: i32) -> i32 {
+    if mg == eg {
+        mg
+    } else {
+        (phase * mg as f32) as i32 + ((1.0 - phase) * eg as f32) as i32
+    }
+}
+
+// Receives every term that the evaluator computes. This allows the same code
+// to either compute the final score or a full breakdown of it.
+trait EvalSink {
+    fn add(&mut self, term: EvalTerm, colour: Colour, mg: i32, eg: i32);
+}
+
+struct ScoreSink {
+    phase: f32,
+    score: i32,
+}
+
+impl EvalSink for ScoreSink {
+    fn add(&mut self, _term: EvalTerm, colour: Colour, mg: i32, eg: i32) {
+        let v = taper(self.phase, mg, eg);
+
+        match colour {
+            Colour::White => self.score += v,
+            Colour::Black => self.score -= v,
+        }
+    }
+}
+
+impl EvalSink for EvalTrace {
+    fn add(&mut self, term: EvalTerm, colour: Colour, mg: i32, eg: i32) {
+        let idx = match self.terms.iter().position(|x| x.term == term) {
+            Some(idx) => idx,
+            None => {
+                self.terms.push(TraceTerm {
+                    term,
+                    mg: [0; 2],
+                    eg: [0; 2],
+                });
+                self.terms.len() - 1
+            }
+        };
+
+        self.terms[idx].mg[colour as usize] += mg;
+        self.terms[idx].eg[colour as usize] += eg;
+
+        let v = taper(self.phase, mg, eg);
+
+        match colour {
+            Colour::White => self.eval += v,
+            Colour::Black => self.eval -= v,
+        }
+    }
 }
 
 impl<'a> Evaluator<'a> {
-    fn apply_psqt(bb: BitBoard, psqt: &[i32; 64]) -> i32 {
-        bb.iter_pieces().map(|x| psqt[x.to_idx() as usize]).sum()
+    fn apply_psqt(&self, piece: Piece, table: PsqtTable) -> i32 {
+        let psqt = self.params.psqt(table);
+
+        self.pos[piece]
+            .iter_pieces()
+            .map(|x| psqt[psqt_square(piece, x.to_idx() as usize)])
+            .sum()
     }
 
     fn calc_phase_coef(material_count: u8) -> f32 {
@@ -137,77 +415,159 @@ impl<'a> Evaluator<'a> {
         }
     }
 
-    fn calc_psqt(&self) -> i32 {
-        let mut ret = 0;
-
-        macro_rules! psqt {
-            ($k:ident, $table:ident) => {
-                ret += Self::apply_psqt(
-                    self.pos[Piece::new(PieceKind::$k, Colour::White)],
-                    &$table[0],
-                );
-                ret -= Self::apply_psqt(
-                    self.pos[Piece::new(PieceKind::$k, Colour::Black)],
-                    &$table[1],
-                );
-            };
-            ($k:ident, $table:ident, $coeff:expr) => {
-                ret += (($coeff)
-                    * Self::apply_psqt(
-                        self.pos[Piece::new(PieceKind::$k, Colour::White)],
-                        &$table[0],
-                    ) as f32) as i32;
-                ret -= (($coeff)
-                    * Self::apply_psqt(
-                        self.pos[Piece::new(PieceKind::$k, Colour::Black)],
-                        &$table[1],
-                    ) as f32) as i32;
-            };
+    fn calc_psqt(&self, sink: &mut impl EvalSink) {
+        for colour in Colour::iter() {
+            for kind in PieceKind::iter() {
+                let piece = Piece::new(kind, colour);
+
+                if kind == PieceKind::King {
+                    sink.add(
+                        EvalTerm::Psqt(kind),
+                        colour,
+                        self.apply_psqt(piece, PsqtTable::KingMiddle),
+                        self.apply_psqt(piece, PsqtTable::KingEnd),
+                    );
+                } else {
+                    let v = self.apply_psqt(piece, psqt_table(kind));
+                    sink.add(EvalTerm::Psqt(kind), colour, v, v);
+                }
+            }
         }
+    }
 
-        let game_phase = Self::calc_phase_coef(self.pos.material_count);
+    fn count_material(&self, sink: &mut impl EvalSink) {
+        for colour in Colour::iter() {
+            let mut v = 0;
 
-        psqt!(Pawn, PSQT_PAWN);
-        psqt!(Rook, PSQT_ROOK);
-        psqt!(Bishop, PSQT_BISHOP);
-        psqt!(Knight, PSQT_KNIGHT);
-        psqt!(Queen, PSQT_QUEEN);
-        psqt!(King, PSQT_KING_MIDDLE, game_phase);
-        psqt!(King, PSQT_KING_END, 1.0 - game_phase);
+            for kind in PieceKind::iter() {
+                v += self.pos[Piece::new(kind, colour)].popcount() as i32
+                    * self.params.material(kind);
+            }
 
-        ret
+            sink.add(EvalTerm::Material, colour, v, v);
+        }
     }
 
-    fn count_material(&self) -> i32 {
-        let mut ret = 0;
+    fn do_eval(&self, sink: &mut impl EvalSink) {
+        self.count_material(sink);
+        self.calc_psqt(sink);
+    }
 
-        for kind in PieceKind::iter() {
-            ret +=
-                self.pos[Piece::new(kind, Colour::White)].popcount() as i32 * kind.score() as i32;
-            ret -=
-                self.pos[Piece::new(kind, Colour::Black)].popcount() as i32 * kind.score() as i32;
+    pub fn eval(pos: &'a Position) -> i32 {
+        Self::eval_with_params(pos, &DEFAULT_EVAL_PARAMS)
+    }
+
+    pub fn eval_with_params(pos: &'a Position, params: &'a EvalParams) -> i32 {
+        let endgame = endgame::probe(pos, params);
+
+        if let Some(Endgame::Eval(_, score)) = endgame {
+            return score;
         }
 
-        ret
+        let score = Self::general_eval(pos, params);
+
+        match endgame {
+            Some(Endgame::Scale(_, factor)) => score * factor / SCALE_NORMAL,
+            _ => score,
+        }
     }
 
-    fn do_eval(&self) -> i32 {
-        let mut ret = 0;
+    // The evaluation from the general terms alone, without any specialised
+    // endgame knowledge.
+    fn general_eval(pos: &'a Position, params: &'a EvalParams) -> i32 {
+        let mut sink = ScoreSink {
+            phase: Self::calc_phase_coef(pos.material_count),
+            score: 0,
+        };
 
-        ret += self.count_material();
-        ret += self.calc_psqt();
+        Self { pos, params }.do_eval(&mut sink);
 
-        ret
+        sink.score
     }
 
-    pub fn eval(pos: &'a Position) -> i32 {
-        Self { pos }.do_eval()
+    /// Evaluate `pos`, recording the contribution of every term.
+    pub fn trace(pos: &'a Position) -> EvalTrace {
+        Self::trace_with_params(pos, &DEFAULT_EVAL_PARAMS)
+    }
+
+    pub fn trace_with_params(pos: &'a Position, params: &'a EvalParams) -> EvalTrace {
+        let mut trace = EvalTrace {
+            phase: Self::calc_phase_coef(pos.material_count),
+            terms: Vec::new(),
+            endgame: None,
+            scale: SCALE_NORMAL,
+            eval: 0,
+        };
+
+        match endgame::probe(pos, params) {
+            Some(Endgame::Eval(name, score)) => {
+                trace.endgame = Some(name);
+                trace.add(EvalTerm::Endgame, Colour::White, score, score);
+            }
+            Some(Endgame::Scale(name, factor)) => {
+                Self { pos, params }.do_eval(&mut trace);
+
+                if factor != SCALE_NORMAL {
+                    trace.endgame = Some(name);
+                    trace.scale = factor;
+                    trace.eval = trace.eval * factor / SCALE_NORMAL;
+                }
+            }
+            None => Self { pos, params }.do_eval(&mut trace),
+        }
+
+        trace
+    }
+
+    /// Compute the coefficient of every evaluation parameter for `pos`, such
+    /// that the (untruncated) evaluation is the dot product of these
+    /// coefficients with [EvalParams::weights], before any specialised endgame
+    /// knowledge. Only non-zero coefficients are returned.
+    pub fn coefficients(pos: &Position) -> Vec<(usize, f32)> {
+        let mut coeffs = [0.0; NUM_PARAMS];
+        let game_phase = Self::calc_phase_coef(pos.material_count);
+
+        for colour in Colour::iter() {
+            let sign = match colour {
+                Colour::White => 1.0,
+                Colour::Black => -1.0,
+            };
+
+            for kind in PieceKind::iter() {
+                let piece = Piece::new(kind, colour);
+
+                for l in pos[piece].iter_pieces() {
+                    let sq = psqt_square(piece, l.to_idx() as usize);
+
+                    coeffs[EvalParams::material_idx(kind)] += sign;
+
+                    if kind == PieceKind::King {
+                        coeffs[EvalParams::psqt_idx(PsqtTable::KingMiddle, sq)] +=
+                            sign * game_phase;
+                        coeffs[EvalParams::psqt_idx(PsqtTable::KingEnd, sq)] +=
+                            sign * (1.0 - game_phase);
+                    } else {
+                        coeffs[EvalParams::psqt_idx(psqt_table(kind), sq)] += sign;
+                    }
+                }
+            }
+        }
+
+        coeffs
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != 0.0)
+            .map(|(i, c)| (i, *c))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::position::eval::Evaluator;
+    use crate::position::{
+        eval::{EvalTerm, Evaluator, DEFAULT_EVAL_PARAMS},
+        Position,
+    };
 
     #[test]
     fn game_phase_coeff() {
@@ -215,4 +575,41 @@ mod tests {
         assert_eq!(Evaluator::calc_phase_coef(25), 1.0);
         assert_eq!(Evaluator::calc_phase_coef(16), 0.6);
     }
+
+    #[test]
+    fn coefficients_match_eval() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/8/8/8/3p4/4K3 w - - 0 1",
+        ];
+
+        for fen in positions {
+            let pos = Position::from_fen(fen).unwrap();
+            let weights = DEFAULT_EVAL_PARAMS.weights();
+            let linear: f32 = Evaluator::coefficients(&pos)
+                .iter()
+                .map(|(i, c)| weights[*i] as f32 * c)
+                .sum();
+
+            // The evaluator truncates the tapered king terms, so allow for a
+            // small rounding difference.
+            let eval = Evaluator::general_eval(&pos, &DEFAULT_EVAL_PARAMS);
+            assert!((linear - eval as f32).abs() <= 2.0);
+        }
+    }
+
+    #[test]
+    fn trace_matches_eval() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let trace = Evaluator::trace(&pos);
+
+        assert_eq!(trace.eval, Evaluator::eval(&pos));
+        assert_eq!(trace.terms[0].term, EvalTerm::Material);
+        assert_eq!(trace.terms[0].mg, [14100, 14100]);
+    }
 }
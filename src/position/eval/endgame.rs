@@ -0,0 +1,392 @@
+//! Specialised knowledge for endgames with particular material
+//! configurations.
+//!
+//! Each endgame is keyed by its [MaterialKey]. An endgame either replaces the
+//! evaluation of the position entirely, or scales the normal evaluation down
+//! towards a draw.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::{
+    piece::{Colour, Piece, PieceKind},
+    position::{locus::Locus, Position},
+};
+
+use super::{kpk, EvalParams};
+
+/// Scale factors are expressed in 64ths of the normal evaluation.
+pub type ScaleFactor = i32;
+
+pub const SCALE_NORMAL: ScaleFactor = 64;
+pub const SCALE_DRAW: ScaleFactor = 0;
+const SCALE_OPPOSITE_BISHOPS: ScaleFactor = 24;
+
+/// A score that is a certain win, yet still well below a mate score.
+pub const KNOWN_WIN: i32 = 5000;
+
+/// A compact signature of the number of each kind of piece each side has.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialKey(u64);
+
+impl MaterialKey {
+    fn shift(kind: PieceKind, colour: Colour) -> u64 {
+        (colour as u64 * PieceKind::COUNT as u64 + kind as u64) * 4
+    }
+
+    pub fn from_position(pos: &Position) -> Self {
+        let mut key = 0;
+
+        for colour in Colour::iter() {
+            for kind in PieceKind::iter() {
+                let n = pos[Piece::new(kind, colour)].popcount() as u64;
+                key |= n.min(15) << Self::shift(kind, colour);
+            }
+        }
+
+        Self(key)
+    }
+
+    /// Build a key from a signature such as "KBNK", where the pieces before
+    /// the second king belong to `strong`.
+    fn from_code(code: &str, strong: Colour) -> Self {
+        let split = code[1..].find('K').expect("Code must have two kings") + 1;
+        let mut key = 0;
+
+        for (pieces, colour) in [(&code[..split], strong), (&code[split..], strong.next())] {
+            for c in pieces.chars() {
+                let kind = match c {
+                    'P' => PieceKind::Pawn,
+                    'N' => PieceKind::Knight,
+                    'B' => PieceKind::Bishop,
+                    'R' => PieceKind::Rook,
+                    'Q' => PieceKind::Queen,
+                    'K' => PieceKind::King,
+                    _ => panic!("Invalid piece {c} in endgame code"),
+                };
+
+                key += 1 << Self::shift(kind, colour);
+            }
+        }
+
+        Self(key)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum EndgameFn {
+    Eval(fn(&Position, &EvalParams, Colour) -> i32),
+    Scale(fn(&Position, Colour) -> ScaleFactor),
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    name: &'static str,
+    strong: Colour,
+    func: EndgameFn,
+}
+
+/// The result of looking up a position in the endgame registry.
+pub enum Endgame {
+    /// The position has a specialised evaluation, given from white's point of
+    /// view.
+    Eval(&'static str, i32),
+    /// The normal evaluation should be scaled by the given factor.
+    Scale(&'static str, ScaleFactor),
+}
+
+static REGISTRY: OnceLock<HashMap<MaterialKey, Entry>> = OnceLock::new();
+
+fn register(
+    map: &mut HashMap<MaterialKey, Entry>,
+    name: &'static str,
+    code: &str,
+    func: EndgameFn,
+) {
+    for strong in Colour::iter() {
+        map.entry(MaterialKey::from_code(code, strong))
+            .or_insert(Entry { name, strong, func });
+    }
+}
+
+fn build_registry() -> HashMap<MaterialKey, Entry> {
+    let mut map = HashMap::new();
+
+    for code in ["KK", "KNK", "KBK", "KNNK"] {
+        register(
+            &mut map,
+            "Insufficient material",
+            code,
+            EndgameFn::Eval(eval_draw),
+        );
+    }
+
+    register(&mut map, "KPK", "KPK", EndgameFn::Eval(eval_kpk));
+    register(&mut map, "KRK", "KRK", EndgameFn::Eval(eval_kxk));
+    register(&mut map, "KQK", "KQK", EndgameFn::Eval(eval_kxk));
+    register(&mut map, "KBNK", "KBNK", EndgameFn::Eval(eval_kbnk));
+
+    for n in 1..=8 {
+        let code = format!("KB{}K", "P".repeat(n));
+        register(&mut map, "KBPsK", &code, EndgameFn::Scale(scale_kbpsk));
+    }
+
+    for n in 0..=8 {
+        for m in 0..=8 {
+            let code = format!("KB{}KB{}", "P".repeat(n), "P".repeat(m));
+            register(
+                &mut map,
+                "Opposite bishops",
+                &code,
+                EndgameFn::Scale(scale_opposite_bishops),
+            );
+        }
+    }
+
+    map
+}
+
+/// Look up any specialised endgame knowledge for `pos`, valuing material with
+/// `params`.
+pub fn probe(pos: &Position, params: &EvalParams) -> Option<Endgame> {
+    let entry = REGISTRY
+        .get_or_init(build_registry)
+        .get(&MaterialKey::from_position(pos))?;
+
+    Some(match entry.func {
+        EndgameFn::Eval(f) => {
+            let score = f(pos, params, entry.strong);
+
+            Endgame::Eval(
+                entry.name,
+                match entry.strong {
+                    Colour::White => score,
+                    Colour::Black => -score,
+                },
+            )
+        }
+        EndgameFn::Scale(f) => Endgame::Scale(entry.name, f(pos, entry.strong)),
+    })
+}
+
+fn square(pos: &Position, p: Piece) -> Locus {
+    pos[p].iter_pieces().next().unwrap()
+}
+
+fn rank(l: Locus) -> i32 {
+    l.to_idx() as i32 / 8
+}
+
+fn file(l: Locus) -> i32 {
+    l.to_idx() as i32 % 8
+}
+
+fn distance(a: Locus, b: Locus) -> i32 {
+    i32::max((rank(a) - rank(b)).abs(), (file(a) - file(b)).abs())
+}
+
+fn is_dark(l: Locus) -> bool {
+    (rank(l) + file(l)) % 2 == 0
+}
+
+// Bonus for keeping the two kings close together.
+fn push_close(a: Locus, b: Locus) -> i32 {
+    (7 - distance(a, b)) * 10
+}
+
+// Bonus for driving a king towards the edge of the board.
+fn push_to_edge(l: Locus) -> i32 {
+    let f = i32::max(3 - file(l), file(l) - 4);
+    let r = i32::max(3 - rank(l), rank(l) - 4);
+
+    (f + r) * 20
+}
+
+fn material(pos: &Position, params: &EvalParams, colour: Colour) -> i32 {
+    PieceKind::iter()
+        .filter(|x| *x != PieceKind::King)
+        .map(|k| pos[Piece::new(k, colour)].popcount() as i32 * params.material(k))
+        .sum()
+}
+
+fn eval_draw(_pos: &Position, _params: &EvalParams, _strong: Colour) -> i32 {
+    0
+}
+
+// King and a major piece against a lone king: drive the king to the edge.
+fn eval_kxk(pos: &Position, params: &EvalParams, strong: Colour) -> i32 {
+    let strong_king = square(pos, Piece::new(PieceKind::King, strong));
+    let weak_king = square(pos, Piece::new(PieceKind::King, strong.next()));
+
+    KNOWN_WIN
+        + material(pos, params, strong)
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+}
+
+// King, bishop and knight against a lone king. Mate can only be forced in a
+// corner of the same colour as the bishop, so drive the king towards one.
+fn eval_kbnk(pos: &Position, params: &EvalParams, strong: Colour) -> i32 {
+    let strong_king = square(pos, Piece::new(PieceKind::King, strong));
+    let weak_king = square(pos, Piece::new(PieceKind::King, strong.next()));
+    let bishop = square(pos, Piece::new(PieceKind::Bishop, strong));
+
+    let corners = if is_dark(bishop) {
+        [(0, 0), (7, 7)]
+    } else {
+        [(0, 7), (7, 0)]
+    };
+
+    let corner_dist = corners
+        .iter()
+        .map(|(r, f)| (rank(weak_king) - r).abs() + (file(weak_king) - f).abs())
+        .min()
+        .unwrap();
+
+    KNOWN_WIN
+        + material(pos, params, strong)
+        + (14 - corner_dist) * 20
+        + push_close(strong_king, weak_king)
+}
+
+fn eval_kpk(pos: &Position, params: &EvalParams, strong: Colour) -> i32 {
+    // Normalise so that the strong side plays up the board.
+    let normalise = |l: Locus| match strong {
+        Colour::White => l.to_idx() as usize,
+        Colour::Black => l.to_idx() as usize ^ 56,
+    };
+
+    let strong_king = normalise(square(pos, Piece::new(PieceKind::King, strong)));
+    let weak_king = normalise(square(pos, Piece::new(PieceKind::King, strong.next())));
+    let pawn = normalise(square(pos, Piece::new(PieceKind::Pawn, strong)));
+
+    if !kpk::probe(strong_king, pawn, weak_king, pos.to_play() == strong) {
+        return 0;
+    }
+
+    KNOWN_WIN + params.material(PieceKind::Pawn) + (pawn / 8) as i32 * 10
+}
+
+// With only bishops of opposite colours and pawns, the weaker side can often
+// blockade the pawns on the squares the attacking bishop doesn't control.
+fn scale_opposite_bishops(pos: &Position, _strong: Colour) -> ScaleFactor {
+    let w = square(pos, Piece::new(PieceKind::Bishop, Colour::White));
+    let b = square(pos, Piece::new(PieceKind::Bishop, Colour::Black));
+
+    if is_dark(w) != is_dark(b) {
+        SCALE_OPPOSITE_BISHOPS
+    } else {
+        SCALE_NORMAL
+    }
+}
+
+// Rook pawns with a bishop that doesn't control the promotion square can't
+// win if the defending king reaches the corner.
+fn scale_kbpsk(pos: &Position, strong: Colour) -> ScaleFactor {
+    let pawns = pos[Piece::new(PieceKind::Pawn, strong)];
+    let pawn_file = file(pawns.iter_pieces().next().unwrap());
+
+    if (pawn_file != 0 && pawn_file != 7) || pawns.iter_pieces().any(|x| file(x) != pawn_file) {
+        return SCALE_NORMAL;
+    }
+
+    let promotion_rank = match strong {
+        Colour::White => 7,
+        Colour::Black => 0,
+    };
+    let queening = Locus::from_idx((promotion_rank * 8 + pawn_file) as u8).unwrap();
+    let bishop = square(pos, Piece::new(PieceKind::Bishop, strong));
+    let weak_king = square(pos, Piece::new(PieceKind::King, strong.next()));
+
+    if is_dark(bishop) != is_dark(queening) && distance(weak_king, queening) <= 1 {
+        SCALE_DRAW
+    } else {
+        SCALE_NORMAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        piece::PieceKind,
+        position::{
+            eval::{Evaluator, DEFAULT_EVAL_PARAMS},
+            Position,
+        },
+    };
+
+    use super::KNOWN_WIN;
+
+    fn eval(fen: &str) -> i32 {
+        Evaluator::eval(&Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn kpk() {
+        // The defending king blocks the pawn, so this is drawn whoever moves.
+        assert_eq!(eval("8/8/8/4k3/4P3/4K3/8/8 w - - 0 1"), 0);
+        assert_eq!(eval("8/8/8/4k3/4P3/4K3/8/8 b - - 0 1"), 0);
+
+        // The attacking king is on a key square.
+        assert!(eval("8/8/4k3/8/4K3/8/4P3/8 w - - 0 1") > KNOWN_WIN);
+        assert!(eval("8/8/4k3/8/4K3/8/4P3/8 b - - 0 1") > KNOWN_WIN);
+
+        // Rook pawn with the defending king in the corner.
+        assert_eq!(eval("k7/8/8/8/8/8/P7/K7 w - - 0 1"), 0);
+
+        // The pawn outruns the king.
+        assert!(eval("7k/8/8/8/8/8/P7/K7 w - - 0 1") > KNOWN_WIN);
+
+        // Same positions with the colours reversed.
+        assert_eq!(eval("8/8/8/4k3/4p3/4K3/8/8 b - - 0 1"), 0);
+        assert!(eval("8/4p3/8/4k3/8/4K3/8/8 w - - 0 1") < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn kbnk_correct_corner() {
+        // Dark squared bishop, so the king must be driven to a1 or h8.
+        let right = eval("7k/8/5K2/8/8/8/8/4BN2 w - - 0 1");
+        let wrong = eval("k7/8/2K5/8/8/8/8/4BN2 w - - 0 1");
+
+        assert!(right > KNOWN_WIN);
+        assert!(right > wrong);
+    }
+
+    #[test]
+    fn krk_drives_to_edge() {
+        let edge = eval("4k3/8/4K3/8/8/8/8/R7 w - - 0 1");
+        let centre = eval("8/8/8/4k3/8/2K5/8/R7 w - - 0 1");
+
+        assert!(edge > centre);
+        assert!(eval("8/8/8/4k3/8/2K5/8/r7 w - - 0 1") < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn material_from_params() {
+        let pos = Position::from_fen("4k3/8/4K3/8/8/8/8/R7 w - - 0 1").unwrap();
+        let mut params = DEFAULT_EVAL_PARAMS.clone();
+        params.set_material(PieceKind::Rook, params.material(PieceKind::Rook) + 50);
+
+        assert_eq!(
+            Evaluator::eval_with_params(&pos, &params),
+            Evaluator::eval(&pos) + 50
+        );
+    }
+
+    #[test]
+    fn drawish_scaling() {
+        assert_eq!(eval("8/8/8/3k4/8/8/8/3KNN2 w - - 0 1"), 0);
+
+        // Wrong rook pawn.
+        assert_eq!(eval("k7/8/8/8/P7/8/8/2B1K3 w - - 0 1"), 0);
+        assert!(eval("k7/8/8/8/P7/8/8/3BK3 w - - 0 1") > 0);
+
+        // Opposite coloured bishops are scaled down, same coloured ones aren't.
+        let opposite = eval("4k3/8/5b2/8/3P4/2P5/8/3BK3 w - - 0 1");
+        let same = eval("4k3/5b2/8/8/3P4/2P5/8/3BK3 w - - 0 1");
+
+        assert!(opposite > 0);
+        assert!(opposite < same);
+    }
+}
@@ -0,0 +1,183 @@
+//! A bitbase for the king and pawn versus king endgame.
+//!
+//! Positions are normalised so that the strong side is white and the pawn is
+//! on files a-d. The table is generated by retrograde analysis the first time
+//! it is probed.
+
+use std::sync::OnceLock;
+
+// Both kings, the side to move, the four pawn files and six pawn ranks.
+const MAX_INDEX: usize = 64 * 64 * 2 * 4 * 6;
+
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+const WHITE: usize = 0;
+const BLACK: usize = 1;
+
+static BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn rank(sq: usize) -> usize {
+    sq / 8
+}
+
+fn file(sq: usize) -> usize {
+    sq % 8
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    usize::max(rank(a).abs_diff(rank(b)), file(a).abs_diff(file(b)))
+}
+
+fn king_moves(sq: usize) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |x| distance(sq, *x) == 1)
+}
+
+fn pawn_attacks(psq: usize, sq: usize) -> bool {
+    rank(sq) == rank(psq) + 1 && file(sq).abs_diff(file(psq)) == 1
+}
+
+fn index(stm: usize, bksq: usize, wksq: usize, psq: usize) -> usize {
+    wksq | (bksq << 6) | (stm << 12) | (file(psq) << 13) | ((6 - rank(psq)) << 15)
+}
+
+fn decode(idx: usize) -> (usize, usize, usize, usize) {
+    let wksq = idx & 0x3f;
+    let bksq = (idx >> 6) & 0x3f;
+    let stm = (idx >> 12) & 0x1;
+    let psq = (6 - ((idx >> 15) & 0x7)) * 8 + ((idx >> 13) & 0x3);
+
+    (stm, bksq, wksq, psq)
+}
+
+fn initial_result(idx: usize) -> u8 {
+    let (stm, bksq, wksq, psq) = decode(idx);
+
+    if wksq == bksq
+        || wksq == psq
+        || bksq == psq
+        || distance(wksq, bksq) <= 1
+        || (stm == WHITE && pawn_attacks(psq, bksq))
+    {
+        return INVALID;
+    }
+
+    // The pawn can promote without being captured.
+    if stm == WHITE
+        && rank(psq) == 6
+        && wksq != psq + 8
+        && (distance(bksq, psq + 8) > 1 || distance(wksq, psq + 8) == 1)
+    {
+        return WIN;
+    }
+
+    if stm == BLACK {
+        // Stalemate.
+        if king_moves(bksq).all(|s| distance(wksq, s) <= 1 || pawn_attacks(psq, s)) {
+            return DRAW;
+        }
+
+        // The pawn can be captured.
+        if distance(bksq, psq) == 1 && distance(wksq, psq) > 1 {
+            return DRAW;
+        }
+    }
+
+    UNKNOWN
+}
+
+fn classify(db: &[u8], idx: usize) -> u8 {
+    let (stm, bksq, wksq, psq) = decode(idx);
+    let mut r = INVALID;
+
+    if stm == WHITE {
+        for s in king_moves(wksq) {
+            r |= db[index(BLACK, bksq, s, psq)];
+        }
+
+        if rank(psq) < 6 {
+            r |= db[index(BLACK, bksq, wksq, psq + 8)];
+        }
+
+        if rank(psq) == 1 && psq + 8 != wksq && psq + 8 != bksq {
+            r |= db[index(BLACK, bksq, wksq, psq + 16)];
+        }
+
+        if r & WIN != 0 {
+            WIN
+        } else if r & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            DRAW
+        }
+    } else {
+        for s in king_moves(bksq) {
+            r |= db[index(WHITE, s, wksq, psq)];
+        }
+
+        if r & DRAW != 0 {
+            DRAW
+        } else if r & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            WIN
+        }
+    }
+}
+
+fn generate() -> Vec<u64> {
+    let mut db: Vec<u8> = (0..MAX_INDEX).map(initial_result).collect();
+
+    loop {
+        let mut changed = false;
+
+        for idx in 0..MAX_INDEX {
+            if db[idx] == UNKNOWN {
+                let r = classify(&db, idx);
+
+                if r != UNKNOWN {
+                    db[idx] = r;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut bits = vec![0u64; MAX_INDEX / 64];
+
+    for (idx, r) in db.iter().enumerate() {
+        if *r == WIN {
+            bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    bits
+}
+
+/// Probe the bitbase. Squares are given as indices from a1, with the strong
+/// side playing up the board. Returns whether the strong side wins.
+pub(super) fn probe(
+    strong_king: usize,
+    pawn: usize,
+    weak_king: usize,
+    strong_to_move: bool,
+) -> bool {
+    let bits = BITBASE.get_or_init(generate);
+
+    // Mirror so that the pawn is always on files a-d.
+    let (wksq, psq, bksq) = if file(pawn) >= 4 {
+        (strong_king ^ 7, pawn ^ 7, weak_king ^ 7)
+    } else {
+        (strong_king, pawn, weak_king)
+    };
+
+    let idx = index(if strong_to_move { WHITE } else { BLACK }, bksq, wksq, psq);
+
+    bits[idx / 64] & (1 << (idx % 64)) != 0
+}
@@ -5,13 +5,16 @@ use nom::Finish;
 use strum::{EnumCount, IntoEnumIterator};
 
 use crate::{
-    parsers::fen::{parse_fen, Fen, FenElement},
+    mmove::CastlingMoveType,
+    parsers::fen::{parse_fen, CastlingFlag, Fen, FenElement},
+    piece::{Piece, PieceKind},
     position::locus::file,
 };
 
 use super::{
     builder::PositionBuilder,
-    locus::{Locus, Rank},
+    castling_rights::CastlingRights,
+    locus::{File, Locus, Rank},
     Position,
 };
 
@@ -41,7 +44,10 @@ impl TryFrom<Fen> for Position {
             for elm in elms.iter() {
                 match elm {
                     FenElement::Piece(p) => {
-                        pos = pos.with_piece_at(*p, loc.unwrap());
+                        let Some(l) = loc else {
+                            bail!("Too many pieces in FEN rank specification");
+                        };
+                        pos = pos.with_piece_at(*p, l);
                         shift(&mut loc)?;
                     }
                     FenElement::Space(n) => {
@@ -57,13 +63,81 @@ impl TryFrom<Fen> for Position {
             }
         }
 
-        Ok(pos
-            .with_castling_rights(value.castling_rights)
-            .with_next_turn(value.colour)
-            .build())
+        if let Some(ep) = value.en_passant {
+            pos = pos.with_en_passant(ep)?;
+        }
+
+        let mut pos = pos.with_next_turn(value.colour).build();
+        let chess960;
+
+        (pos.castling_rights, chess960) = castling_rights(&pos, &value.castling);
+        pos.chess960 = chess960;
+        pos.halfmove_clock = value.halfmove_clock.min(u16::MAX as u32) as u16;
+        pos.hash = pos.zobrist.from_position(&pos);
+
+        Ok(pos)
     }
 }
 
+// Resolve the castling availability of a FEN string against the board, also
+// returning whether it describes a Chess960 position.
+pub(super) fn castling_rights(pos: &Position, flags: &[CastlingFlag]) -> (CastlingRights, bool) {
+    let mut rights = CastlingRights::empty();
+    let mut chess960 = false;
+
+    for flag in flags {
+        let c = match flag {
+            CastlingFlag::Outermost(c, _) | CastlingFlag::Rook(c, _) => *c,
+        };
+
+        let rank = Position::back_rank(c);
+        let rook = pos[Piece::new(PieceKind::Rook, c)];
+        let king = pos[Piece::new(PieceKind::King, c)]
+            .iter_pieces()
+            .map(|x| x.to_rank_file())
+            .find(|(r, _)| *r == rank)
+            .map(|(_, f)| f as u8)
+            .unwrap_or(File::E as u8);
+
+        let (kind, file) = match *flag {
+            CastlingFlag::Outermost(_, kind) => {
+                let (mut files, standard): (Vec<_>, _) = match kind {
+                    CastlingMoveType::Kingside => (File::iter().rev().collect(), File::H),
+                    CastlingMoveType::Queenside => (File::iter().collect(), File::A),
+                };
+
+                // Without a rook, keep the right for the standard rook, as
+                // the FEN string says.
+                files.retain(|f| (*f as u8).cmp(&king) == (standard as u8).cmp(&king));
+                let file = files
+                    .into_iter()
+                    .find(|f| rook.has_piece_at(Locus::from_rank_file(rank, *f)))
+                    .unwrap_or(standard);
+
+                (kind, file)
+            }
+            CastlingFlag::Rook(_, file) => {
+                if file as u8 > king {
+                    (CastlingMoveType::Kingside, file)
+                } else {
+                    (CastlingMoveType::Queenside, file)
+                }
+            }
+        };
+
+        let standard = match kind {
+            CastlingMoveType::Kingside => File::H,
+            CastlingMoveType::Queenside => File::A,
+        };
+
+        // Shredder-FEN of a standard position, e.g. `HAha`, is still chess.
+        chess960 |= file != standard || king != File::E as u8;
+        rights[c].set_rook_file(kind, file);
+    }
+
+    (rights, chess960)
+}
+
 impl Position {
     pub fn from_fen(fen: impl ToString) -> Result<Self> {
         let fen = parse_fen(&fen.to_string())
@@ -86,7 +160,11 @@ impl Debug for Position {
 
 #[cfg(test)]
 mod tests {
-    use crate::{piece::Colour, position::Position};
+    use crate::{
+        mmove::CastlingMoveType,
+        piece::Colour,
+        position::{locus::loc, Position},
+    };
 
     #[test]
     fn starting_pos() {
@@ -113,6 +191,36 @@ mod tests {
         assert!(result.castling_rights[Colour::Black].queen_side());
     }
 
+    #[test]
+    fn chess960_castling_rights() {
+        // Shredder-FEN names the rook files outright.
+        let shredder =
+            Position::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9")
+                .unwrap();
+
+        // X-FEN uses KQkq for the outermost rook, as here.
+        let xfen =
+            Position::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9")
+                .unwrap();
+
+        assert_eq!(shredder, xfen);
+        assert!(shredder.is_chess960());
+        assert_eq!(
+            shredder.castling_rook(Colour::White, CastlingMoveType::Kingside),
+            Some(loc!(h 1))
+        );
+        assert_eq!(
+            shredder.castling_rook(Colour::Black, CastlingMoveType::Queenside),
+            Some(loc!(f 8))
+        );
+
+        // The standard start position isn't Chess960, whichever is used.
+        let std =
+            Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1").unwrap();
+        assert!(!std.is_chess960());
+        assert_eq!(std, Position::default());
+    }
+
     #[test]
     #[should_panic]
     fn too_many_pieces_on_rank() {
@@ -11,8 +11,10 @@ use super::{bitboard::BitBoard, locus::Locus, Position};
 mod bishop;
 mod king;
 mod knight;
+mod legal;
 mod magics;
 mod pawn;
+pub mod perft;
 mod queen;
 pub mod rays;
 mod rook;
@@ -87,28 +89,8 @@ impl<'a> MoveGen<'a> {
         self.is_loc_under_attack(king_loc, their_colour)
     }
 
-    pub fn perft(pos: &'a mut Position, depth: u32) -> Vec<(Move, u32)> {
-        fn _perft(pos: &mut Position, depth: u32) -> u32 {
-            if depth == 0 {
-                return 1;
-            }
-
-            let mut n = 0;
-
-            for m in MoveGen::new(pos).gen() {
-                let token = pos.make_move(m);
-                if MoveGen::new(pos).in_check(pos.to_play().next()) {
-                    pos.undo_move(token);
-                    continue;
-                }
-                let moves = _perft(pos, depth - 1);
-                n += moves;
-                pos.undo_move(token);
-            }
-
-            n
-        }
-
+    /// Generate the moves of `pos` which don't leave the mover in check.
+    pub fn legal_moves(pos: &mut Position) -> MoveList {
         let mut moves = MoveGen::new(pos).gen();
 
         moves.retain(|mmove| {
@@ -118,17 +100,11 @@ impl<'a> MoveGen<'a> {
             ret
         });
 
-        let results: Vec<_> = moves
-            .into_iter()
-            .map(|m| {
-                let token = pos.make_move(m);
-                let x = _perft(pos, depth - 1);
-                pos.undo_move(token);
-                (m, x)
-            })
-            .collect();
-
-        results
+        moves
+    }
+
+    pub fn perft(pos: &'a mut Position, depth: u32) -> Vec<(Move, u64)> {
+        perft::Perft::new(depth).split(pos)
     }
 }
 
@@ -151,8 +127,8 @@ mod tests {
         ];
 
         for pos in check_pos.iter() {
-            let mut pos = Position::from_fen(pos).unwrap();
-            let mgen = MoveGen::new(&mut pos);
+            let pos = Position::from_fen(pos).unwrap();
+            let mgen = MoveGen::new(&pos);
 
             assert!(mgen.in_check(Colour::White));
         }
@@ -167,15 +143,15 @@ mod tests {
         ];
 
         for pos in check_pos.iter() {
-            let mut pos = Position::from_fen(pos).unwrap();
-            let mgen = MoveGen::new(&mut pos);
+            let pos = Position::from_fen(pos).unwrap();
+            let mgen = MoveGen::new(&pos);
 
             assert!(mgen.in_check(Colour::Black));
         }
 
         // For sanity, the starting position shouldn't be in check.
-        assert!(!MoveGen::new(&mut Position::default()).in_check(Colour::White));
-        assert!(!MoveGen::new(&mut Position::default()).in_check(Colour::Black));
+        assert!(!MoveGen::new(&Position::default()).in_check(Colour::White));
+        assert!(!MoveGen::new(&Position::default()).in_check(Colour::Black));
     }
 
     #[test]
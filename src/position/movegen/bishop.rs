@@ -51,7 +51,7 @@ mod tests {
 
     #[test]
     fn loc_attack() {
-        let mut pos = PositionBuilder::new()
+        let pos = PositionBuilder::new()
             .with_piece_at(mkp!(White, Bishop), loc!(c 4))
             .with_piece_at(mkp!(White, Pawn), loc!(e 6))
             .with_piece_at(mkp!(Black, Pawn), loc!(d 3))
@@ -67,7 +67,7 @@ mod tests {
             loc!(d 3),
         ];
 
-        let mgen = MoveGen::new(&mut pos);
+        let mgen = MoveGen::new(&pos);
 
         for loc in Locus::iter_all_squares() {
             if attacked_squares.contains(&loc) {
@@ -5,7 +5,7 @@ use crate::{
     piece::{Colour, Piece, PieceKind},
     position::{
         bitboard::BitBoard,
-        locus::{loc, File, Locus},
+        locus::{File, Locus},
     },
 };
 
@@ -48,29 +48,6 @@ gen_king_move!(north, west);
 gen_king_move!(south, west);
 gen_king_move!(south, east);
 
-const BLOCKER_MASK_KINGSIDE: [BitBoard; 2] = [
-    BitBoard::empty()
-        .set_piece_at(loc!(f 1))
-        .set_piece_at(loc!(g 1)),
-    BitBoard::empty()
-        .set_piece_at(loc!(f 8))
-        .set_piece_at(loc!(g 8)),
-];
-
-const BLOCKER_MASK_QUEENSIDE: [BitBoard; 2] = [
-    BitBoard::empty()
-        .set_piece_at(loc!(b 1))
-        .set_piece_at(loc!(c 1))
-        .set_piece_at(loc!(d 1)),
-    BitBoard::empty()
-        .set_piece_at(loc!(b 8))
-        .set_piece_at(loc!(c 8))
-        .set_piece_at(loc!(d 8)),
-];
-
-const CHECK_SQ_KINGSIDE: [Locus; 2] = [loc!(f 1), loc!(f 8)];
-const CHECK_SQ_QUEENSIDE: [Locus; 2] = [loc!(d 1), loc!(d 8)];
-
 const fn gen_king_move(locus: Locus) -> BitBoard {
     BitBoard::empty()
         .opt_or(gen_king_move_north(locus))
@@ -83,6 +60,19 @@ const fn gen_king_move(locus: Locus) -> BitBoard {
         .opt_or(gen_king_move_south_west(locus))
 }
 
+// The squares from `a` to `b` inclusive, which must be on the same rank.
+fn span(a: Locus, b: Locus) -> BitBoard {
+    let (lo, hi) = if a.to_idx() <= b.to_idx() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+
+    (lo.to_idx()..=hi.to_idx()).fold(BitBoard::empty(), |bb, i| {
+        bb.set_piece_at(Locus::from_idx(i).unwrap())
+    })
+}
+
 const fn gen_king_moves() -> [BitBoard; 64] {
     let mut table: [BitBoard; 64] = [BitBoard::empty(); 64];
     let mut idx = 0;
@@ -115,36 +105,49 @@ impl MoveGen<'_> {
             self.moves.push(mgen.with_dst(dst).build())
         }
 
-        let castling_rights = self.position.castling_rights[self.position.to_play];
-        if castling_rights.king_side()
-            && (BLOCKER_MASK_KINGSIDE[self.position.to_play as usize] & self.blockers).is_empty()
-            && !self.is_loc_under_attack(
-                CHECK_SQ_KINGSIDE[self.position.to_play as usize],
-                self.position.to_play.next(),
-            )
-            && !self.in_check(self.position.to_play)
-        {
-            self.moves.push(
-                mgen.with_dst(Locus::from_rank_file(r, File::G))
-                    .is_castling_move(CastlingMoveType::Kingside)
-                    .build(),
-            );
+        for kind in [CastlingMoveType::Kingside, CastlingMoveType::Queenside] {
+            if self.can_castle(src, kind) {
+                let file = match kind {
+                    CastlingMoveType::Kingside => File::G,
+                    CastlingMoveType::Queenside => File::C,
+                };
+
+                self.moves.push(
+                    mgen.with_dst(Locus::from_rank_file(r, file))
+                        .is_castling_move(kind)
+                        .build(),
+                );
+            }
         }
+    }
 
-        if castling_rights.queen_side()
-            && (BLOCKER_MASK_QUEENSIDE[self.position.to_play as usize] & self.blockers).is_empty()
-            && !self.is_loc_under_attack(
-                CHECK_SQ_QUEENSIDE[self.position.to_play as usize],
-                self.position.to_play.next(),
-            )
-            && !self.in_check(self.position.to_play)
-        {
-            self.moves.push(
-                mgen.with_dst(Locus::from_rank_file(r, File::C))
-                    .is_castling_move(CastlingMoveType::Queenside)
-                    .build(),
-            );
+    // Whether the king on `king` may castle in the direction of `kind`. This
+    // is written for Chess960, where the king and rook can start on any file
+    // and one may even stay where it is.
+    fn can_castle(&self, king: Locus, kind: CastlingMoveType) -> bool {
+        let Some(rook) = self.position.castling_rook(self.position.to_play, kind) else {
+            return false;
+        };
+
+        let (r, _) = king.to_rank_file();
+        let (king_dst, rook_dst) = match kind {
+            CastlingMoveType::Kingside => (File::G, File::F),
+            CastlingMoveType::Queenside => (File::C, File::D),
+        };
+        let king_dst = Locus::from_rank_file(r, king_dst);
+        let rook_dst = Locus::from_rank_file(r, rook_dst);
+
+        // Every square either piece crosses must be empty, but for the king
+        // and rook themselves.
+        let others = self.blockers & !king.to_bitboard() & !rook.to_bitboard();
+        if !(others & (span(king, king_dst) | span(rook, rook_dst))).is_empty() {
+            return false;
         }
+
+        // The king may not castle out of, through or into check.
+        !span(king, king_dst)
+            .iter_pieces()
+            .any(|l| self.is_loc_under_attack(l, self.position.to_play.next()))
     }
 
     pub fn loc_attacked_by_king(&self, l: Locus, c: Colour) -> bool {
@@ -98,7 +98,7 @@ mod tests {
 
     #[test]
     fn loc_attack() {
-        let mut pos = PositionBuilder::new()
+        let pos = PositionBuilder::new()
             .with_piece_at(mkp!(White, Knight), loc!(c 4))
             .build();
 
@@ -113,7 +113,7 @@ mod tests {
             loc!(b 6),
         ];
 
-        let mgen = MoveGen::new(&mut pos);
+        let mgen = MoveGen::new(&pos);
 
         for loc in Locus::iter_all_squares() {
             if attacked_squares.contains(&loc) {
@@ -0,0 +1,152 @@
+use crate::{
+    mmove::MoveType,
+    piece::{Piece, PieceKind},
+    position::{bitboard::BitBoard, locus::Locus, Position},
+};
+
+use super::{
+    magics::{BISHOP_TABLES, ROOK_TABLES},
+    MoveGen, MoveList,
+};
+
+// The squares a slider on a square attacks, given the blocking pieces.
+type Attacks = fn(Locus, BitBoard) -> BitBoard;
+
+// The squares strictly between `a` and `b`, which must share a rank, file or
+// diagonal that `attacks` moves along.
+fn between(attacks: Attacks, a: Locus, b: Locus) -> BitBoard {
+    attacks(a, b.to_bitboard()) & attacks(b, a.to_bitboard())
+}
+
+impl<'a> MoveGen<'a> {
+    // Each piece of the side to move which is pinned to its king, along with
+    // the squares it may still move to: those between the king and the
+    // pinning piece, and the pinning piece itself.
+    fn pins(&self, king: Locus) -> Vec<(Locus, BitBoard)> {
+        let us = self.position.to_play;
+        let ours = self.position.all_pieces_for_colour(us);
+        let theirs = self.position.all_pieces_for_colour(us.next());
+        let queens = self.position[Piece::new(PieceKind::Queen, us.next())];
+        let mut ret = Vec::new();
+
+        let sliders: [(Attacks, BitBoard); 2] = [
+            (
+                |l, b| ROOK_TABLES.lookup(l, b),
+                self.position[Piece::new(PieceKind::Rook, us.next())] | queens,
+            ),
+            (
+                |l, b| BISHOP_TABLES.lookup(l, b),
+                self.position[Piece::new(PieceKind::Bishop, us.next())] | queens,
+            ),
+        ];
+
+        for (attacks, pinners) in sliders {
+            // Look through our own pieces to find their sliders lined up
+            // with the king.
+            for pinner in (attacks(king, theirs) & pinners).iter_pieces() {
+                let ray = between(attacks, king, pinner);
+                let blockers = ray & self.blockers;
+
+                if blockers.popcount() == 1 && !(blockers & ours).is_empty() {
+                    ret.push((
+                        blockers.iter_pieces().next().unwrap(),
+                        ray | pinner.to_bitboard(),
+                    ));
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Count the moves of `pos` which don't leave the mover in check. This
+    /// is the same as the length of [MoveGen::legal_moves], but only falls
+    /// back on making and undoing moves in the rare cases where pins and
+    /// attacks on the king's squares aren't enough: when in check, and for
+    /// castling and en passant.
+    pub fn count_legal_moves(pos: &mut Position) -> usize {
+        let us = pos.to_play;
+
+        if MoveGen::new(pos).in_check(us) {
+            return MoveGen::legal_moves(pos).len();
+        }
+
+        let king = pos[Piece::new(PieceKind::King, us)]
+            .iter_pieces()
+            .next()
+            .unwrap();
+        let gen = MoveGen::new(pos);
+        let pins = gen.pins(king);
+
+        // Squares the king moves to must be safe once it has left its own.
+        let without_king = MoveGen {
+            blockers: gen.blockers & !king.to_bitboard(),
+            ..MoveGen::new(pos)
+        };
+
+        let mut n = 0;
+        let mut special = MoveList::new();
+
+        for m in gen.gen() {
+            let legal = match m.kind {
+                MoveType::EnPassant | MoveType::Castle(_) => {
+                    special.push(m);
+                    false
+                }
+                _ if m.src == king => !without_king.is_loc_under_attack(m.dst, us.next()),
+                _ => pins
+                    .iter()
+                    .find(|(l, _)| *l == m.src)
+                    .is_none_or(|(_, ray)| ray.has_piece_at(m.dst)),
+            };
+
+            n += legal as usize;
+        }
+
+        for m in special {
+            let token = pos.make_move(m);
+            n += !MoveGen::new(pos).in_check(us) as usize;
+            pos.undo_move(token);
+        }
+
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::{movegen::MoveGen, Position};
+
+    #[test]
+    fn count_matches_legal_moves() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Pinned pieces, and an en passant capture which would expose
+            // the king along the rank.
+            "4k3/8/8/r2pP2K/8/8/1q6/4R3 w - d6 0 1",
+            "4k3/4r3/8/b7/8/8/3PN3/4K3 w - - 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+
+            for m in MoveGen::legal_moves(&mut pos.clone()) {
+                let token = pos.make_move(m);
+                assert_eq!(
+                    MoveGen::count_legal_moves(&mut pos),
+                    MoveGen::legal_moves(&mut pos).len(),
+                    "{fen} {m:?}"
+                );
+                pos.undo_move(token);
+            }
+
+            assert_eq!(
+                MoveGen::count_legal_moves(&mut pos),
+                MoveGen::legal_moves(&mut pos).len(),
+                "{fen}"
+            );
+        }
+    }
+}
@@ -207,7 +207,7 @@ impl Magics {
                 .iter()
                 .powerset()
                 .map(|x| x.iter().fold(0, |accum, x| accum | 1 << *x))
-                .map(|x| BitBoard::new(x))
+                .map(BitBoard::new)
                 .collect::<Vec<_>>();
 
             for blocker in blockers {
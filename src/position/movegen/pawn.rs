@@ -264,13 +264,13 @@ mod tests {
 
     #[test]
     fn loc_attack_white() {
-        let mut pos = PositionBuilder::new()
+        let pos = PositionBuilder::new()
             .with_piece_at(mkp!(White, Pawn), loc!(c 4))
             .with_piece_at(mkp!(White, Pawn), loc!(e 6))
             .with_piece_at(mkp!(White, Pawn), loc!(g 7))
             .build();
 
-        let mgen = MoveGen::new(&mut pos);
+        let mgen = MoveGen::new(&pos);
 
         for loc in Locus::iter_all_squares() {
             if loc == loc!(b 5)
@@ -289,11 +289,11 @@ mod tests {
 
     #[test]
     fn loc_attack_black() {
-        let mut pos = PositionBuilder::new()
+        let pos = PositionBuilder::new()
             .with_piece_at(mkp!(Black, Pawn), loc!(c 4))
             .build();
 
-        let mgen = MoveGen::new(&mut pos);
+        let mgen = MoveGen::new(&pos);
 
         for loc in Locus::iter_all_squares() {
             if loc == loc!(b 3) || loc == loc!(d 3) {
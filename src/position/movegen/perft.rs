@@ -0,0 +1,372 @@
+use std::{
+    fmt::Display,
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    mmove::{Move, MoveType},
+    position::{zobrist::ZobristKey, Position},
+};
+
+use super::MoveGen;
+
+pub mod divide;
+
+/// Detailed counts of the leaf nodes of a perft search, matching the columns
+/// of the commonly published perft tables.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub mates: u64,
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.en_passants += rhs.en_passants;
+        self.castles += rhs.castles;
+        self.promotions += rhs.promotions;
+        self.checks += rhs.checks;
+        self.mates += rhs.mates;
+    }
+}
+
+impl PerftStats {
+    fn leaf(pos: &mut Position, m: Move) -> Self {
+        let mut stats = Self {
+            nodes: 1,
+            captures: (m.capture.is_some() || m.kind == MoveType::EnPassant) as u64,
+            en_passants: (m.kind == MoveType::EnPassant) as u64,
+            castles: matches!(m.kind, MoveType::Castle(_)) as u64,
+            promotions: matches!(m.kind, MoveType::Promote(_)) as u64,
+            ..Default::default()
+        };
+
+        if MoveGen::new(pos).in_check(pos.to_play()) {
+            stats.checks = 1;
+            stats.mates = MoveGen::legal_moves(pos).is_empty() as u64;
+        }
+
+        stats
+    }
+}
+
+impl Display for PerftStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>12} {:>12} {:>6} {:>10} {:>12} {:>10} {:>11}",
+            self.nodes,
+            self.captures,
+            self.en_passants,
+            self.castles,
+            self.promotions,
+            self.checks,
+            self.mates
+        )
+    }
+}
+
+// A lockless hash table of node counts. Each entry stores the key XORed with
+// the data so that torn writes from concurrent threads are detected on probe.
+struct PerftTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftTable {
+    fn new(size_mb: usize) -> Self {
+        let n = (size_mb * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>()).max(1);
+
+        Self {
+            entries: (0..n)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    fn entry(&self, hash: ZobristKey) -> &[AtomicU64; 2] {
+        &self.entries[(hash % self.entries.len() as u64) as usize]
+    }
+
+    fn get(&self, hash: ZobristKey, depth: u32) -> Option<u64> {
+        let entry = self.entry(hash);
+        let key = entry[0].load(Ordering::Relaxed);
+        let data = entry[1].load(Ordering::Relaxed);
+
+        if key ^ data == hash && data & 0xff == depth as u64 {
+            Some(data >> 8)
+        } else {
+            None
+        }
+    }
+
+    fn insert(&self, hash: ZobristKey, depth: u32, nodes: u64) {
+        let entry = self.entry(hash);
+        let data = nodes << 8 | depth as u64;
+
+        entry[0].store(hash ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+}
+
+pub struct Perft {
+    depth: u32,
+    threads: usize,
+    table: Option<PerftTable>,
+}
+
+impl Perft {
+    pub fn new(depth: u32) -> Self {
+        Self {
+            depth,
+            threads: 1,
+            table: None,
+        }
+    }
+
+    /// Split the root moves across `threads` threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Cache node counts of sub-trees in a table of `size_mb` megabytes.
+    pub fn with_hash(mut self, size_mb: usize) -> Self {
+        self.table = Some(PerftTable::new(size_mb));
+        self
+    }
+
+    fn count(&self, pos: &mut Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        // Bulk count the leaves rather than making each move.
+        if depth == 1 {
+            return MoveGen::count_legal_moves(pos) as u64;
+        }
+
+        if let Some(n) = self.table.as_ref().and_then(|t| t.get(pos.hash(), depth)) {
+            return n;
+        }
+
+        let mut n = 0;
+
+        for m in MoveGen::legal_moves(pos) {
+            let token = pos.make_move(m);
+            n += self.count(pos, depth - 1);
+            pos.undo_move(token);
+        }
+
+        if let Some(t) = self.table.as_ref() {
+            t.insert(pos.hash(), depth, n);
+        }
+
+        n
+    }
+
+    fn detailed(pos: &mut Position, depth: u32) -> PerftStats {
+        let mut stats = PerftStats::default();
+
+        for m in MoveGen::legal_moves(pos) {
+            let token = pos.make_move(m);
+
+            if depth <= 1 {
+                stats += PerftStats::leaf(pos, m);
+            } else {
+                stats += Self::detailed(pos, depth - 1);
+            }
+
+            pos.undo_move(token);
+        }
+
+        stats
+    }
+
+    // Run `f` on every legal root move, sharing the moves between threads.
+    fn map_root<T: Send>(
+        &self,
+        pos: &Position,
+        f: impl Fn(&mut Position) -> T + Sync,
+    ) -> Vec<(Move, T)> {
+        let moves = MoveGen::legal_moves(&mut pos.clone());
+        let next = AtomicUsize::new(0);
+
+        let mut results: Vec<_> = thread::scope(|s| {
+            let workers: Vec<_> = (0..self.threads.min(moves.len()))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut pos = pos.clone();
+                        let mut ret = Vec::new();
+
+                        loop {
+                            let idx = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(m) = moves.get(idx) else {
+                                break;
+                            };
+
+                            let token = pos.make_move(*m);
+                            ret.push((idx, *m, f(&mut pos)));
+                            pos.undo_move(token);
+                        }
+
+                        ret
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap())
+                .collect()
+        });
+
+        results.sort_by_key(|(idx, _, _)| *idx);
+
+        results.into_iter().map(|(_, m, x)| (m, x)).collect()
+    }
+
+    /// Count the leaf nodes below each legal move of `pos`.
+    pub fn split(&self, pos: &Position) -> Vec<(Move, u64)> {
+        if self.depth == 0 {
+            return Vec::new();
+        }
+
+        self.map_root(pos, |pos| self.count(pos, self.depth - 1))
+    }
+
+    /// The total number of leaf nodes of `pos`.
+    pub fn nodes(&self, pos: &Position) -> u64 {
+        if self.depth == 0 {
+            return 1;
+        }
+
+        self.split(pos).iter().map(|(_, n)| n).sum()
+    }
+
+    /// Compute detailed statistics about the leaf nodes of `pos`. The
+    /// transposition table isn't used, since every leaf must be visited.
+    pub fn stats(&self, pos: &Position) -> PerftStats {
+        if self.depth == 0 {
+            return PerftStats {
+                nodes: 1,
+                ..Default::default()
+            };
+        }
+
+        self.map_root(pos, |pos| {
+            if self.depth == 1 {
+                // The root move has already been made, so classify it.
+                let m = pos.last_move().unwrap();
+                PerftStats::leaf(pos, m)
+            } else {
+                Self::detailed(pos, self.depth - 1)
+            }
+        })
+        .into_iter()
+        .fold(PerftStats::default(), |mut acc, (_, x)| {
+            acc += x;
+            acc
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position;
+
+    use super::{Perft, PerftStats};
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn hashed_and_threaded_match() {
+        let pos = Position::from_fen(KIWIPETE).unwrap();
+        let plain = Perft::new(4).split(&pos);
+
+        assert_eq!(plain.iter().map(|(_, n)| n).sum::<u64>(), 4085603);
+        assert_eq!(Perft::new(4).with_hash(16).split(&pos), plain);
+        assert_eq!(
+            Perft::new(4).with_threads(4).with_hash(16).split(&pos),
+            plain
+        );
+    }
+
+    #[test]
+    fn detailed_stats() {
+        let stats = Perft::new(3)
+            .with_threads(2)
+            .stats(&Position::from_fen(KIWIPETE).unwrap());
+
+        assert_eq!(
+            stats,
+            PerftStats {
+                nodes: 97862,
+                captures: 17102,
+                en_passants: 45,
+                castles: 3162,
+                promotions: 0,
+                checks: 993,
+                mates: 1,
+            }
+        );
+
+        let stats = Perft::new(4).stats(&Position::default());
+
+        assert_eq!(
+            stats,
+            PerftStats {
+                nodes: 197281,
+                captures: 1576,
+                en_passants: 0,
+                castles: 0,
+                promotions: 0,
+                checks: 469,
+                mates: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn chess960() {
+        // From the Chess960 perft results published alongside Stockfish's
+        // and Reinhard Scharnagl's move generators.
+        for (fen, nodes) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                [22, 593, 13440],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [28, 1120, 31058],
+            ),
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            assert!(pos.is_chess960());
+
+            for (depth, n) in nodes.into_iter().enumerate() {
+                assert_eq!(Perft::new(depth as u32 + 1).nodes(&pos), n, "{fen}");
+            }
+        }
+    }
+}
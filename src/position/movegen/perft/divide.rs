@@ -0,0 +1,401 @@
+//! Find where our move generator disagrees with a reference by recursively
+//! comparing perft divide output.
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nom::Finish;
+
+use crate::{
+    parsers::{perft::parse_divide_line, uci_move::UciMove},
+    position::Position,
+};
+
+use super::Perft;
+
+/// A source of reference perft divide results.
+pub trait DivideSource {
+    /// Count the leaf nodes below each move of the position reached by
+    /// playing `moves` from `fen`.
+    fn divide(&mut self, fen: &str, moves: &[UciMove], depth: u32) -> Result<Vec<(UciMove, u64)>>;
+}
+
+/// Reads divide output pasted in by the user from another engine.
+pub struct StdinDivide;
+
+impl DivideSource for StdinDivide {
+    fn divide(&mut self, fen: &str, moves: &[UciMove], depth: u32) -> Result<Vec<(UciMove, u64)>> {
+        print!("position fen \"{}\" moves ", fen);
+        moves.iter().for_each(|m| print!("{} ", m));
+        println!();
+        println!("Depth: {}", depth);
+        println!("Enter the perft output from another engine, followed by a blank newline:");
+
+        let mut ret = Vec::new();
+
+        for line in io::stdin().lock().lines() {
+            let line = line.context("Could not read line")?;
+
+            if line.is_empty() {
+                break;
+            }
+
+            ret.push(
+                parse_divide_line(&line)
+                    .finish()
+                    .map_err(|e| anyhow!("Could not parse perft line: {e}"))?
+                    .1,
+            );
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Runs `go perft` in a reference engine over a pipe. The engine must accept
+/// `position fen ... moves ...` and print one `move: nodes` line per move,
+/// followed by a `Nodes searched` line, as Stockfish does.
+pub struct EngineDivide {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl EngineDivide {
+    /// Spawn the engine `cmd`, passing it `args`.
+    pub fn spawn(cmd: &str, args: &[&str]) -> Result<Self> {
+        let mut child = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not spawn reference engine {cmd}"))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Spawn the engine run by `cmd`, a program followed by any arguments,
+    /// separated by whitespace.
+    pub fn from_command(cmd: &str) -> Result<Self> {
+        let mut words = cmd.split_whitespace();
+        let program = words.next().context("Empty engine command")?;
+
+        Self::spawn(program, &words.collect::<Vec<_>>())
+    }
+}
+
+impl DivideSource for EngineDivide {
+    fn divide(&mut self, fen: &str, moves: &[UciMove], depth: u32) -> Result<Vec<(UciMove, u64)>> {
+        let mut cmd = format!("position fen {fen}");
+
+        if !moves.is_empty() {
+            cmd.push_str(" moves");
+            moves.iter().for_each(|m| cmd.push_str(&format!(" {m}")));
+        }
+
+        writeln!(self.stdin, "{cmd}")?;
+        writeln!(self.stdin, "go perft {depth}")?;
+        self.stdin.flush()?;
+
+        let mut ret = Vec::new();
+
+        loop {
+            let mut line = String::new();
+
+            if self.stdout.read_line(&mut line)? == 0 {
+                bail!("Reference engine exited during perft");
+            }
+
+            let line = line.trim();
+
+            if line.starts_with("Nodes searched") {
+                return Ok(ret);
+            }
+
+            // Engines print other information, such as the time taken, so
+            // skip any line that isn't part of the divide.
+            if let Ok((_, x)) = parse_divide_line(line) {
+                ret.push(x);
+            }
+        }
+    }
+}
+
+impl Drop for EngineDivide {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.stdin.flush();
+
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+        }
+
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Divergence {
+    /// The move generators agree.
+    None,
+    /// After `moves`, the reference generates `mmove` but we don't.
+    Missing { moves: Vec<UciMove>, mmove: UciMove },
+    /// After `moves`, we generate `mmove` but the reference doesn't.
+    Extra { moves: Vec<UciMove>, mmove: UciMove },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (moves, mmove, msg) = match self {
+            Divergence::None => return write!(f, "Perft results match."),
+            Divergence::Missing { moves, mmove } => {
+                (moves, mmove, "exists in other engine, but not us")
+            }
+            Divergence::Extra { moves, mmove } => {
+                (moves, mmove, "exists in our engine, but not the other")
+            }
+        };
+
+        write!(f, "After moves [")?;
+        for (i, m) in moves.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { " " }, m)?;
+        }
+        write!(f, "] move {} {}.", mmove, msg)
+    }
+}
+
+/// Compare divide results from `source` with our own, descending into the
+/// first move whose node counts differ, until the exact position and move the
+/// two move generators disagree on is found.
+pub fn find_divergence(
+    source: &mut impl DivideSource,
+    fen: &str,
+    depth: u32,
+    threads: usize,
+) -> Result<Divergence> {
+    if depth == 0 {
+        bail!("Perft depth must be at least 1 to compare moves");
+    }
+
+    let mut pos = Position::from_fen(fen).context("Could not create position from FEN")?;
+    let mut moves = Vec::new();
+
+    for depth in (1..=depth).rev() {
+        let theirs = source.divide(fen, &moves, depth)?;
+        let ours: Vec<_> = Perft::new(depth)
+            .with_threads(threads)
+            .split(&pos)
+            .into_iter()
+            .map(|(m, n)| (m, UciMove::from_move(m, &pos), n))
+            .collect();
+
+        if let Some((mmove, _)) = theirs.iter().find(|(t, _)| !ours.iter().any(|o| o.1 == *t)) {
+            return Ok(Divergence::Missing {
+                moves,
+                mmove: *mmove,
+            });
+        }
+
+        let mut next = None;
+
+        for (m, uci, n) in ours {
+            match theirs.iter().find(|(t, _)| *t == uci) {
+                None => return Ok(Divergence::Extra { moves, mmove: uci }),
+                Some((_, t)) if *t != n && next.is_none() => next = Some((m, uci)),
+                _ => {}
+            }
+        }
+
+        match next {
+            Some((m, uci)) => {
+                pos.make_move(m).consume();
+                moves.push(uci);
+            }
+            None => return Ok(Divergence::None),
+        }
+    }
+
+    // Every move at depth 1 has exactly one leaf, so the reference must have
+    // reported something else for a move both sides generate.
+    bail!(
+        "After moves {:?}, the reference reports more than one node at depth 1",
+        moves.iter().map(|m| m.to_string()).collect::<Vec<_>>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{
+        mmove::Move,
+        parsers::uci_move::{parse_uci_move, UciMove},
+        position::{movegen::MoveGen, Position},
+    };
+
+    use super::{find_divergence, Divergence, DivideSource, EngineDivide};
+
+    // A reference that agrees with us, except that it forgets about a move
+    // after a given sequence of moves.
+    struct Buggy {
+        after: Vec<UciMove>,
+        forget: UciMove,
+    }
+
+    impl Buggy {
+        fn moves(&self, pos: &mut Position, line: &[UciMove]) -> Vec<Move> {
+            let mut moves = MoveGen::legal_moves(pos).to_vec();
+
+            if line == self.after {
+                moves.retain(|m| UciMove::from(*m) != self.forget);
+            }
+
+            moves
+        }
+
+        fn perft(&self, pos: &mut Position, line: &mut Vec<UciMove>, depth: u32) -> u64 {
+            if depth == 0 {
+                return 1;
+            }
+
+            let mut n = 0;
+
+            for m in self.moves(pos, line) {
+                let token = pos.make_move(m);
+                line.push(m.into());
+                n += self.perft(pos, line, depth - 1);
+                line.pop();
+                pos.undo_move(token);
+            }
+
+            n
+        }
+    }
+
+    impl DivideSource for Buggy {
+        fn divide(
+            &mut self,
+            fen: &str,
+            moves: &[UciMove],
+            depth: u32,
+        ) -> Result<Vec<(UciMove, u64)>> {
+            let mut pos = Position::from_fen(fen)?;
+            let mut line = moves.to_vec();
+
+            for m in moves {
+                pos.make_move(m.to_move(&pos).unwrap()).consume();
+            }
+
+            let mut ret = Vec::new();
+
+            for m in self.moves(&mut pos, &line) {
+                let token = pos.make_move(m);
+                line.push(m.into());
+                ret.push((m.into(), self.perft(&mut pos, &mut line, depth - 1)));
+                line.pop();
+                pos.undo_move(token);
+            }
+
+            Ok(ret)
+        }
+    }
+
+    fn mv(s: &str) -> UciMove {
+        parse_uci_move(s).unwrap().1
+    }
+
+    #[test]
+    fn finds_extra_move() {
+        let mut source = Buggy {
+            after: vec![mv("e2e4"), mv("d7d5")],
+            forget: mv("e4d5"),
+        };
+
+        let divergence = find_divergence(
+            &mut source,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            3,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            divergence,
+            Divergence::Extra {
+                moves: vec![mv("e2e4"), mv("d7d5")],
+                mmove: mv("e4d5"),
+            }
+        );
+    }
+
+    #[test]
+    fn agrees_with_itself() {
+        // The forgotten move is never legal, so the reference is correct.
+        let mut source = Buggy {
+            after: Vec::new(),
+            forget: mv("e2e5"),
+        };
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        assert_eq!(
+            find_divergence(&mut source, fen, 3, 1).unwrap(),
+            Divergence::None
+        );
+        assert!(find_divergence(&mut source, fen, 0, 1).is_err());
+    }
+
+    #[test]
+    fn finds_deep_divergence() {
+        let mut source = Buggy {
+            after: vec![mv("a2a3"), mv("a7a6"), mv("b2b3")],
+            forget: mv("b7b6"),
+        };
+
+        // The bug is at the last ply of the search, so the search must
+        // descend all the way to find it.
+        let divergence = find_divergence(
+            &mut source,
+            "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1",
+            4,
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            divergence,
+            Divergence::Extra {
+                moves: vec![mv("a2a3"), mv("a7a6"), mv("b2b3")],
+                mmove: mv("b7b6"),
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reference_engine() {
+        let stub = concat!(env!("CARGO_MANIFEST_DIR"), "/etc/perft-stub.sh");
+        let mut engine = EngineDivide::spawn("sh", &[stub]).unwrap();
+
+        let divergence = find_divergence(&mut engine, "k7/8/8/8/8/8/8/K7 w - - 0 1", 2, 1).unwrap();
+
+        assert_eq!(
+            divergence,
+            Divergence::Extra {
+                moves: vec![mv("a1b2")],
+                mmove: mv("a8b7"),
+            }
+        );
+    }
+}
@@ -162,7 +162,7 @@ const fn calc_rook_occ_mask() -> [BitBoard; 64] {
     let mut idx = 0;
 
     while idx < 64 {
-        ret[idx as usize] = ret[idx as usize]
+        ret[idx] = ret[idx]
             .or(NORTH_OCC_RAYS[idx])
             .or(EAST_OCC_RAYS[idx])
             .or(WEST_OCC_RAYS[idx])
@@ -179,7 +179,7 @@ const fn calc_bishop_occ_mask() -> [BitBoard; 64] {
     let mut idx = 0;
 
     while idx < 64 {
-        ret[idx as usize] = ret[idx as usize]
+        ret[idx] = ret[idx]
             .or(NORTH_EAST_OCC_RAYS[idx])
             .or(NORTH_WEST_OCC_RAYS[idx])
             .or(SOUTH_EAST_OCC_RAYS[idx])
@@ -51,7 +51,7 @@ mod tests {
 
     #[test]
     fn loc_attack() {
-        let mut pos = PositionBuilder::new()
+        let pos = PositionBuilder::new()
             .with_piece_at(mkp!(White, Rook), loc!(c 4))
             .with_piece_at(mkp!(White, Pawn), loc!(c 6))
             .with_piece_at(mkp!(Black, Pawn), loc!(f 4))
@@ -70,7 +70,7 @@ mod tests {
             loc!(f 4),
         ];
 
-        let mgen = MoveGen::new(&mut pos);
+        let mgen = MoveGen::new(&pos);
 
         for loc in Locus::iter_all_squares() {
             if attacked_squares.contains(&loc) {
@@ -0,0 +1,240 @@
+//! A position packed into a fixed number of bytes, to store many of them
+//! cheaply, e.g. as training data.
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    mmove::CastlingMoveType,
+    parsers::fen::CastlingFlag,
+    piece::{Colour, Piece, PieceKind},
+};
+
+use super::{
+    bitboard::BitBoard, builder::PositionBuilder, fen::castling_rights, locus::Locus, Position,
+};
+
+// The kind of a rook which may still castle, in place of its own.
+const CASTLING_ROOK: u8 = 6;
+const BLACK: u8 = 8;
+
+const BLACK_TO_PLAY: u8 = 1;
+const CHESS960: u8 = 2;
+const EN_PASSANT: u8 = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PackedPosition {
+    /// The squares with a piece on them.
+    occupancy: u64,
+    /// The pieces on the occupied squares, in order, a nibble each starting
+    /// with the low one: the kind, or `CASTLING_ROOK`, plus `BLACK` for a
+    /// black piece.
+    pieces: [u8; 16],
+    flags: u8,
+    en_passant: u8,
+    halfmove_clock: u8,
+}
+
+impl PackedPosition {
+    pub const SIZE: usize = 27;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut ret = [0; Self::SIZE];
+
+        ret[..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        ret[8..24].copy_from_slice(&self.pieces);
+        ret[24] = self.flags;
+        ret[25] = self.en_passant;
+        ret[26] = self.halfmove_clock;
+
+        ret
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        Self {
+            occupancy: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            pieces: bytes[8..24].try_into().unwrap(),
+            flags: bytes[24],
+            en_passant: bytes[25],
+            halfmove_clock: bytes[26],
+        }
+    }
+}
+
+impl TryFrom<&Position> for PackedPosition {
+    type Error = anyhow::Error;
+
+    /// Pack `pos`, which fails if it has more than 32 pieces or castling
+    /// rights without a rook to castle with. The halfmove clock is capped at
+    /// 255.
+    fn try_from(pos: &Position) -> Result<Self> {
+        let occupancy =
+            pos.all_pieces_for_colour(Colour::White) | pos.all_pieces_for_colour(Colour::Black);
+
+        if occupancy.popcount() > 32 {
+            bail!("Too many pieces to pack");
+        }
+
+        let mut rooks = Vec::new();
+        for c in [Colour::White, Colour::Black] {
+            for kind in [CastlingMoveType::Kingside, CastlingMoveType::Queenside] {
+                if let Some(l) = pos.castling_rook(c, kind) {
+                    if pos.piece_at_loc(l) != Some(Piece::new(PieceKind::Rook, c)) {
+                        bail!("{c:?} can't castle with a rook on {l}");
+                    }
+
+                    rooks.push(l);
+                }
+            }
+        }
+
+        let mut pieces = [0; 16];
+        for (n, l) in occupancy.iter_pieces().enumerate() {
+            let p = pos.piece_at_loc(l).unwrap();
+            let kind = if rooks.contains(&l) {
+                CASTLING_ROOK
+            } else {
+                p.kind() as u8
+            };
+            let colour = match p.colour() {
+                Colour::White => 0,
+                Colour::Black => BLACK,
+            };
+
+            pieces[n / 2] |= (kind | colour) << (n % 2 * 4);
+        }
+
+        let mut flags = 0;
+        if pos.to_play() == Colour::Black {
+            flags |= BLACK_TO_PLAY;
+        }
+        if pos.is_chess960() {
+            flags |= CHESS960;
+        }
+        if pos.en_passant.is_some() {
+            flags |= EN_PASSANT;
+        }
+
+        Ok(Self {
+            occupancy: occupancy.into(),
+            pieces,
+            flags,
+            en_passant: pos.en_passant.map_or(0, Locus::to_idx),
+            halfmove_clock: pos.halfmove_clock().min(u8::MAX as u16) as u8,
+        })
+    }
+}
+
+impl TryFrom<&PackedPosition> for Position {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &PackedPosition) -> Result<Self> {
+        let occupancy = BitBoard::new(value.occupancy);
+
+        if occupancy.popcount() > 32 {
+            bail!("Too many pieces in packed position");
+        }
+
+        let mut pos = PositionBuilder::new();
+        let mut castling = Vec::new();
+
+        for (n, l) in occupancy.iter_pieces().enumerate() {
+            let nibble = value.pieces[n / 2] >> (n % 2 * 4) & 0xf;
+            let colour = if nibble & BLACK != 0 {
+                Colour::Black
+            } else {
+                Colour::White
+            };
+
+            let kind = match nibble & !BLACK {
+                CASTLING_ROOK => {
+                    if l.to_rank_file().0 != Position::back_rank(colour) {
+                        bail!("Castling rook on {l}, off its back rank");
+                    }
+
+                    castling.push(CastlingFlag::Rook(colour, l.to_rank_file().1));
+                    PieceKind::Rook
+                }
+                x => PieceKind::try_from(x).with_context(|| format!("Invalid piece on {l}"))?,
+            };
+
+            pos = pos.with_piece_at(Piece::new(kind, colour), l);
+        }
+
+        if value.flags & EN_PASSANT != 0 {
+            let l = Locus::from_idx(value.en_passant).context("Invalid en passant square")?;
+            pos = pos.with_en_passant(l)?;
+        }
+
+        let colour = if value.flags & BLACK_TO_PLAY != 0 {
+            Colour::Black
+        } else {
+            Colour::White
+        };
+
+        let mut pos = pos.with_next_turn(colour).build();
+
+        (pos.castling_rights, _) = castling_rights(&pos, &castling);
+        pos.chess960 = value.flags & CHESS960 != 0;
+        pos.halfmove_clock = value.halfmove_clock as u16;
+        pos.hash = pos.zobrist.from_position(&pos);
+
+        // Bytes which decode may still give a position that move generation
+        // can't handle, such as one without a king.
+        pos.check_legal()?;
+
+        Ok(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::position::Position;
+
+    use super::PackedPosition;
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+            "nrbbqkrn/pppppppp/8/8/8/8/PPPPPPPP/NRBBQKRN w GBgb - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - 99 120",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let packed = PackedPosition::try_from(&pos).unwrap();
+            let bytes = packed.to_bytes();
+
+            assert_eq!(PackedPosition::from_bytes(&bytes), packed);
+            assert!(Position::try_from(&packed).unwrap() == pos, "{fen}");
+        }
+
+        let crowded = Position::from_fen("qqqqkqqq/qqqqqqqq/8/8/8/P7/QQQQQQQQ/QQQQKQQQ w - - 0 1");
+        assert!(PackedPosition::try_from(&crowded.unwrap()).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        // Whatever the bytes, unpacking them either fails or gives a legal
+        // position, rather than panicking.
+        for _ in 0..10000 {
+            let mut bytes = [0; PackedPosition::SIZE];
+            rng.fill(&mut bytes[..]);
+
+            if let Ok(pos) = Position::try_from(&PackedPosition::from_bytes(&bytes)) {
+                pos.check_legal().unwrap();
+            }
+        }
+
+        // Decodable, but with knights in place of the kings.
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut packed = PackedPosition::try_from(&pos).unwrap();
+        packed.pieces[0] = 0x91;
+        assert!(Position::try_from(&packed).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use rand_chacha::ChaCha8Rng;
 use strum::IntoEnumIterator;
 
 use super::{
+    castling_rights::CastlingRights,
     locus::{loc, File, Locus},
     Position,
 };
@@ -18,6 +19,12 @@ pub struct Zobrist {
     ep_file: [ZobristKey; 8],
 }
 
+impl Default for Zobrist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Zobrist {
     pub fn new() -> Self {
         let mut rng = ChaCha8Rng::seed_from_u64(0xdeadbeefd00dfeed);
@@ -68,6 +75,23 @@ impl Zobrist {
         self.castling_rights[c as usize * 2 + f_idx]
     }
 
+    /// The combined key of every castling right in `rights`.
+    pub fn castling_key(&self, rights: CastlingRights) -> ZobristKey {
+        let mut key = 0;
+
+        for c in Colour::iter() {
+            if rights[c].queen_side() {
+                key ^= self.castling_rights_key(c, loc!(a 1));
+            }
+
+            if rights[c].king_side() {
+                key ^= self.castling_rights_key(c, loc!(h 1));
+            }
+        }
+
+        key
+    }
+
     pub fn ep_key(&self, loc: Locus) -> ZobristKey {
         let (_, f) = loc.to_rank_file();
 
@@ -90,16 +114,7 @@ impl Zobrist {
             key ^= self.btm_key();
         }
 
-        for c in Colour::iter() {
-            let crights = pos.castling_rights[c];
-            if crights.queen_side() {
-                key ^= self.castling_rights_key(c, loc!(a 1));
-            }
-
-            if crights.king_side() {
-                key ^= self.castling_rights_key(c, loc!(h 1));
-            }
-        }
+        key ^= self.castling_key(pos.castling_rights);
 
         if let Some(ep) = pos.en_passant {
             key ^= self.ep_key(ep);
@@ -3,51 +3,117 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    thread::{self, sleep},
     time::{Duration, Instant},
 };
 
 use arrayvec::ArrayVec;
 use time::{TimeAction, TimeMan};
-use ttable::{EntryKind, TEntry, TTable};
+use trace::{NodeKind, SearchTrace, TraceEvent};
+use ttable::{EntryKind, TEntry};
 
 use crate::{
-    mmove::{Move, MoveType},
+    mmove::{Move, MoveType, PackedMove},
+    options::{self, Options},
     piece::Colour,
     position::{
-        eval::Evaluator,
+        eval::{EvalParams, Evaluator},
         movegen::{MoveGen, MoveList},
         Position,
     },
 };
 
+pub use engine::{Engine, Limits, SearchEvent, SearchHandle};
+pub use params::{ParamSpec, SearchParams};
+pub use time::DEFAULT_MOVE_OVERHEAD;
+pub use ttable::{TTable, DEFAULT_SIZE_MB as DEFAULT_HASH_MB};
+
+mod engine;
+mod params;
 mod time;
+pub mod trace;
 mod ttable;
 
 const MAX_PLY: usize = 100;
 
 type PvStack = ArrayVec<Move, MAX_PLY>;
+type ReportCallback = Box<dyn Fn(&SearchResults)>;
+type CurrMoveCallback = Box<dyn Fn(Move, usize)>;
+
+// How long to search before announcing each root move as it's searched.
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
 
-#[derive(Default)]
+/// One of the lines of a MultiPV search, with its own score.
+#[derive(Clone, Debug, Default)]
+pub struct PvLine {
+    pub pv: PvStack,
+    pub eval: i32,
+}
+
+#[derive(Clone, Default)]
 pub struct SearchResults {
     pub depth: usize,
+    /// The deepest ply reached, including the quiescence search.
+    pub seldepth: usize,
     pub pv: PvStack,
     pub eval: i32,
+    /// The best lines found, best first. The first is the same as `pv` and
+    /// `eval`.
+    pub lines: Vec<PvLine>,
+    /// The nodes searched by this iteration.
     pub nodes: u32,
+    /// The nodes searched by this and every earlier iteration.
+    pub total_nodes: u64,
+    /// The time since the search started.
+    pub time: Duration,
+    /// How full the transposition table is, in permille.
+    pub hashfull: usize,
     pub qnodes: u32,
     pub ttable_hits: u32,
+    /// The tree this iteration searched, if it was traced.
+    pub trace: Option<SearchTrace>,
     pub beta_cutoffs: u32,
     pub alpha_increases: u32,
 }
 
+impl SearchResults {
+    pub fn nps(&self) -> u64 {
+        (self.total_nodes as f64 / self.time.as_secs_f64().max(1e-9)) as u64
+    }
+}
+
 pub struct Search {
     pos: Position,
-    should_exit: Arc<AtomicBool>,
+    /// The hard time limit, past which the search stops.
+    deadline: Option<Instant>,
+    /// The nodes spent on the best root move in this iteration.
+    best_move_nodes: u32,
+    /// Set once the search has been stopped, so every ply unwinds.
+    aborted: bool,
+    /// The number of times the search has checked whether to stop.
+    polls: u32,
     pv: ArrayVec<PvStack, MAX_PLY>,
     ttable: TTable,
     time: TimeMan,
-    report_callback: Option<Box<dyn Fn(&SearchResults)>>,
+    params: SearchParams,
+    eval_params: EvalParams,
+    /// How much worse than even a draw is for the side to move at the root.
+    contempt: i32,
+    root_colour: Colour,
+    trace: Option<SearchTrace>,
+    report_callback: Option<ReportCallback>,
+    currmove_callback: Option<CurrMoveCallback>,
+    start: Instant,
+    /// When the clock started, which is later than the start when pondering.
+    clock_start: Instant,
+    hard_limit: Duration,
+    /// Set while pondering, until the opponent plays the move pondered on.
+    ponder: Option<Arc<AtomicBool>>,
     to_depth: Option<usize>,
+    stop: Option<Arc<AtomicBool>>,
+    infinite: bool,
+    multi_pv: usize,
+    /// Root moves already searched as an earlier line of this iteration.
+    excluded: Vec<Move>,
     results: SearchResults,
 }
 
@@ -57,7 +123,7 @@ pub const MATE: i32 = INF - 1;
 impl Search {
     pub fn order_moves(&self, moves: &mut MoveList) {
         // order captures first.
-        moves.sort_by(|x, y| y.mvv_lva().cmp(&x.mvv_lva()));
+        moves.sort_by_key(|x| std::cmp::Reverse(x.mvv_lva()));
 
         // then promotions.
         moves.sort_by(|x, y| {
@@ -76,87 +142,274 @@ impl Search {
 
         // Always investigate the corresponding node from the previous PV first
         if let Some(tentry) = self.ttable.lookup(self.pos.hash()) {
-            match tentry.kind {
-                EntryKind::Score(m) => {
-                    if let Some(idx) = moves.iter().position(|x| *x == m) {
-                        moves.swap(idx, 0);
-                    }
+            if let EntryKind::Score(m) = tentry.kind {
+                if let Some(idx) = moves.iter().position(|x| PackedMove::from(*x) == m) {
+                    moves.swap(idx, 0);
                 }
-                _ => {}
             }
         }
     }
 
+    fn stopped(&self) -> bool {
+        self.deadline.is_some_and(|x| Instant::now() >= x)
+            || self
+                .stop
+                .as_ref()
+                .is_some_and(|x| x.load(Ordering::Relaxed))
+    }
+
+    // Poll for the end of the search every thousand or so calls, remembering
+    // the answer so that the plies above return too.
+    fn check_stop(&mut self) -> bool {
+        self.polls = self.polls.wrapping_add(1);
+
+        if !self.aborted && self.polls & 0x3ff == 0 {
+            self.check_ponderhit();
+            self.aborted = self.stopped();
+        }
+
+        self.aborted
+    }
+
+    fn pondering(&self) -> bool {
+        self.ponder
+            .as_ref()
+            .is_some_and(|x| x.load(Ordering::Relaxed))
+    }
+
+    // Once the move pondered on is played, the clock starts, and the search
+    // goes on as a normal timed one.
+    fn check_ponderhit(&mut self) {
+        if self.ponder.is_some() && !self.pondering() {
+            self.ponder = None;
+            self.clock_start = Instant::now();
+
+            // Even a search stopped in its first iteration has a move to
+            // play, so the deadline applies at once.
+            if !self.infinite && self.to_depth.is_none() {
+                self.deadline = Some(self.clock_start + self.hard_limit);
+            }
+        }
+    }
+
+    // Neither a ponder search nor an infinite one may finish before it's
+    // told to, by the move pondered on being played or by being stopped,
+    // even when there's nothing left to search.
+    fn wait_for_stop(&self) {
+        while (self.pondering() || self.infinite) && !self.stopped() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
     pub fn get_initial_move(&mut self) -> Option<Move> {
-        let mut moves = MoveGen::new(&self.pos).gen();
-        moves.sort_by(|x, y| y.mvv_lva().cmp(&x.mvv_lva()));
+        let mut moves = MoveGen::legal_moves(&mut self.pos);
+        moves.sort_by_key(|x| std::cmp::Reverse(x.mvv_lva()));
         moves.first().copied()
     }
 
-    pub fn go(mut self) -> SearchResults {
+    pub fn go(&mut self) -> SearchResults {
+        self.run()
+    }
+
+    /// The transposition table, to keep for later searches.
+    pub fn into_ttable(self) -> TTable {
+        self.ttable
+    }
+
+    // Search by iterative deepening until the depth or time runs out, or the
+    // search is stopped.
+    fn run(&mut self) -> SearchResults {
+        self.start = Instant::now();
+        self.clock_start = self.start;
+        self.aborted = false;
+        self.root_colour = self.pos.to_play();
+        self.ttable.new_search();
+        self.ttable.set_draw_score(match self.root_colour {
+            Colour::White => -self.contempt,
+            Colour::Black => self.contempt,
+        });
         let mut depth = 1;
-        let mut deadline = Duration::MAX;
+        self.hard_limit = self.time.init(&self.params);
         let mut last_results = SearchResults::default();
+        let mut total_nodes = 0;
+        let lines = self
+            .multi_pv
+            .min(MoveGen::legal_moves(&mut self.pos).len())
+            .max(1);
 
         loop {
             self.results = SearchResults::default();
             self.results.depth = depth;
-            let now = Instant::now();
-            self.should_exit = Arc::new(AtomicBool::new(false));
-            let should_exit = self.should_exit.clone();
-
-            if self.to_depth.is_none() {
-                thread::spawn(move || {
-                    sleep(deadline);
-                    should_exit.store(true, Ordering::Relaxed);
-                });
-            }
 
-            self.results.eval = self.search(-INF, INF, 0, depth as u32);
+            self.excluded.clear();
+
+            for idx in 0..lines {
+                let eval = self.search(-INF, INF, 0, depth as u32);
+
+                // The search was stopped before this iteration completed, so
+                // take the results of the previous one.
+                if self.aborted && depth > 1 {
+                    return last_results;
+                }
+
+                // Stopped during the first iteration, so play the best root
+                // move found so far, or failing that any legal move.
+                if self.aborted {
+                    return self.partial_results();
+                }
+
+                let pv = self.pv[0].clone();
 
-            // Take the last results from the previous iteration, since when the
-            // exit flag is true, we didn't complete the search.
-            if self.should_exit.load(Ordering::Relaxed) {
-                return last_results;
+                match pv.first() {
+                    Some(m) => self.excluded.push(*m),
+                    None if idx > 0 => break,
+                    None => {}
+                }
+
+                self.results.lines.push(PvLine { pv, eval });
             }
 
-            self.results.pv = self.pv[0].clone();
+            self.results
+                .lines
+                .sort_by_key(|x| std::cmp::Reverse(x.eval));
+            self.results.pv = self.results.lines[0].pv.clone();
+            self.results.eval = self.results.lines[0].eval;
+
+            total_nodes += self.results.nodes as u64;
+            self.results.total_nodes = total_nodes;
+            self.results.time = self.start.elapsed();
+            self.results.hashfull = self.ttable.hashfull();
+            self.results.trace = self.trace.as_mut().map(SearchTrace::take);
 
             if let Some(ref cb) = self.report_callback {
                 cb(&self.results);
             }
 
+            self.check_ponderhit();
+
+            // Checkmated or stalemated, so there's no move to play, nor any
+            // deeper to search.
+            if self.results.pv.is_empty() {
+                self.wait_for_stop();
+                return std::mem::take(&mut self.results);
+            }
+
             if let Some(srch_depth) = self.to_depth {
                 if srch_depth == self.results.depth {
-                    return self.results;
+                    self.wait_for_stop();
+                    return std::mem::take(&mut self.results);
                 }
-            } else {
+            } else if !self.infinite && self.ponder.is_none() {
                 match self.time.iter_complete(
+                    &self.params,
                     self.results.eval,
                     *self.results.pv.first().unwrap(),
-                    now.elapsed(),
+                    self.best_move_nodes,
+                    self.results.nodes,
+                    self.clock_start.elapsed(),
                 ) {
-                    time::TimeAction::YieldResult => return self.results,
-                    TimeAction::Iterate(d) => deadline = d,
+                    TimeAction::YieldResult => return std::mem::take(&mut self.results),
+                    TimeAction::Iterate => {}
                 }
+
+                // The first iteration always completes, so there's a move to
+                // play, however short the time.
+                self.deadline = Some(self.clock_start + self.hard_limit);
             }
 
             if self.results.eval == MATE || self.results.eval == -MATE {
-                return self.results;
+                self.wait_for_stop();
+                return std::mem::take(&mut self.results);
+            }
+
+            // The PV stacks can't hold a deeper search.
+            if depth == MAX_PLY - 1 {
+                self.wait_for_stop();
+                return std::mem::take(&mut self.results);
             }
 
             depth += 1;
-            last_results = self.results;
+            last_results = std::mem::take(&mut self.results);
         }
     }
 
-    fn quiescence(&mut self, mut alpha: i32, beta: i32) -> i32 {
-        let eval = Evaluator::eval(&self.pos);
+    // The results of a first iteration that didn't complete.
+    fn partial_results(&mut self) -> SearchResults {
+        if self.results.pv.is_empty() {
+            let m = self.get_initial_move();
+            self.results.pv.extend(m);
+            self.results.eval = 0;
+        }
+
+        self.results.lines = vec![PvLine {
+            pv: self.results.pv.clone(),
+            eval: self.results.eval,
+        }];
+        self.results.total_nodes = self.results.nodes as u64;
+        self.results.time = self.start.elapsed();
+
+        std::mem::take(&mut self.results)
+    }
+
+    // The score of a draw for the side to move.
+    fn draw_score(&self) -> i32 {
+        if self.pos.to_play() == self.root_colour {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    // Start tracing a node, if it's traced, returning whether it is.
+    fn trace_enter(
+        &mut self,
+        kind: NodeKind,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+        depth: u32,
+    ) -> bool {
+        match self.trace {
+            Some(ref mut t) => {
+                let mmove = if ply > 0 { self.pos.last_move() } else { None };
+                t.enter(kind, mmove, ply, depth, alpha, beta)
+            }
+            None => false,
+        }
+    }
+
+    fn trace_event(&mut self, ply: usize, event: TraceEvent) {
+        if let Some(ref mut t) = self.trace {
+            t.event(ply, event);
+        }
+    }
+
+    fn quiescence(&mut self, alpha: i32, beta: i32, ply: usize) -> i32 {
+        let traced = self.trace_enter(NodeKind::Quiescence, alpha, beta, ply, 0);
+        let score = self.quiescence_node(alpha, beta, ply);
+
+        if traced {
+            self.trace.as_mut().unwrap().exit(score);
+        }
+
+        score
+    }
+
+    fn quiescence_node(&mut self, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        if self.check_stop() {
+            self.trace_event(ply, TraceEvent::Aborted);
+            return 0;
+        }
+
+        self.results.seldepth = self.results.seldepth.max(ply);
+
+        let eval = Evaluator::eval_with_params(&self.pos, &self.eval_params);
         let stand_pat = if self.pos.to_play() == Colour::White {
             eval
         } else {
             -eval
         };
+        self.trace_event(ply, TraceEvent::StandPat(stand_pat));
 
         if stand_pat > beta {
             return beta;
@@ -166,11 +419,7 @@ impl Search {
             alpha = stand_pat;
         }
 
-        if (self.results.nodes & 0xfff == 0xfff) && self.should_exit.load(Ordering::Relaxed) {
-            return 0;
-        }
-
-        let mut cap_moves = MoveGen::new(&mut self.pos).gen();
+        let mut cap_moves = MoveGen::new(&self.pos).gen();
         cap_moves.retain(|x| x.capture.is_some());
         self.order_moves(&mut cap_moves);
 
@@ -183,9 +432,16 @@ impl Search {
                 self.pos.undo_move(token);
                 continue;
             }
-            let score = -self.quiescence(-beta, -alpha);
+            let score = -self.quiescence(-beta, -alpha, ply + 1);
             self.pos.undo_move(token);
+
+            if self.aborted {
+                self.trace_event(ply, TraceEvent::Aborted);
+                return 0;
+            }
+
             if score >= beta {
+                self.trace_event(ply, TraceEvent::Cutoff(cap_move));
                 return beta;
             }
             if score > alpha {
@@ -196,9 +452,26 @@ impl Search {
         alpha
     }
 
-    fn search(&mut self, mut alpha: i32, beta: i32, ply: usize, depth: u32) -> i32 {
-        if let Some(entry) = self.ttable.lookup(self.pos.hash()) {
+    fn search(&mut self, alpha: i32, beta: i32, ply: usize, depth: u32) -> i32 {
+        let traced = self.trace_enter(NodeKind::Search, alpha, beta, ply, depth);
+        let score = self.search_node(alpha, beta, ply, depth);
+
+        if traced {
+            self.trace.as_mut().unwrap().exit(score);
+        }
+
+        score
+    }
+
+    fn search_node(&mut self, mut alpha: i32, beta: i32, ply: usize, depth: u32) -> i32 {
+        // The table knows nothing of the root moves left out of this search.
+        let excluding = ply == 0 && !self.excluded.is_empty();
+
+        // The root always needs searching for a move to play, even when the
+        // table, kept from an earlier search, already has its score.
+        if let Some(entry) = self.ttable.lookup(self.pos.hash()).filter(|_| ply > 0) {
             if entry.kind.is_score() && (entry.eval == -MATE || entry.eval == MATE) {
+                self.trace_event(ply, TraceEvent::TtHit);
                 return entry.eval;
             }
 
@@ -207,15 +480,18 @@ impl Search {
                 match entry.kind {
                     EntryKind::Score(_) => {
                         self.pv[ply].clear();
+                        self.trace_event(ply, TraceEvent::TtHit);
                         return entry.eval;
                     }
                     EntryKind::Alpha => {
                         if entry.eval <= alpha {
+                            self.trace_event(ply, TraceEvent::TtHit);
                             return alpha;
                         }
                     }
                     EntryKind::Beta => {
                         if entry.eval >= beta {
+                            self.trace_event(ply, TraceEvent::TtHit);
                             return beta;
                         }
                     }
@@ -223,16 +499,20 @@ impl Search {
             }
         }
 
-        if self.pos.has_repeated() {
+        // A move must still be found at the root, even if the game's over.
+        if ply > 0 && (self.pos.has_repeated() || self.pos.is_fifty_move_draw()) {
             self.pv[ply].clear();
-            return 0;
+            self.trace_event(ply, TraceEvent::Draw);
+            return self.draw_score();
         }
 
         if depth == 0 {
-            return self.quiescence(alpha, beta);
+            return self.quiescence(alpha, beta, ply);
         }
 
-        let mut mmoves = MoveGen::new(&mut self.pos).gen();
+        self.results.seldepth = self.results.seldepth.max(ply);
+
+        let mut mmoves = MoveGen::new(&self.pos).gen();
         self.order_moves(&mut mmoves);
 
         let mut legal_moves = 0;
@@ -246,6 +526,11 @@ impl Search {
         };
 
         for m in mmoves {
+            if excluding && self.excluded.contains(&m) {
+                continue;
+            }
+
+            let nodes = self.results.nodes;
             let token = self.pos.make_move(m);
             if MoveGen::new(&self.pos).in_check(self.pos.to_play().next()) {
                 self.pos.undo_move(token);
@@ -253,12 +538,19 @@ impl Search {
             }
             legal_moves += 1;
 
+            if ply == 0 && self.start.elapsed() >= CURRMOVE_DELAY {
+                if let Some(ref cb) = self.currmove_callback {
+                    cb(m, legal_moves);
+                }
+            }
+
             if legal_moves == 1 {
                 eval = -self.search(-beta, -alpha, ply + 1, depth - 1);
             } else {
                 eval = -self.search(-alpha - 1, -alpha, ply + 1, depth - 1);
 
                 if (eval > alpha) && (eval < beta) {
+                    self.trace_event(ply, TraceEvent::ReSearch(m));
                     eval = -self.search(-beta, -alpha, ply + 1, depth - 1);
                 }
             }
@@ -266,7 +558,8 @@ impl Search {
             self.pos.undo_move(token);
 
             // Timeout detection.
-            if (self.results.nodes & 0xfff == 0xfff) && self.should_exit.load(Ordering::Relaxed) {
+            if self.check_stop() {
+                self.trace_event(ply, TraceEvent::Aborted);
                 return 0;
             }
 
@@ -277,12 +570,13 @@ impl Search {
                 tentry.eval = beta;
                 self.ttable.insert(tentry);
                 self.results.beta_cutoffs += 1;
+                self.trace_event(ply, TraceEvent::Cutoff(m));
                 return beta;
             }
 
             if eval > alpha {
                 alpha = eval;
-                tentry.kind = EntryKind::Score(m);
+                tentry.kind = EntryKind::Score(m.into());
                 self.pv[ply].clear();
                 self.pv[ply].push(m);
                 self.pv[ply + 1]
@@ -290,20 +584,30 @@ impl Search {
                     .into_iter()
                     .for_each(|m| self.pv[ply].push(m));
                 self.results.alpha_increases += 1;
+
+                if ply == 0 && !excluding {
+                    self.best_move_nodes = self.results.nodes - nodes;
+                    self.results.pv = self.pv[0].clone();
+                    self.results.eval = eval;
+                }
             }
         }
 
         if legal_moves == 0 {
             self.pv[ply].clear();
             return if MoveGen::new(&self.pos).in_check(self.pos.to_play()) {
+                self.trace_event(ply, TraceEvent::Checkmate);
                 -MATE
             } else {
-                0
+                self.trace_event(ply, TraceEvent::Stalemate);
+                self.draw_score()
             };
         }
 
-        tentry.eval = alpha;
-        self.ttable.insert(tentry);
+        if !excluding {
+            tentry.eval = alpha;
+            self.ttable.insert(tentry);
+        }
 
         alpha
     }
@@ -311,6 +615,8 @@ impl Search {
 
 pub struct SearchBuilder {
     srch: Search,
+    hash_mb: usize,
+    ttable: Option<TTable>,
 }
 
 impl SearchBuilder {
@@ -319,13 +625,32 @@ impl SearchBuilder {
             srch: Search {
                 pos,
                 results: SearchResults::default(),
-                should_exit: Arc::new(AtomicBool::new(false)),
+                deadline: None,
+                best_move_nodes: 0,
+                aborted: false,
+                polls: 0,
                 pv: ArrayVec::from_iter((0..MAX_PLY).map(|_| PvStack::new())),
-                ttable: TTable::new(),
+                ttable: TTable::with_size_mb(0),
                 time: TimeMan::new(),
+                params: SearchParams::default(),
+                eval_params: EvalParams::default(),
+                contempt: 0,
+                root_colour: Colour::White,
+                trace: None,
                 to_depth: None,
+                stop: None,
+                infinite: false,
+                multi_pv: 1,
+                excluded: Vec::new(),
                 report_callback: None,
+                currmove_callback: None,
+                start: Instant::now(),
+                clock_start: Instant::now(),
+                hard_limit: Duration::ZERO,
+                ponder: None,
             },
+            hash_mb: DEFAULT_HASH_MB,
+            ttable: None,
         }
     }
 
@@ -334,11 +659,24 @@ impl SearchBuilder {
         self
     }
 
+    /// Search for exactly `move_time`, rather than managing a time budget.
+    pub fn with_move_time(mut self, move_time: Duration) -> Self {
+        self.srch.time.move_time = Some(move_time);
+        self
+    }
+
     pub fn with_report_callback(mut self, callback: impl Fn(&SearchResults) + 'static) -> Self {
         self.srch.report_callback = Some(Box::new(callback));
         self
     }
 
+    /// Call `callback` with each root move, and its number, as it's searched.
+    /// Only moves searched after the first second are reported.
+    pub fn with_currmove_callback(mut self, callback: impl Fn(Move, usize) + 'static) -> Self {
+        self.srch.currmove_callback = Some(Box::new(callback));
+        self
+    }
+
     pub fn with_depth(mut self, depth: usize) -> Self {
         self.srch.to_depth = Some(depth);
         self
@@ -349,14 +687,167 @@ impl SearchBuilder {
         self
     }
 
-    pub fn build(self) -> Search {
+    /// The number of moves to make before the next time control.
+    pub fn with_moves_to_go(mut self, moves_to_go: u32) -> Self {
+        self.srch.time.moves_to_go = Some(moves_to_go);
+        self
+    }
+
+    /// Stop the search as soon as `flag` is set, e.g. by another thread, as
+    /// well as at the deadline.
+    pub fn with_stop_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.srch.stop = Some(flag);
+        self
+    }
+
+    /// Ponder, searching without regard to time until `flag` is cleared, when
+    /// the opponent plays the move pondered on, from which point the search
+    /// keeps to the clock.
+    pub fn with_ponder(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.srch.ponder = Some(flag);
+        self
+    }
+
+    /// Keep deepening the search, regardless of time, until it is stopped.
+    /// Even a search with nothing left to find, such as a mate, only
+    /// finishes then.
+    pub fn with_infinite(mut self) -> Self {
+        self.srch.infinite = true;
+        self
+    }
+
+    /// Search for the best `lines` root moves, each with its own PV, rather
+    /// than only the best one.
+    pub fn with_multi_pv(mut self, lines: usize) -> Self {
+        self.srch.multi_pv = lines;
+        self
+    }
+
+    /// Size the transposition table to `mb` megabytes.
+    pub fn with_hash_size(mut self, mb: usize) -> Self {
+        self.hash_mb = mb;
+        self
+    }
+
+    /// Keep `overhead` of the time left back for sending the move.
+    pub fn with_move_overhead(mut self, overhead: Duration) -> Self {
+        self.srch.time.move_overhead = overhead;
+        self
+    }
+
+    pub fn with_eval_params(mut self, params: EvalParams) -> Self {
+        self.srch.eval_params = params;
+        self
+    }
+
+    pub fn with_params(mut self, params: SearchParams) -> Self {
+        self.srch.params = params;
+        self
+    }
+
+    /// Score draws `contempt` centipawns below even for the side to move at
+    /// the root, so that it plays on against a weaker opponent.
+    pub fn with_contempt(mut self, contempt: i32) -> Self {
+        self.srch.contempt = contempt;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: &Limits) -> Self {
+        if let Some(x) = limits.depth {
+            self = self.with_depth(x);
+        }
+
+        if let Some(x) = limits.move_time {
+            self = self.with_move_time(x);
+        }
+
+        if let Some(x) = limits.time_left {
+            self = self.with_deadline(x);
+        }
+
+        if let Some(x) = limits.increment {
+            self = self.with_increment(x);
+        }
+
+        if let Some(x) = limits.moves_to_go {
+            self = self.with_moves_to_go(x);
+        }
+
+        if limits.infinite {
+            self = self.with_infinite();
+        }
+
+        self
+    }
+
+    /// Record the tree each iteration searches, down to `max_ply`, in its
+    /// results.
+    pub fn with_trace(mut self, max_ply: usize) -> Self {
+        self.srch.trace = Some(SearchTrace::new(max_ply));
+        self
+    }
+
+    /// Configure the search from the options a GUI has set.
+    pub fn with_options(self, opts: &Options) -> Self {
+        let mut ret = self
+            .with_eval_params(opts.eval_params())
+            .with_params(opts.search_params());
+
+        if let Some(x) = opts.spin(options::HASH) {
+            ret = ret.with_hash_size(x as usize);
+        }
+
+        if let Some(x) = opts.spin(options::MULTI_PV) {
+            ret = ret.with_multi_pv(x as usize);
+        }
+
+        if let Some(x) = opts.spin(options::MOVE_OVERHEAD) {
+            ret = ret.with_move_overhead(Duration::from_millis(x as u64));
+        }
+
+        if opts
+            .string(options::TRACE_FILE)
+            .is_some_and(|x| !x.is_empty())
+        {
+            if let Some(x) = opts.spin(options::TRACE_DEPTH) {
+                ret = ret.with_trace(x as usize);
+            }
+        }
+
+        // Analysis should show the objective score.
+        if let Some(x) = opts.spin(options::CONTEMPT) {
+            if opts.check(options::ANALYSE_MODE) != Some(true) {
+                ret = ret.with_contempt(x as i32);
+            }
+        }
+
+        ret
+    }
+
+    /// Search with a table kept from earlier searches, rather than a new one.
+    /// It's only used if it has the size the search is given.
+    pub fn with_ttable(mut self, ttable: TTable) -> Self {
+        self.ttable = Some(ttable);
+        self
+    }
+
+    pub fn build(mut self) -> Search {
+        self.srch.ttable = match self.ttable {
+            Some(t) if t.size_mb() == self.hash_mb => t,
+            _ => TTable::with_size_mb(self.hash_mb),
+        };
         self.srch
     }
 }
 
 #[cfg(test)]
 mod test {
-    use arrayvec::ArrayVec;
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        sync::{atomic::AtomicBool, Arc},
+        time::Duration,
+    };
 
     use crate::{
         mmove::MoveBuilder,
@@ -365,14 +856,18 @@ mod test {
             locus::loc,
             movegen::{MoveGen, MoveList},
             Position,
-        }, search::{ttable::{EntryKind, TEntry}, MATE},
+        },
+        search::{
+            ttable::{EntryKind, TEntry},
+            MATE,
+        },
     };
 
-    use super::SearchBuilder;
+    use super::{Options, SearchBuilder};
 
     #[test]
     fn move_ordering() {
-        let mut pos = Position::default();
+        let pos = Position::default();
         let principle_move = MoveBuilder::new(mkp!(White, Pawn), loc!(g 2))
             .with_dst(loc!(g 3))
             .build();
@@ -381,11 +876,11 @@ mod test {
         srch.ttable.insert(TEntry {
             hash: pos.hash(),
             depth: 1,
-            kind: EntryKind::Score(principle_move),
+            kind: EntryKind::Score(principle_move.into()),
             eval: 5,
         });
 
-        let mut moves = MoveGen::new(&mut pos).gen();
+        let mut moves = MoveGen::new(&pos).gen();
         srch.order_moves(&mut moves);
         assert_eq!(*moves.first().unwrap(), principle_move);
 
@@ -423,13 +918,13 @@ mod test {
 
         assert_eq!(
             some_moves.to_vec(),
-            vec![
+            [
                 high_val_promote,
                 low_val_promote,
                 high_val_capture,
                 mid_val_capture,
                 low_val_capture,
-                no_capture,
+                no_capture
             ]
             .iter()
             .map(|x| x.build())
@@ -449,8 +944,8 @@ mod test {
 
     #[test]
     fn mate_3_pos2() {
-        let pos =
-            Position::from_fen("r1b1k2r/pp3p2/6p1/3pq3/1P4P1/P2BPQp1/5PP1/R4RK1 b kq - 1 20").unwrap();
+        let pos = Position::from_fen("r1b1k2r/pp3p2/6p1/3pq3/1P4P1/P2BPQp1/5PP1/R4RK1 b kq - 1 20")
+            .unwrap();
 
         let results = SearchBuilder::new(pos).with_depth(6).build().go();
 
@@ -459,8 +954,8 @@ mod test {
 
     #[test]
     fn mate_3_pos3() {
-        let pos =
-            Position::from_fen("1r4k1/4pp1p/3p2p1/1P1Pn3/Q3P3/3n2PP/5qBK/1R3R2 b - - 1 31").unwrap();
+        let pos = Position::from_fen("1r4k1/4pp1p/3p2p1/1P1Pn3/Q3P3/3n2PP/5qBK/1R3R2 b - - 1 31")
+            .unwrap();
 
         let results = SearchBuilder::new(pos).with_depth(6).build().go();
 
@@ -469,11 +964,170 @@ mod test {
 
     #[test]
     fn mate_4_pos1() {
-        let pos =
-            Position::from_fen("1r4k1/4pp1p/3p2p1/1P1Pn3/Q3P3/3n2PP/5qBK/1R3R2 b - - 1 31").unwrap();
+        let pos = Position::from_fen("1r4k1/4pp1p/3p2p1/1P1Pn3/Q3P3/3n2PP/5qBK/1R3R2 b - - 1 31")
+            .unwrap();
 
         let results = SearchBuilder::new(pos).with_depth(6).build().go();
 
         assert_eq!(results.eval, MATE);
     }
+
+    #[test]
+    fn multi_pv() {
+        let pos =
+            Position::from_fen("4r1k1/p1qn1ppp/1p3n2/4NR2/3P4/B5Q1/P1r3PP/R6K w - - 1 20").unwrap();
+
+        let results = SearchBuilder::new(pos)
+            .with_depth(4)
+            .with_multi_pv(3)
+            .build()
+            .go();
+
+        assert_eq!(results.lines.len(), 3);
+        assert_eq!(results.lines[0].pv, results.pv);
+        assert_eq!(results.lines[0].eval, results.eval);
+        assert!(results.lines.windows(2).all(|x| x[0].eval >= x[1].eval));
+
+        let first: Vec<_> = results.lines.iter().map(|x| x.pv[0]).collect();
+        assert!(first[0] != first[1] && first[1] != first[2] && first[0] != first[2]);
+    }
+
+    #[test]
+    fn multi_pv_few_moves() {
+        // The only legal move is the king taking the rook.
+        let pos = Position::from_fen("7k/8/8/8/8/8/1r6/K7 w - - 0 1").unwrap();
+
+        let results = SearchBuilder::new(pos)
+            .with_depth(2)
+            .with_multi_pv(5)
+            .build()
+            .go();
+
+        assert_eq!(results.lines.len(), 1);
+    }
+
+    #[test]
+    fn results_stats() {
+        let depths = Rc::new(RefCell::new(Vec::new()));
+        let results = SearchBuilder::new(Position::default())
+            .with_depth(4)
+            .with_report_callback({
+                let depths = depths.clone();
+                move |x| depths.borrow_mut().push((x.nodes as u64, x.total_nodes))
+            })
+            .build()
+            .go();
+
+        assert!(results.seldepth >= results.depth);
+        assert_eq!(
+            depths.borrow().iter().map(|x| x.0).sum::<u64>(),
+            results.total_nodes
+        );
+        assert!(results.time > Duration::ZERO);
+        assert!(results.nps() > 0);
+    }
+
+    #[test]
+    fn with_options() {
+        let mut opts = Options::engine();
+        opts.set("MultiPV", Some("2")).unwrap();
+        opts.set("Hash", Some("1")).unwrap();
+
+        let results = SearchBuilder::new(Position::default())
+            .with_options(&opts)
+            .with_depth(5)
+            .build()
+            .go();
+
+        assert_eq!(results.lines.len(), 2);
+    }
+
+    #[test]
+    fn stopped_in_first_iteration() {
+        let mut pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let legal = MoveGen::legal_moves(&mut pos);
+
+        // Stop at the first poll, before any root move is searched, and at a
+        // later one, once some are.
+        for polls in [0x3ff, 0x300] {
+            let mut search = SearchBuilder::new(pos.clone())
+                .with_hash_size(1)
+                .with_stop_flag(Arc::new(AtomicBool::new(true)))
+                .build();
+            search.polls = polls;
+
+            let results = search.go();
+
+            assert_eq!(results.depth, 1);
+            assert_eq!(results.pv.len(), 1);
+            assert!(legal.contains(&results.pv[0]));
+            assert_eq!(results.lines[0].pv, results.pv);
+        }
+    }
+
+    #[test]
+    fn no_legal_moves() {
+        for fen in [
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            "7k/6Q1/6K1/8/8/8/8/8 b - - 0 1",
+        ] {
+            let results = SearchBuilder::new(Position::from_fen(fen).unwrap())
+                .with_hash_size(1)
+                .with_deadline(Duration::from_secs(1))
+                .build()
+                .go();
+
+            assert!(results.pv.is_empty(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn contempt() {
+        // Every move draws by the fifty move rule.
+        let pos = Position::from_fen("8/8/8/4k3/8/8/8/K7 b - - 99 80").unwrap();
+        let search = |opts: &Options| {
+            SearchBuilder::new(pos.clone())
+                .with_options(opts)
+                .with_hash_size(1)
+                .with_depth(3)
+                .build()
+                .go()
+                .eval
+        };
+
+        let mut opts = Options::engine();
+        assert_eq!(search(&opts), 0);
+
+        opts.set("Contempt", Some("20")).unwrap();
+        assert_eq!(search(&opts), -20);
+
+        opts.set("UCI_AnalyseMode", Some("true")).unwrap();
+        assert_eq!(search(&opts), 0);
+    }
+
+    #[test]
+    fn trace() {
+        let results = SearchBuilder::new(Position::default())
+            .with_hash_size(1)
+            .with_trace(1)
+            .with_depth(3)
+            .build()
+            .go();
+
+        let trace = results.trace.unwrap();
+        let root = &trace.nodes[trace.roots[0]];
+
+        // Every root move is searched, but nothing deeper than ply 1 kept.
+        assert_eq!(trace.roots.len(), 1);
+        assert_eq!(root.children.len(), 20);
+        assert_eq!(root.score, results.eval);
+        assert!(trace.nodes.iter().all(|x| x.ply <= 1));
+        assert!(root
+            .children
+            .iter()
+            .any(|x| !trace.nodes[*x].events.is_empty()));
+    }
 }
@@ -0,0 +1,361 @@
+//! An engine which searches on a thread of its own, keeping its
+//! transposition table from one search to the next, so that rmace can be
+//! embedded in multi-threaded or async applications.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::{mmove::Move, options::Options, position::Position};
+
+use super::{SearchBuilder, SearchResults, TTable};
+
+// The search recurses deeply, with large frames in debug builds, so needs
+// more than a spawned thread's default stack.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// How long to search for, as UCI's `go` gives it. Without any limit, the
+/// search takes a few seconds.
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+    pub depth: Option<usize>,
+    pub move_time: Option<Duration>,
+    /// The time left on the clock of the side to move.
+    pub time_left: Option<Duration>,
+    pub increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    /// Search until stopped.
+    pub infinite: bool,
+    /// Ponder on the opponent's time, ignoring the clock until
+    /// `SearchHandle::ponderhit`.
+    pub ponder: bool,
+}
+
+/// What a search reports as it runs.
+pub enum SearchEvent<'a> {
+    /// An iteration has completed.
+    Iteration(&'a SearchResults),
+    /// A root move, with its number, is being searched.
+    CurrMove(Move, usize),
+    /// The search has finished, with these results.
+    Finished(&'a SearchResults),
+}
+
+pub struct Engine {
+    options: Options,
+    ttable: Arc<Mutex<TTable>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::with_options(Options::engine())
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            ttable: Arc::new(Mutex::new(TTable::default())),
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Set an option, which takes effect from the next search.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        self.options.set(name, value)
+    }
+
+    /// Forget what earlier searches found, once any still running finishes.
+    pub fn new_game(&self) {
+        lock(&self.ttable).clear();
+    }
+
+    /// Start searching `pos`, returning at once with a handle to the search.
+    pub fn go(&self, pos: Position, limits: Limits) -> SearchHandle {
+        self.go_with_callback(pos, limits, |_| {})
+    }
+
+    /// As `go`, calling `callback`, from the search's thread, with each
+    /// event of the search.
+    pub fn go_with_callback(
+        &self,
+        pos: Position,
+        limits: Limits,
+        callback: impl Fn(SearchEvent) + Send + Sync + 'static,
+    ) -> SearchHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let ponder = Arc::new(AtomicBool::new(limits.ponder));
+        let latest = Arc::new(Mutex::new(None));
+        let options = self.options.clone();
+        let ttable = self.ttable.clone();
+        let callback = Arc::new(callback);
+
+        let thread = thread::Builder::new()
+            .name("search".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn({
+                let stop = stop.clone();
+                let ponder = ponder.clone();
+                let latest = latest.clone();
+
+                move || {
+                    // Holding the table for the whole search keeps searches
+                    // from overlapping.
+                    let mut ttable = lock(&ttable);
+
+                    let report = callback.clone();
+                    let currmove = callback.clone();
+                    let mut search = SearchBuilder::new(pos)
+                        .with_options(&options)
+                        .with_limits(&limits)
+                        .with_stop_flag(stop)
+                        .with_ttable(std::mem::take(&mut *ttable))
+                        .with_report_callback(move |results| {
+                            *lock(&latest) = Some(results.clone());
+                            report(SearchEvent::Iteration(results));
+                        })
+                        .with_currmove_callback(move |m, n| currmove(SearchEvent::CurrMove(m, n)));
+
+                    if limits.ponder {
+                        search = search.with_ponder(ponder);
+                    }
+
+                    let mut search = search.build();
+                    let results = search.run();
+                    *ttable = search.into_ttable();
+                    drop(ttable);
+
+                    callback(SearchEvent::Finished(&results));
+                    results
+                }
+            })
+            .expect("Could not start the search thread");
+
+        SearchHandle {
+            stop,
+            ponder,
+            latest,
+            thread: Some(thread),
+        }
+    }
+
+    /// As `go`, sending the results of each iteration down the channel
+    /// returned.
+    pub fn go_with_channel(
+        &self,
+        pos: Position,
+        limits: Limits,
+    ) -> (SearchHandle, Receiver<SearchResults>) {
+        let (tx, rx) = mpsc::channel();
+
+        let handle = self.go_with_callback(pos, limits, move |event| {
+            if let SearchEvent::Iteration(results) = event {
+                // The receiver may have been dropped, if it wasn't wanted.
+                let _ = tx.send(results.clone());
+            }
+        });
+
+        (handle, rx)
+    }
+}
+
+// A search which panicked leaves nothing half-updated worth refusing.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A search running on another thread. Dropping the handle stops the search.
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<SearchResults>>>,
+    thread: Option<JoinHandle<SearchResults>>,
+}
+
+impl SearchHandle {
+    /// Ask the search to stop, without waiting for it to.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// The opponent has played the move pondered on, so search as normal,
+    /// keeping to the clock from now on.
+    pub fn ponderhit(&self) {
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// The results of the last iteration completed so far.
+    pub fn latest(&self) -> Option<SearchResults> {
+        lock(&self.latest).clone()
+    }
+
+    /// Wait for the search to finish, returning its results.
+    pub fn join(mut self) -> SearchResults {
+        self.thread
+            .take()
+            .unwrap()
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    /// The search's results if it has finished, or the handle back if not.
+    pub fn try_join(self) -> std::result::Result<SearchResults, Self> {
+        if self.is_finished() {
+            Ok(self.join())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::{position::Position, search::MATE};
+
+    use super::{Engine, Limits, SearchHandle};
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_option("Hash", Some("1")).unwrap();
+        engine
+    }
+
+    fn depth(depth: usize) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn send_sync() {
+        fn check<T: Send + Sync>() {}
+
+        check::<Engine>();
+        check::<SearchHandle>();
+    }
+
+    #[test]
+    fn stop() {
+        let limits = Limits {
+            infinite: true,
+            ..Default::default()
+        };
+        let handle = engine().go(Position::default(), limits);
+
+        while handle.latest().is_none() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // An infinite search only finishes once it's stopped.
+        let Err(handle) = handle.try_join() else {
+            panic!("Infinite search finished");
+        };
+        handle.stop();
+
+        assert!(!handle.join().pv.is_empty());
+    }
+
+    #[test]
+    fn stop_after_mate() {
+        let limits = Limits {
+            infinite: true,
+            ..Default::default()
+        };
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let handle = engine().go(pos, limits);
+
+        // Having nothing more to find doesn't end an infinite search.
+        while handle.latest().is_none_or(|x| x.eval != MATE) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        handle.stop();
+        assert_eq!(handle.join().eval, MATE);
+    }
+
+    #[test]
+    fn ponder() {
+        let limits = Limits {
+            move_time: Some(Duration::from_millis(20)),
+            ponder: true,
+            ..depth(2)
+        };
+        let handle = engine().go(Position::default(), limits);
+
+        // The clock doesn't start until the move pondered on is played.
+        thread::sleep(Duration::from_millis(100));
+        assert!(!handle.is_finished());
+
+        handle.ponderhit();
+        assert_eq!(handle.join().depth, 2);
+    }
+
+    #[test]
+    fn channel() {
+        let (handle, rx) = engine().go_with_channel(Position::default(), depth(4));
+        let results = handle.join();
+
+        assert_eq!(rx.iter().map(|x| x.depth).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(results.depth, 4);
+    }
+
+    #[test]
+    fn keeps_ttable() {
+        let engine = engine();
+
+        let first = engine.go(Position::default(), depth(5)).join();
+        let second = engine.go(Position::default(), depth(5)).join();
+        assert!(second.total_nodes < first.total_nodes);
+        assert!(!second.pv.is_empty());
+
+        engine.new_game();
+        let third = engine.go(Position::default(), depth(5)).join();
+        assert_eq!(third.total_nodes, first.total_nodes);
+    }
+
+    #[test]
+    fn contempt_with_kept_ttable() {
+        let mut engine = engine();
+        engine.set_option("Contempt", Some("20")).unwrap();
+
+        // Every black move draws by the fifty move rule. The draws are bad
+        // for black when black is to play from the root, but good for black
+        // when white is, so the first search's scores mustn't be reused.
+        let after = Position::from_fen("8/8/8/4k3/8/8/8/1K6 b - - 99 80").unwrap();
+        assert_eq!(engine.go(after, depth(3)).join().eval, -20);
+
+        let before = Position::from_fen("8/8/8/4k3/8/8/8/K7 w - - 98 80").unwrap();
+        assert_eq!(engine.go(before, depth(4)).join().eval, -20);
+    }
+}
@@ -0,0 +1,128 @@
+//! The search's tunable parameters, which can be changed at runtime, e.g. by
+//! an SPSA tuner or, with the `spsa` feature, through UCI options.
+
+use anyhow::{anyhow, bail, Result};
+
+/// The name, default and bounds of one of the parameters.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+macro_rules! search_params {
+    ($($(#[doc = $doc:literal])* $name:ident: $default:literal, $min:literal..=$max:literal;)*) => {
+        /// Every parameter is an integer, so that it can be a UCI spin option.
+        /// Fractions are given in percent.
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub struct SearchParams {
+            $($(#[doc = $doc])* pub $name: i32,)*
+        }
+
+        impl Default for SearchParams {
+            fn default() -> Self {
+                Self {
+                    $($name: $default,)*
+                }
+            }
+        }
+
+        impl SearchParams {
+            pub const SPECS: &'static [ParamSpec] = &[
+                $(ParamSpec {
+                    name: stringify!($name),
+                    default: $default,
+                    min: $min,
+                    max: $max,
+                },)*
+            ];
+
+            pub fn get(&self, name: &str) -> Option<i32> {
+                match name {
+                    $(stringify!($name) => Some(self.$name),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut i32> {
+                match name {
+                    $(stringify!($name) => Some(&mut self.$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+search_params! {
+    /// Without a moves-to-go count, assume the game lasts this many more
+    /// moves.
+    moves_to_go: 30, 5..=80;
+    /// The share of the increment spent on each move.
+    inc_percent: 75, 0..=100;
+    /// The hard limit as a multiple of the soft limit.
+    hard_scale_percent: 400, 100..=1000;
+    /// The most of the time left the hard limit may be.
+    hard_fraction_percent: 50, 10..=90;
+    /// As `hard_fraction_percent`, on the last move before the time control.
+    hard_fraction_last_percent: 80, 10..=95;
+    /// The scale of the soft limit while the best move is new.
+    stability_base_percent: 160, 50..=300;
+    /// How much less time is used for each iteration the best move stays the
+    /// same.
+    stability_step_percent: 15, 0..=40;
+    /// The most iterations of stability which count.
+    stability_iters: 6, 1..=15;
+    /// The largest fall in score, in centipawns, which earns more time.
+    score_drop_max: 100, 0..=400;
+    /// The fall in score, in centipawns, which doubles the soft limit.
+    score_drop_scale: 200, 25..=800;
+    /// Subtracted from the share of nodes spent on the best move to scale
+    /// the soft limit.
+    node_fraction_base_percent: 150, 100..=250;
+    node_fraction_scale_percent: 135, 25..=300;
+    /// How much longer the next iteration is expected to take than the
+    /// whole search so far.
+    iter_growth_percent: 200, 100..=500;
+}
+
+impl SearchParams {
+    pub fn spec(name: &str) -> Option<&'static ParamSpec> {
+        Self::SPECS.iter().find(|x| x.name == name)
+    }
+
+    pub fn set(&mut self, name: &str, value: i32) -> Result<()> {
+        let spec = Self::spec(name).ok_or_else(|| anyhow!("No such parameter {name}"))?;
+
+        if value < spec.min || value > spec.max {
+            bail!("{name} must be between {} and {}", spec.min, spec.max);
+        }
+
+        *self.field_mut(name).unwrap() = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchParams;
+
+    #[test]
+    fn get_set() {
+        let mut params = SearchParams::default();
+
+        for spec in SearchParams::SPECS {
+            assert_eq!(params.get(spec.name), Some(spec.default));
+            assert!((spec.min..=spec.max).contains(&spec.default));
+        }
+
+        params.set("moves_to_go", 40).unwrap();
+        assert_eq!(params.moves_to_go, 40);
+
+        assert!(params.set("moves_to_go", 1000).is_err());
+        assert!(params.set("no_such_param", 1).is_err());
+        assert_eq!(params.get("moves_to_go"), Some(40));
+    }
+}
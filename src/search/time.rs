@@ -1,45 +1,36 @@
 use crate::mmove::Move;
-use arrayvec::ArrayVec;
-use itertools::Itertools;
 use std::time::Duration;
 
-const MAX_DEPTH: usize = 20;
-const MIN_EARLY_YIELD_DEPTH: usize = 8;
+use super::params::SearchParams;
+
+/// Kept back from every allocation for the time it takes to send the move.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(10);
+
+// The search time when there's no clock at all.
+const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);
+
+// A percentage parameter as a factor.
+fn factor(percent: i32) -> f64 {
+    percent as f64 / 100.0
+}
 
 pub enum TimeAction {
     YieldResult,
-    Iterate(Duration),
+    Iterate,
 }
 
 #[derive(Clone)]
 pub struct TimeMan {
     pub(super) time_left: Option<Duration>,
     pub(super) increment: Option<Duration>,
-    scores: ArrayVec<i32, MAX_DEPTH>,
-    best_moves: ArrayVec<Move, MAX_DEPTH>,
-}
-
-struct Stats {
-    max: i32,
-    min: i32,
-}
-
-impl Stats {
-    fn new() -> Self {
-        Self {
-            max: i32::MIN,
-            min: i32::MAX,
-        }
-    }
-
-    pub fn add_value(&mut self, v: i32) {
-        self.max = i32::max(self.max, v);
-        self.min = i32::min(self.min, v);
-    }
-
-    pub fn range(&self) -> i32 {
-        self.max - self.min
-    }
+    pub(super) move_time: Option<Duration>,
+    pub(super) moves_to_go: Option<u32>,
+    pub(super) move_overhead: Duration,
+    soft: Duration,
+    hard: Duration,
+    best_move: Option<Move>,
+    stability: u32,
+    last_score: Option<i32>,
 }
 
 impl TimeMan {
@@ -47,72 +38,173 @@ impl TimeMan {
         Self {
             time_left: None,
             increment: None,
-            scores: ArrayVec::new(),
-            best_moves: ArrayVec::new(),
+            move_time: None,
+            moves_to_go: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            soft: DEFAULT_MOVE_TIME,
+            hard: DEFAULT_MOVE_TIME,
+            best_move: None,
+            stability: 0,
+            last_score: None,
         }
     }
 
+    /// Work out the soft and hard limits of this move, returning the hard
+    /// limit, after which the search must stop.
+    pub fn init(&mut self, params: &SearchParams) -> Duration {
+        (self.soft, self.hard) = match (self.move_time, self.time_left) {
+            (Some(t), _) => (t, t),
+            (None, Some(t)) => {
+                let avail = t.saturating_sub(self.move_overhead);
+                let inc = self.increment.unwrap_or_default();
+                let mtg = self.moves_to_go.unwrap_or(params.moves_to_go as u32).max(1);
+
+                let soft = avail / mtg + inc.mul_f64(factor(params.inc_percent));
+                let fraction = if mtg == 1 {
+                    params.hard_fraction_last_percent
+                } else {
+                    params.hard_fraction_percent
+                };
+                let hard = soft
+                    .mul_f64(factor(params.hard_scale_percent))
+                    .min(avail.mul_f64(factor(fraction)));
+
+                (soft.min(hard), hard)
+            }
+            (None, None) => (DEFAULT_MOVE_TIME, DEFAULT_MOVE_TIME),
+        };
+
+        self.hard
+    }
+
+    /// Decide whether to start another iteration, given the results of the
+    /// last one. `best_move_nodes` is the share of its `nodes` that were spent
+    /// on `best_move`, and `elapsed` the time since the search started.
     pub fn iter_complete(
         &mut self,
+        params: &SearchParams,
         score: i32,
         best_move: Move,
-        time_taken: Duration,
+        best_move_nodes: u32,
+        nodes: u32,
+        elapsed: Duration,
     ) -> TimeAction {
-        self.scores.push(score);
-        self.best_moves.push(best_move);
-        let depth = self.best_moves.len();
-
-        if let Some(ref mut d) = self.time_left {
-            *d -= time_taken;
-
-            // If we have less than 5 millies remaining, yield now since even a
-            // depth 2 search could take longer.
-            if *d < Duration::from_millis(5) {
-                return TimeAction::YieldResult;
-            }
+        // A fixed time per move uses all of it, so only the hard limit stops
+        // the search.
+        if self.move_time.is_some() {
+            return if elapsed >= self.hard {
+                TimeAction::YieldResult
+            } else {
+                TimeAction::Iterate
+            };
         }
 
-        let percent_time_to_use = if score < -500 {
-            // Things haven't gone great. Use more time up in the hoeps that we
-            // can maybe recover the position.
-            0.35
+        if self.best_move == Some(best_move) {
+            self.stability += 1;
         } else {
-            0.15
-        };
+            self.stability = 0;
+        }
+        self.best_move = Some(best_move);
+
+        // The longer the best move has stayed the same, the less time is
+        // needed to be sure of it.
+        let stability = (factor(params.stability_base_percent)
+            - factor(params.stability_step_percent)
+                * self.stability.min(params.stability_iters as u32) as f64)
+            .max(0.0);
+
+        // When the score falls, spend longer looking for something better.
+        let drop = self
+            .last_score
+            .map(|x| (x - score).clamp(0, params.score_drop_max))
+            .unwrap_or(0);
+        let score_drop = 1.0 + drop as f64 / params.score_drop_scale as f64;
+        self.last_score = Some(score);
+
+        // A best move which took most of the effort is unlikely to be refuted.
+        let fraction = best_move_nodes as f64 / nodes.max(1) as f64;
+        let node_fraction = (factor(params.node_fraction_base_percent) - fraction)
+            * factor(params.node_fraction_scale_percent);
+
+        let limit = self
+            .soft
+            .mul_f64(stability * score_drop * node_fraction)
+            .min(self.hard);
+
+        // The next iteration takes several times as long as this one, so
+        // don't start one which is unlikely to finish in time.
+        if elapsed.mul_f64(factor(params.iter_growth_percent)) >= limit {
+            TimeAction::YieldResult
+        } else {
+            TimeAction::Iterate
+        }
+    }
+}
 
-        let time_left = self
-            .time_left
-            .map(|x| x.mul_f32(percent_time_to_use))
-            .unwrap_or(Duration::from_secs(5));
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
 
-        if depth < MIN_EARLY_YIELD_DEPTH {
-            return TimeAction::Iterate(time_left);
-        }
+    use crate::{mmove::MoveBuilder, piece::mkp, position::locus::loc};
+
+    use super::{SearchParams, TimeAction, TimeMan};
 
-        let score_stats =
-            self.scores
-                .iter()
-                .rev()
-                .take(MIN_EARLY_YIELD_DEPTH)
-                .fold(Stats::new(), |mut s, x| {
-                    s.add_value(*x);
-                    s
-                });
-
-        if score_stats.range() < 20 {
-            return TimeAction::YieldResult;
+    fn with_clock(time_left: u64, increment: u64, moves_to_go: Option<u32>) -> TimeMan {
+        TimeMan {
+            time_left: Some(Duration::from_millis(time_left)),
+            increment: Some(Duration::from_millis(increment)),
+            moves_to_go,
+            ..TimeMan::new()
         }
+    }
+
+    #[test]
+    fn limits() {
+        let params = SearchParams::default();
+        let mut tm = with_clock(60_000, 0, None);
+        let hard = tm.init(&params);
+
+        assert!(tm.soft < hard && hard <= Duration::from_secs(30));
+
+        // An increment is spent as it comes.
+        let mut inc = with_clock(60_000, 1000, None);
+        inc.init(&params);
+        assert!(inc.soft > tm.soft);
+
+        // The last move before the time control can use most of the clock.
+        let mut last = with_clock(10_000, 0, Some(1));
+        assert!(last.init(&params) >= Duration::from_secs(7));
+
+        // The hard limit never exceeds what's left, even with a large
+        // increment and little time.
+        let mut low = with_clock(100, 5000, None);
+        assert!(low.init(&params) < Duration::from_millis(100));
+    }
 
-        if self
-            .best_moves
-            .iter()
-            .rev()
-            .take(MIN_EARLY_YIELD_DEPTH)
-            .all_equal()
-        {
-            return TimeAction::YieldResult;
+    #[test]
+    fn stability() {
+        let params = SearchParams::default();
+        let m = MoveBuilder::new(mkp!(White, Pawn), loc!(e 2))
+            .with_dst(loc!(e 4))
+            .build();
+        let mut tm = with_clock(60_000, 0, None);
+        tm.init(&params);
+        let soft = tm.soft;
+
+        // An unstable best move with an even spread of nodes runs past the
+        // soft limit, but the same move found repeatedly stops before it.
+        assert!(matches!(
+            tm.iter_complete(&params, 0, m, 10, 100, soft),
+            TimeAction::Iterate
+        ));
+
+        for _ in 0..6 {
+            tm.iter_complete(&params, 0, m, 90, 100, Duration::ZERO);
         }
 
-        TimeAction::Iterate(time_left)
+        assert!(matches!(
+            tm.iter_complete(&params, 0, m, 90, 100, soft.mul_f64(0.8)),
+            TimeAction::YieldResult
+        ));
     }
 }
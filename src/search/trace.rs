@@ -0,0 +1,272 @@
+//! A record of the search tree, with the window each node was searched with
+//! and what happened there, to show why a move was chosen or passed over.
+
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{mmove::Move, parsers::uci_move::UciMove};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NodeKind {
+    Search,
+    Quiescence,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TraceEvent {
+    /// The transposition table gave the score, or a bound outside the window.
+    TtHit,
+    /// A draw by repetition or the fifty move rule.
+    Draw,
+    Checkmate,
+    Stalemate,
+    /// The static evaluation, which the side to move can stand pat on.
+    StandPat(i32),
+    /// A move scored at least beta, so the rest weren't searched.
+    Cutoff(Move),
+    /// A move beat alpha with a null window, so is searched again with the
+    /// full one.
+    ReSearch(Move),
+    /// The search was stopped, so the score means nothing.
+    Aborted,
+}
+
+#[derive(Clone, Debug)]
+pub struct TraceNode {
+    pub kind: NodeKind,
+    /// The move leading here, which the roots don't have.
+    pub mmove: Option<Move>,
+    pub ply: usize,
+    pub depth: u32,
+    pub alpha: i32,
+    pub beta: i32,
+    pub score: i32,
+    pub events: Vec<TraceEvent>,
+    /// The indices of the nodes searched from this one, in order.
+    pub children: Vec<usize>,
+}
+
+/// The tree searched by one iteration, down to `max_ply`. Each MultiPV line
+/// has its own root.
+#[derive(Clone, Default, Debug)]
+pub struct SearchTrace {
+    max_ply: usize,
+    pub nodes: Vec<TraceNode>,
+    pub roots: Vec<usize>,
+    // The nodes entered but not yet left, deepest last.
+    open: Vec<usize>,
+}
+
+impl SearchTrace {
+    pub fn new(max_ply: usize) -> Self {
+        Self {
+            max_ply,
+            ..Default::default()
+        }
+    }
+
+    /// Take the tree recorded so far, leaving this one empty.
+    pub fn take(&mut self) -> Self {
+        std::mem::replace(self, Self::new(self.max_ply))
+    }
+
+    /// Start recording a node, returning whether it's shallow enough to be
+    /// recorded, in which case it must be left with `exit`.
+    pub fn enter(
+        &mut self,
+        kind: NodeKind,
+        mmove: Option<Move>,
+        ply: usize,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+    ) -> bool {
+        if ply > self.max_ply {
+            return false;
+        }
+
+        let idx = self.nodes.len();
+        self.nodes.push(TraceNode {
+            kind,
+            mmove,
+            ply,
+            depth,
+            alpha,
+            beta,
+            score: 0,
+            events: Vec::new(),
+            children: Vec::new(),
+        });
+
+        match self.open.last() {
+            Some(&parent) => self.nodes[parent].children.push(idx),
+            None => self.roots.push(idx),
+        }
+
+        self.open.push(idx);
+        true
+    }
+
+    pub fn exit(&mut self, score: i32) {
+        let idx = self.open.pop().unwrap();
+        self.nodes[idx].score = score;
+    }
+
+    /// Note `event` against the node being searched at `ply`.
+    pub fn event(&mut self, ply: usize, event: TraceEvent) {
+        if ply <= self.max_ply {
+            if let Some(&idx) = self.open.last() {
+                self.nodes[idx].events.push(event);
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut ret = String::new();
+        self.write_json_list(&mut ret, &self.roots).unwrap();
+        ret.push('\n');
+        ret
+    }
+
+    fn write_json_list(&self, f: &mut String, nodes: &[usize]) -> fmt::Result {
+        f.push('[');
+
+        for (n, idx) in nodes.iter().enumerate() {
+            if n > 0 {
+                f.push(',');
+            }
+
+            let node = &self.nodes[*idx];
+            f.push('{');
+
+            if let Some(m) = node.mmove {
+                write!(f, "\"move\":\"{}\",", UciMove::from(m))?;
+            }
+
+            let events = node
+                .events
+                .iter()
+                .map(|x| format!("\"{x}\""))
+                .collect::<Vec<_>>()
+                .join(",");
+
+            write!(
+                f,
+                "\"kind\":\"{}\",\"ply\":{},\"depth\":{},\"alpha\":{},\"beta\":{},\"score\":{},\"events\":[{events}],\"children\":",
+                node.kind, node.ply, node.depth, node.alpha, node.beta, node.score
+            )?;
+
+            self.write_json_list(f, &node.children)?;
+            f.push('}');
+        }
+
+        f.push(']');
+        Ok(())
+    }
+
+    /// The tree in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("digraph search {\n    node [shape=box];\n");
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let mut label = match node.mmove {
+                Some(m) => format!("{}\\n", UciMove::from(m)),
+                None => String::from("root\\n"),
+            };
+
+            label += &format!(
+                "{} d{} [{}, {}]\\nscore {}",
+                node.kind, node.depth, node.alpha, node.beta, node.score
+            );
+
+            for event in node.events.iter() {
+                label += &format!("\\n{event}");
+            }
+
+            ret += &format!("    n{idx} [label=\"{label}\"];\n");
+
+            for child in node.children.iter() {
+                ret += &format!("    n{idx} -> n{child};\n");
+            }
+        }
+
+        ret += "}\n";
+        ret
+    }
+
+    /// Write the tree to `path`, in the DOT language if it ends in `.dot` or
+    /// `.gv`, or as JSON otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = match path.extension().and_then(|x| x.to_str()) {
+            Some("dot" | "gv") => self.to_dot(),
+            _ => self.to_json(),
+        };
+
+        fs::write(path, contents).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeKind::Search => write!(f, "search"),
+            NodeKind::Quiescence => write!(f, "quiescence"),
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::TtHit => write!(f, "tt hit"),
+            TraceEvent::Draw => write!(f, "draw"),
+            TraceEvent::Checkmate => write!(f, "checkmate"),
+            TraceEvent::Stalemate => write!(f, "stalemate"),
+            TraceEvent::StandPat(x) => write!(f, "stand pat {x}"),
+            TraceEvent::Cutoff(m) => write!(f, "cutoff {}", UciMove::from(*m)),
+            TraceEvent::ReSearch(m) => write!(f, "re-search {}", UciMove::from(*m)),
+            TraceEvent::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mmove::MoveBuilder, piece::mkp, position::locus::loc};
+
+    use super::{NodeKind, SearchTrace, TraceEvent};
+
+    #[test]
+    fn record() {
+        let m = MoveBuilder::new(mkp!(White, Pawn), loc!(e 2))
+            .with_dst(loc!(e 4))
+            .build();
+        let mut trace = SearchTrace::new(1);
+
+        assert!(trace.enter(NodeKind::Search, None, 0, 2, -100, 100));
+        assert!(trace.enter(NodeKind::Search, Some(m), 1, 1, -100, 100));
+
+        // Nodes past the deepest ply traced are left out.
+        assert!(!trace.enter(NodeKind::Quiescence, Some(m), 2, 0, -100, 100));
+        trace.event(2, TraceEvent::StandPat(5));
+
+        trace.event(1, TraceEvent::Cutoff(m));
+        trace.exit(-20);
+        trace.exit(20);
+
+        assert_eq!(
+            trace.to_json(),
+            "[{\"kind\":\"search\",\"ply\":0,\"depth\":2,\"alpha\":-100,\"beta\":100,\"score\":20,\"events\":[],\"children\":\
+             [{\"move\":\"e2e4\",\"kind\":\"search\",\"ply\":1,\"depth\":1,\"alpha\":-100,\"beta\":100,\"score\":-20,\"events\":[\"cutoff e2e4\"],\"children\":[]}]}]\n"
+        );
+        assert!(trace.to_dot().contains("n0 -> n1;"));
+
+        assert_eq!(trace.take().nodes.len(), 2);
+        assert!(trace.nodes.is_empty());
+    }
+}
@@ -1,24 +1,19 @@
-use std::collections::HashMap;
+use crate::{mmove::PackedMove, position::zobrist::ZobristKey};
 
-use crate::{mmove::Move, position::zobrist::ZobristKey};
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum EntryKind {
-    Score(Move),
+    Score(PackedMove),
     Alpha,
     Beta,
 }
 
 impl EntryKind {
     pub fn is_score(&self) -> bool {
-        match self {
-            EntryKind::Score(_) => true,
-            _ => false,
-        }
+        matches!(self, EntryKind::Score(_))
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct TEntry {
     pub hash: ZobristKey,
     pub depth: u32,
@@ -26,26 +21,218 @@ pub struct TEntry {
     pub eval: i32,
 }
 
-const TABLE_SZ_MB: usize = 256;
-const ENTRIES: usize = TABLE_SZ_MB * 1024 * 1024 / std::mem::size_of::<TEntry>();
+pub const DEFAULT_SIZE_MB: usize = 256;
+
+// The entries that a position may be stored in, which together fill a cache
+// line.
+const BUCKET_SIZE: usize = 4;
+
+// An entry is its hash and its data packed into a word: the evaluation in the
+// low 32 bits, then 8 bits of depth, 2 of kind, 16 of move and 6 of the
+// generation of the search that stored it. A kind of zero marks an empty
+// entry, so a zeroed table is an empty one.
+type Slot = [u64; 2];
+type Bucket = [Slot; BUCKET_SIZE];
+
+const KIND_SCORE: u64 = 1;
+const KIND_ALPHA: u64 = 2;
+const KIND_BETA: u64 = 3;
+
+const GENERATIONS: u8 = 64;
+
+// How many plies of depth an entry is worth per search that it's older than
+// the current one, when choosing which entry of a bucket to replace.
+const AGE_WEIGHT: i32 = 8;
+
+fn pack(entry: &TEntry, generation: u8) -> u64 {
+    let (kind, mmove) = match entry.kind {
+        EntryKind::Score(m) => (KIND_SCORE, m.to_bits()),
+        EntryKind::Alpha => (KIND_ALPHA, 0),
+        EntryKind::Beta => (KIND_BETA, 0),
+    };
+
+    entry.eval as u32 as u64
+        | (entry.depth.min(u8::MAX as u32) as u64) << 32
+        | kind << 40
+        | (mmove as u64) << 42
+        | (generation as u64) << 58
+}
+
+fn unpack([hash, data]: Slot) -> TEntry {
+    TEntry {
+        hash,
+        depth: (data >> 32) as u8 as u32,
+        kind: match data >> 40 & 3 {
+            KIND_SCORE => EntryKind::Score(PackedMove::from_bits((data >> 42) as u16)),
+            KIND_ALPHA => EntryKind::Alpha,
+            _ => EntryKind::Beta,
+        },
+        eval: data as u32 as i32,
+    }
+}
+
+fn is_empty([_, data]: Slot) -> bool {
+    data >> 40 & 3 == 0
+}
+
+fn generation([_, data]: Slot) -> u8 {
+    (data >> 58) as u8
+}
 
+/// A fixed size table of the results of searching positions. Each position
+/// has a bucket of a few entries it can go in, and when they're all taken,
+/// the shallowest is replaced, counting those from earlier searches as
+/// shallower the older they are.
 #[derive(Clone)]
 pub struct TTable {
-    table: HashMap<ZobristKey, TEntry>,
+    table: Vec<Bucket>,
+    size_mb: usize,
+    generation: u8,
+    /// The score of a draw for white in the searches that filled the table.
+    draw_score: i32,
+}
+
+impl Default for TTable {
+    fn default() -> Self {
+        Self::with_size_mb(0)
+    }
 }
 
 impl TTable {
-    pub fn new() -> Self {
+    /// A table holding as many entries as fit in `mb` megabytes.
+    pub fn with_size_mb(mb: usize) -> Self {
+        let buckets = (mb * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+
         Self {
-            table: HashMap::with_capacity(ENTRIES),
+            // A zeroed allocation, which the OS provides lazily, so a large
+            // table costs little until it's used.
+            table: vec![[[0; 2]; BUCKET_SIZE]; buckets],
+            size_mb: mb,
+            generation: 0,
+            draw_score: 0,
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    /// Start a new search, whose entries are preferred over those of
+    /// earlier ones.
+    pub fn new_search(&mut self) {
+        self.generation = (self.generation + 1) % GENERATIONS;
+    }
+
+    /// Prepare for a search in which a draw scores `draw_score` for white.
+    /// Scores from searches with a different one, due to contempt, are wrong
+    /// for this one, so they're cleared.
+    pub fn set_draw_score(&mut self, draw_score: i32) {
+        if draw_score != self.draw_score {
+            self.clear();
+            self.draw_score = draw_score;
         }
     }
 
-    pub fn lookup(&self, hash: ZobristKey) -> Option<&TEntry> {
-        self.table.get(&hash)
+    fn bucket(&self, hash: ZobristKey) -> usize {
+        (hash % self.table.len() as u64) as usize
+    }
+
+    pub fn lookup(&self, hash: ZobristKey) -> Option<TEntry> {
+        self.table[self.bucket(hash)]
+            .iter()
+            .find(|x| x[0] == hash && !is_empty(**x))
+            .map(|x| unpack(*x))
+    }
+
+    // How much an entry is worth keeping.
+    fn worth(&self, slot: Slot) -> i32 {
+        let age = (self.generation + GENERATIONS - generation(slot)) % GENERATIONS;
+
+        unpack(slot).depth as i32 - age as i32 * AGE_WEIGHT
     }
 
+    /// Store `entry`, over any entry for the same position, or else over the
+    /// least valuable entry of its bucket.
     pub fn insert(&mut self, entry: TEntry) {
-        self.table.insert(entry.hash, entry);
+        let idx = self.bucket(entry.hash);
+        let bucket = &self.table[idx];
+
+        let slot = bucket
+            .iter()
+            .position(|x| x[0] == entry.hash && !is_empty(*x))
+            .or_else(|| bucket.iter().position(|x| is_empty(*x)))
+            .unwrap_or_else(|| {
+                (0..BUCKET_SIZE)
+                    .min_by_key(|x| self.worth(bucket[*x]))
+                    .unwrap()
+            });
+
+        self.table[idx][slot] = [entry.hash, pack(&entry, self.generation)];
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::with_size_mb(self.size_mb);
+    }
+
+    /// How full the table is, in permille, from a sample of its entries
+    /// stored by the current search.
+    pub fn hashfull(&self) -> usize {
+        let sample = self.table.iter().flatten().take(1000);
+        let total = sample.clone().count();
+
+        sample
+            .filter(|x| !is_empty(**x) && generation(**x) == self.generation)
+            .count()
+            * 1000
+            / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntryKind, TEntry, TTable};
+
+    #[test]
+    fn replacement() {
+        let mut ttable = TTable::with_size_mb(1);
+        let entry = |hash, depth| TEntry {
+            hash,
+            depth,
+            kind: EntryKind::Alpha,
+            eval: 0,
+        };
+
+        // Positions this far apart share a bucket.
+        let stride = 1024 * 1024 / 64;
+
+        for n in 0..4 {
+            ttable.insert(entry(n * stride, 10 - n as u32));
+        }
+
+        // A full bucket still takes new positions, in place of the
+        // shallowest entry, and updates the positions it holds.
+        ttable.insert(entry(4 * stride, 20));
+        assert!(ttable.lookup(4 * stride).is_some());
+        assert!(ttable.lookup(3 * stride).is_none());
+
+        ttable.insert(TEntry {
+            eval: 5,
+            ..entry(0, 1)
+        });
+        assert_eq!(ttable.lookup(0).unwrap().eval, 5);
+
+        // The deep entries of an earlier search give way to shallow ones.
+        ttable.new_search();
+        ttable.new_search();
+        ttable.new_search();
+        for n in 5..9 {
+            ttable.insert(entry(n * stride, 2));
+        }
+        assert!((5..9).all(|n| ttable.lookup(n * stride).is_some()));
+        assert!(ttable.lookup(4 * stride).is_none());
+
+        assert_eq!(ttable.hashfull(), 4);
+        ttable.clear();
+        assert!(ttable.lookup(5 * stride).is_none());
     }
 }
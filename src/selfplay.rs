@@ -0,0 +1,465 @@
+//! Play games between two engines, either of which may be rmace itself or
+//! another engine spoken to over UCI, to measure the strength of a change.
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use nom::Finish;
+
+use crate::{
+    game::Game,
+    mmove::Move,
+    parsers::{pgn::PgnGame, san::parse_san, uci_move::UciMove},
+    piece::Colour,
+    position::Position,
+};
+
+pub mod player;
+pub mod spsa;
+pub mod stats;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The score players report when they have found a forced mate.
+pub const MATE_SCORE: i32 = 32000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub white: Duration,
+    pub black: Duration,
+    pub increment: Duration,
+}
+
+impl Clock {
+    fn remaining(&mut self, colour: Colour) -> &mut Duration {
+        match colour {
+            Colour::White => &mut self.white,
+            Colour::Black => &mut self.black,
+        }
+    }
+}
+
+/// A base time per game plus an increment per move, written `base+inc` in
+/// seconds, e.g. `10+0.1`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    fn clock(&self) -> Clock {
+        Clock {
+            white: self.base,
+            black: self.base,
+            increment: self.increment,
+        }
+    }
+}
+
+impl std::str::FromStr for TimeControl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (base, inc) = s.split_once('+').unwrap_or((s, "0"));
+        let secs = |x: &str| -> Result<Duration> {
+            Duration::try_from_secs_f64(x.parse()?)
+                .with_context(|| format!("Invalid time control {s}"))
+        };
+
+        Ok(Self {
+            base: secs(base)?,
+            increment: secs(inc)?,
+        })
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.base.as_secs_f64(),
+            self.increment.as_secs_f64()
+        )
+    }
+}
+
+/// What a player chose to play, and what it thinks of the position.
+pub struct Reply {
+    pub mmove: UciMove,
+    /// The score in centipawns from the mover's point of view, or
+    /// ±`MATE_SCORE` for a forced mate.
+    pub score: Option<i32>,
+}
+
+pub trait Player {
+    fn name(&self) -> &str;
+
+    /// Prepare for a game with no connection to the previous one.
+    fn new_game(&mut self) -> Result<()>;
+
+    /// Choose a move in the position reached by playing `moves` from `fen`.
+    fn go(&mut self, fen: &str, moves: &[UciMove], clock: &Clock) -> Result<Reply>;
+}
+
+/// When to end a game early, rather than playing it out.
+#[derive(Clone, Copy, Debug)]
+pub struct Adjudication {
+    /// A player resigns once its score has been at or below minus this for
+    /// `resign_moves` moves in a row.
+    pub resign_score: i32,
+    pub resign_moves: usize,
+    /// The game is drawn once both players' scores have been within this of
+    /// zero for `draw_moves` moves each, after `draw_min_ply`.
+    pub draw_score: i32,
+    pub draw_moves: usize,
+    pub draw_min_ply: usize,
+    /// The game is drawn once this many plies have been played.
+    pub max_plies: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            resign_score: 1000,
+            resign_moves: 4,
+            draw_score: 10,
+            draw_moves: 8,
+            draw_min_ply: 80,
+            max_plies: 400,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Win(Colour),
+    Draw,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Win(Colour::White) => write!(f, "1-0"),
+            Outcome::Win(Colour::Black) => write!(f, "0-1"),
+            Outcome::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+/// A finished game.
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub fen: String,
+    /// The moves of the game, including those of the opening, in SAN.
+    pub moves: Vec<String>,
+    pub opening_plies: usize,
+    pub outcome: Outcome,
+    pub reason: String,
+    pub time_control: TimeControl,
+}
+
+impl GameRecord {
+    pub fn to_pgn(&self, round: usize) -> String {
+        let mut ret = String::new();
+        let mut tags = vec![
+            ("Event", "rmace match".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", "????.??.??".to_string()),
+            ("Round", round.to_string()),
+            ("White", self.white.clone()),
+            ("Black", self.black.clone()),
+            ("Result", self.outcome.to_string()),
+        ];
+
+        if self.fen != START_FEN {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", self.fen.clone()));
+        }
+
+        tags.push(("TimeControl", self.time_control.to_string()));
+        tags.push(("PlyCount", self.moves.len().to_string()));
+        tags.push(("Termination", self.reason.clone()));
+
+        for (name, value) in tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            ret.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+
+        ret.push('\n');
+
+        let mut fields = self.fen.split_whitespace().skip(1);
+        let mut black = fields.next() == Some("b");
+        let mut number: usize = fields.nth(3).and_then(|x| x.parse().ok()).unwrap_or(1);
+        let mut words = Vec::new();
+
+        if black {
+            words.push(format!("{number}..."));
+        }
+
+        for m in self.moves.iter() {
+            if !black {
+                words.push(format!("{number}."));
+            } else {
+                number += 1;
+            }
+
+            words.push(m.clone());
+            black = !black;
+        }
+
+        words.push(format!("{{{}}}", self.reason));
+        words.push(self.outcome.to_string());
+
+        // Wrap the movetext at 80 columns.
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + word.len() >= 80 {
+                ret.push_str(&line);
+                ret.push('\n');
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+
+        ret.push_str(&line);
+        ret.push_str("\n\n");
+        ret
+    }
+}
+
+/// The starting position and moves of an opening, from a PGN game.
+#[derive(Clone)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<Move>,
+}
+
+impl TryFrom<&PgnGame> for Opening {
+    type Error = anyhow::Error;
+
+    fn try_from(game: &PgnGame) -> Result<Self> {
+        let fen = game.tag("FEN").unwrap_or(START_FEN).to_string();
+        let mut pos = Position::from_fen(&fen)?;
+        let mut moves = Vec::new();
+
+        for san in game.moves.iter() {
+            let m = parse_san(san)
+                .finish()
+                .ok()
+                .and_then(|(_, x)| x.to_move(&mut pos))
+                .ok_or_else(|| anyhow!("Illegal opening move {san}"))?;
+
+            pos.make_move(m).consume();
+            moves.push(m);
+        }
+
+        Ok(Self { fen, moves })
+    }
+}
+
+// Scores of the last `n` moves of `colour`, if it has made that many.
+fn last_scores(scores: &[(Colour, Option<i32>)], colour: Colour, n: usize) -> Option<Vec<i32>> {
+    let ret: Vec<_> = scores
+        .iter()
+        .rev()
+        .filter(|(c, _)| *c == colour)
+        .take(n)
+        .map_while(|(_, s)| *s)
+        .collect();
+
+    (ret.len() == n).then_some(ret)
+}
+
+/// Play a game from `opening` between `white` and `black`.
+pub fn play_game(
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    opening: &Opening,
+    tc: TimeControl,
+    adjudication: &Adjudication,
+) -> Result<GameRecord> {
+    let mut game = Game::new(Position::from_fen(&opening.fen)?);
+    let mut clock = tc.clock();
+    let mut history = Vec::new();
+    let mut scores = Vec::new();
+
+    white.new_game()?;
+    black.new_game()?;
+
+    for m in opening.moves.iter() {
+        history.push(UciMove::from_move(*m, game.position()));
+        game.make_move(*m)?;
+    }
+
+    let (outcome, reason) = loop {
+        let mover = game.position().to_play();
+
+        if let Some(t) = game.termination() {
+            break (
+                t.winner().map_or(Outcome::Draw, Outcome::Win),
+                t.to_string(),
+            );
+        }
+
+        if history.len() >= adjudication.max_plies {
+            break (Outcome::Draw, "maximum game length".to_string());
+        }
+
+        let start = Instant::now();
+        let reply = match mover {
+            Colour::White => white.go(&opening.fen, &history, &clock)?,
+            Colour::Black => black.go(&opening.fen, &history, &clock)?,
+        };
+        let increment = clock.increment;
+        let remaining = clock.remaining(mover);
+
+        match remaining.checked_sub(start.elapsed()) {
+            Some(x) => *remaining = x + increment,
+            None => break (Outcome::Win(mover.next()), "time forfeit".to_string()),
+        }
+
+        if game.make_uci_move(reply.mmove).is_err() {
+            break (
+                Outcome::Win(mover.next()),
+                format!("illegal move {}", reply.mmove),
+            );
+        }
+
+        history.push(reply.mmove);
+        scores.push((mover, reply.score));
+
+        if let Some(s) = last_scores(&scores, mover, adjudication.resign_moves) {
+            if s.iter().all(|x| *x <= -adjudication.resign_score) {
+                break (Outcome::Win(mover.next()), "resignation".to_string());
+            }
+        }
+
+        if history.len() >= adjudication.draw_min_ply {
+            let drawish = |colour| {
+                last_scores(&scores, colour, adjudication.draw_moves)
+                    .is_some_and(|s| s.iter().all(|x| x.abs() <= adjudication.draw_score))
+            };
+
+            if drawish(Colour::White) && drawish(Colour::Black) {
+                break (Outcome::Draw, "adjudicated draw".to_string());
+            }
+        }
+    };
+
+    Ok(GameRecord {
+        white: white.name().to_string(),
+        black: black.name().to_string(),
+        fen: opening.fen.clone(),
+        moves: game.to_san(),
+        opening_plies: opening.moves.len(),
+        outcome,
+        reason,
+        time_control: tc,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        parsers::pgn::parse_pgn,
+        piece::Colour,
+        selfplay::{GameRecord, Opening, Outcome, TimeControl},
+    };
+
+    use super::{play_game, player::InProcessPlayer, Adjudication};
+
+    #[test]
+    fn time_control() {
+        assert_eq!(
+            "10+0.1".parse::<TimeControl>().unwrap(),
+            TimeControl {
+                base: Duration::from_secs(10),
+                increment: Duration::from_millis(100),
+            }
+        );
+        assert_eq!(
+            "60".parse::<TimeControl>().unwrap().increment,
+            Duration::ZERO
+        );
+        assert!("ten".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn finds_mate() {
+        let opening = Opening {
+            fen: "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1".to_string(),
+            moves: vec![],
+        };
+
+        let game = play_game(
+            &mut InProcessPlayer::with_depth("a", 3),
+            &mut InProcessPlayer::with_depth("b", 3),
+            &opening,
+            "10+0".parse().unwrap(),
+            &Adjudication::default(),
+        )
+        .unwrap();
+
+        assert_eq!(game.outcome, Outcome::Win(Colour::White));
+        assert_eq!(game.reason, "checkmate");
+        assert_eq!(game.moves, ["Rd8#"]);
+    }
+
+    #[test]
+    fn plays_opening() {
+        let pgn = parse_pgn("1. e4 e5 2. Nf3 Nc6 *").unwrap();
+        let opening = Opening::try_from(&pgn[0]).unwrap();
+
+        let game = play_game(
+            &mut InProcessPlayer::with_depth("a", 2),
+            &mut InProcessPlayer::with_depth("b", 2),
+            &opening,
+            "10+0".parse().unwrap(),
+            &Adjudication {
+                max_plies: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(game.moves.len(), 10);
+        assert_eq!(game.moves[..4], ["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.outcome, Outcome::Draw);
+        assert_eq!(game.reason, "maximum game length");
+
+        let pgn = game.to_pgn(1);
+        assert!(pgn.contains("[Result \"1/2-1/2\"]"));
+        assert!(pgn.contains("1. e4 e5 2. Nf3 Nc6 3."));
+        assert!(pgn.trim_end().ends_with("{maximum game length} 1/2-1/2"));
+    }
+
+    #[test]
+    fn pgn_from_black() {
+        let game = GameRecord {
+            white: "a".to_string(),
+            black: "b".to_string(),
+            fen: "4k3/8/8/8/8/8/8/4K2R b K - 0 12".to_string(),
+            moves: vec!["Kd7".to_string(), "O-O".to_string()],
+            opening_plies: 0,
+            outcome: Outcome::Draw,
+            reason: "adjudicated draw".to_string(),
+            time_control: "1+0".parse().unwrap(),
+        };
+
+        let pgn = game.to_pgn(3);
+        assert!(pgn.contains("[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 0 12\"]"));
+        assert!(pgn.contains("12... Kd7 13. O-O {adjudicated draw} 1/2-1/2"));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nom::Finish;
+
+use crate::{
+    game::Game,
+    parsers::uci_move::{parse_uci_move, UciMove},
+    piece::Colour,
+    position::{movegen::MoveGen, Position},
+    search::{SearchBuilder, SearchParams, TTable, DEFAULT_HASH_MB, MATE},
+};
+
+use super::{Clock, Player, Reply, MATE_SCORE};
+
+/// Plays with rmace's own search, in this process.
+pub struct InProcessPlayer {
+    name: String,
+    depth: Option<usize>,
+    move_time: Option<Duration>,
+    params: SearchParams,
+    hash_mb: usize,
+    /// The table kept from one move of a game to the next.
+    ttable: TTable,
+}
+
+impl InProcessPlayer {
+    /// Manage the time on the clock, as in a normal game.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            depth: None,
+            move_time: None,
+            params: SearchParams::default(),
+            hash_mb: DEFAULT_HASH_MB,
+            ttable: TTable::default(),
+        }
+    }
+
+    /// Search every move to `depth`, regardless of the clock.
+    pub fn with_depth(name: &str, depth: usize) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::new(name)
+        }
+    }
+
+    /// Search every move for `move_time`, regardless of the clock.
+    pub fn with_move_time(name: &str, move_time: Duration) -> Self {
+        Self {
+            move_time: Some(move_time),
+            ..Self::new(name)
+        }
+    }
+
+    /// Search with `params` rather than the defaults.
+    pub fn with_params(mut self, params: SearchParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Size the transposition table of each search to `mb` megabytes.
+    pub fn with_hash_size(mut self, mb: usize) -> Self {
+        self.hash_mb = mb;
+        self
+    }
+}
+
+impl Player for InProcessPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<()> {
+        self.ttable.clear();
+        Ok(())
+    }
+
+    fn go(&mut self, fen: &str, moves: &[UciMove], clock: &Clock) -> Result<Reply> {
+        // Played out through a game, which keeps the position's history no
+        // longer than needed, however many moves there are.
+        let mut game = Game::new(Position::from_fen(fen)?);
+
+        for m in moves {
+            game.make_uci_move(*m).context("Illegal move in game")?;
+        }
+
+        let mut pos = game.position().clone();
+
+        let mut search = SearchBuilder::new(pos.clone())
+            .with_params(self.params)
+            .with_hash_size(self.hash_mb)
+            .with_ttable(std::mem::take(&mut self.ttable));
+
+        search = if let Some(depth) = self.depth {
+            search.with_depth(depth)
+        } else if let Some(move_time) = self.move_time {
+            search.with_move_time(move_time)
+        } else {
+            let remaining = match pos.to_play() {
+                Colour::White => clock.white,
+                Colour::Black => clock.black,
+            };
+
+            search
+                .with_deadline(remaining)
+                .with_increment(clock.increment)
+        };
+
+        let mut search = search.build();
+        let results = search.go();
+        self.ttable = search.into_ttable();
+
+        // A search stopped before completing its first iteration has no PV.
+        let Some(m) = results
+            .pv
+            .first()
+            .copied()
+            .or_else(|| MoveGen::legal_moves(&mut pos).first().copied())
+        else {
+            bail!("No legal moves");
+        };
+
+        let score = match results.eval {
+            MATE => MATE_SCORE,
+            x if x == -MATE => -MATE_SCORE,
+            x => x,
+        };
+
+        Ok(Reply {
+            mmove: UciMove::from_move(m, &pos),
+            score: (!results.pv.is_empty()).then_some(score),
+        })
+    }
+}
+
+/// Plays with another engine, spoken to over UCI on its standard input and
+/// output.
+pub struct UciPlayer {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciPlayer {
+    /// Spawn the engine `cmd`, passing it `args`, and wait for it to finish
+    /// the UCI handshake.
+    pub fn spawn(name: &str, cmd: &str, args: &[&str]) -> Result<Self> {
+        let mut child = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not spawn engine {cmd}"))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut ret = Self {
+            name: name.to_string(),
+            child,
+            stdin,
+            stdout,
+        };
+
+        ret.send("uci")?;
+        ret.wait_for("uciok")?;
+
+        Ok(ret)
+    }
+
+    fn send(&mut self, cmd: &str) -> Result<()> {
+        writeln!(self.stdin, "{cmd}")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+
+        if self.stdout.read_line(&mut line)? == 0 {
+            bail!("Engine {} exited", self.name);
+        }
+
+        Ok(line.trim().to_string())
+    }
+
+    fn wait_for(&mut self, reply: &str) -> Result<()> {
+        while self.read_line()? != reply {}
+        Ok(())
+    }
+}
+
+// The score of an `info` line, if it has one.
+fn parse_info_score(line: &str) -> Option<i32> {
+    let mut words = line.split_whitespace();
+
+    if words.next() != Some("info") {
+        return None;
+    }
+
+    words.find(|x| *x == "score")?;
+
+    match (words.next()?, words.next()?.parse::<i32>().ok()?) {
+        ("cp", x) => Some(x),
+        ("mate", x) if x > 0 => Some(MATE_SCORE),
+        ("mate", _) => Some(-MATE_SCORE),
+        _ => None,
+    }
+}
+
+impl Player for UciPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<()> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok")
+    }
+
+    fn go(&mut self, fen: &str, moves: &[UciMove], clock: &Clock) -> Result<Reply> {
+        let mut cmd = format!("position fen {fen}");
+
+        if !moves.is_empty() {
+            cmd.push_str(" moves");
+            moves.iter().for_each(|m| cmd.push_str(&format!(" {m}")));
+        }
+
+        self.send(&cmd)?;
+        self.send(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            clock.white.as_millis(),
+            clock.black.as_millis(),
+            clock.increment.as_millis(),
+            clock.increment.as_millis()
+        ))?;
+
+        let mut score = None;
+
+        loop {
+            let line = self.read_line()?;
+
+            if let Some(x) = parse_info_score(&line) {
+                score = Some(x);
+            }
+
+            if let Some(m) = line.strip_prefix("bestmove ") {
+                let m = m.split_whitespace().next().unwrap_or_default();
+                let (_, mmove) = parse_uci_move(m)
+                    .finish()
+                    .map_err(|_| anyhow!("Engine {} sent invalid move {m}", self.name))?;
+
+                return Ok(Reply { mmove, score });
+            }
+        }
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+        }
+
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        parsers::uci_move::parse_uci_move,
+        selfplay::{Clock, Player},
+    };
+
+    use super::{parse_info_score, InProcessPlayer, MATE_SCORE};
+
+    #[test]
+    fn info_score() {
+        assert_eq!(
+            parse_info_score("info depth 5 pv e2e4 e7e5 score cp -31 nodes 1234"),
+            Some(-31)
+        );
+        assert_eq!(
+            parse_info_score("info depth 9 score mate -3 nodes 10"),
+            Some(-MATE_SCORE)
+        );
+        assert_eq!(parse_info_score("info string score cp"), None);
+        assert_eq!(parse_info_score("bestmove e2e4"), None);
+    }
+
+    #[test]
+    fn long_game() {
+        let mut player = InProcessPlayer::with_depth("rmace", 1).with_hash_size(1);
+        let moves = ["a1a2", "e8d8", "a2a1", "d8e8"]
+            .iter()
+            .map(|m| parse_uci_move(m).unwrap().1)
+            .cycle()
+            .take(600)
+            .collect::<Vec<_>>();
+        let clock = Clock {
+            white: Duration::from_secs(60),
+            black: Duration::from_secs(60),
+            increment: Duration::ZERO,
+        };
+
+        // More moves than the position's own history could hold.
+        player
+            .go("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", &moves, &clock)
+            .unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//! Tune the search parameters with simultaneous perturbation stochastic
+//! approximation: each iteration plays a match between two copies of the
+//! current parameters, one nudged up and the other down along a random
+//! direction, and moves the parameters towards the winner.
+
+use anyhow::Result;
+use rand::Rng;
+
+use super::stats::MatchScore;
+use crate::search::SearchParams;
+
+// The usual exponents of the decay of the step size and the perturbation.
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+
+pub struct Spsa {
+    /// The parameters' values, which may fall between integers.
+    theta: Vec<f64>,
+    iterations: usize,
+    iteration: usize,
+    perturbation: f64,
+    learning_rate: f64,
+}
+
+impl Spsa {
+    /// Start tuning from `params`, for a run of `iterations`.
+    pub fn new(params: SearchParams, iterations: usize) -> Self {
+        Self {
+            theta: SearchParams::SPECS
+                .iter()
+                .map(|x| params.get(x.name).unwrap() as f64)
+                .collect(),
+            iterations,
+            iteration: 0,
+            perturbation: 0.05,
+            learning_rate: 0.002,
+        }
+    }
+
+    /// Perturb each parameter by `fraction` of its range in the first
+    /// iteration, decaying slowly after that.
+    pub fn with_perturbation(mut self, fraction: f64) -> Self {
+        self.perturbation = fraction;
+        self
+    }
+
+    /// Scale the updates, relative to the square of the perturbation.
+    pub fn with_learning_rate(mut self, rate: f64) -> Self {
+        self.learning_rate = rate;
+        self
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// The current parameters, rounded and kept within their bounds.
+    pub fn params(&self) -> SearchParams {
+        self.to_params(&self.theta)
+    }
+
+    fn to_params(&self, theta: &[f64]) -> SearchParams {
+        let mut ret = SearchParams::default();
+
+        for (spec, x) in SearchParams::SPECS.iter().zip(theta) {
+            let x = (x.round() as i32).clamp(spec.min, spec.max);
+            ret.set(spec.name, x).unwrap();
+        }
+
+        ret
+    }
+
+    /// Run one iteration, calling `play` to score the parameters perturbed
+    /// upwards against those perturbed downwards.
+    pub fn step(
+        &mut self,
+        rng: &mut impl Rng,
+        play: impl FnOnce(&SearchParams, &SearchParams) -> Result<MatchScore>,
+    ) -> Result<()> {
+        let k = self.iteration as f64 + 1.0;
+        let stability = self.iterations as f64 / 10.0;
+
+        // The perturbation of each parameter, which is at least 1 so that the
+        // two sides differ once rounded.
+        let c: Vec<_> = SearchParams::SPECS
+            .iter()
+            .map(|x| (self.perturbation * (x.max - x.min) as f64).max(1.0))
+            .collect();
+        let ck: Vec<_> = c.iter().map(|x| (x / k.powf(GAMMA)).max(1.0)).collect();
+        let delta: Vec<f64> = (0..c.len())
+            .map(|_| if rng.gen() { 1.0 } else { -1.0 })
+            .collect();
+
+        let shifted = |sign: f64| -> Vec<f64> {
+            self.theta
+                .iter()
+                .zip(ck.iter().zip(&delta))
+                .map(|(t, (c, d))| t + sign * c * d)
+                .collect()
+        };
+
+        let score = play(
+            &self.to_params(&shifted(1.0)),
+            &self.to_params(&shifted(-1.0)),
+        )?;
+        let result = score.wins as f64 - score.losses as f64;
+
+        for (i, spec) in SearchParams::SPECS.iter().enumerate() {
+            let a = self.learning_rate * c[i].powi(2) / (stability + k).powf(ALPHA);
+            self.theta[i] = (self.theta[i] + a / ck[i] * result * delta[i])
+                .clamp(spec.min as f64, spec.max as f64);
+        }
+
+        self.iteration += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::Spsa;
+    use crate::{search::SearchParams, selfplay::stats::MatchScore};
+
+    #[test]
+    fn converges() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut spsa = Spsa::new(SearchParams::default(), 200).with_learning_rate(0.5);
+
+        // Whichever side is closer to 50 moves to go wins.
+        let target = 50;
+        for _ in 0..200 {
+            spsa.step(&mut rng, |plus, minus| {
+                let dist = |x: &SearchParams| (x.moves_to_go - target).abs();
+
+                Ok(match dist(plus).cmp(&dist(minus)) {
+                    Ordering::Less => MatchScore {
+                        wins: 1,
+                        ..Default::default()
+                    },
+                    Ordering::Greater => MatchScore {
+                        losses: 1,
+                        ..Default::default()
+                    },
+                    Ordering::Equal => MatchScore {
+                        draws: 1,
+                        ..Default::default()
+                    },
+                })
+            })
+            .unwrap();
+        }
+
+        assert_eq!(spsa.iteration(), 200);
+        assert!((spsa.params().moves_to_go - target).abs() <= 3);
+    }
+}
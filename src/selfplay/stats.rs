@@ -0,0 +1,293 @@
+//! Elo estimates and the sequential probability ratio test, from the results
+//! of a match.
+
+use std::fmt::Display;
+
+use super::Outcome;
+use crate::piece::Colour;
+
+// The quantile of the normal distribution for a 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Wins, draws and losses, from the first player's point of view.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    /// Add the outcome of a game, in which the first player had `colour`.
+    pub fn add(&mut self, outcome: Outcome, colour: Colour) {
+        match outcome {
+            Outcome::Draw => self.draws += 1,
+            Outcome::Win(c) if c == colour => self.wins += 1,
+            Outcome::Win(_) => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The average points per game.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // The average points and variance of the points of a single game, with
+    // `extra` games of each outcome added.
+    fn moments(&self, extra: f64) -> (f64, f64) {
+        let (w, d, l) = (
+            self.wins as f64 + extra,
+            self.draws as f64 + extra,
+            self.losses as f64 + extra,
+        );
+        let n = w + d + l;
+        let s = (w + d / 2.0) / n;
+
+        (
+            s,
+            (w * (1.0 - s).powi(2) + d * (0.5 - s).powi(2) + l * s.powi(2)) / n,
+        )
+    }
+
+    // The moments of the results. If every game had the same outcome, half a
+    // game of each outcome is added, so that the variance isn't zero.
+    fn regularised(&self) -> (f64, f64) {
+        let plain = self.moments(0.0);
+
+        if plain.1 > 0.0 {
+            plain
+        } else {
+            self.moments(0.5)
+        }
+    }
+
+    /// The estimated Elo difference between the players. A perfect or zero
+    /// score counts as if half a game had gone the other way, rather than
+    /// being infinite.
+    pub fn elo(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+
+        elo(self.clamped_score())
+    }
+
+    fn clamped_score(&self) -> f64 {
+        let margin = 0.5 / self.games() as f64;
+
+        self.score().clamp(margin, 1.0 - margin)
+    }
+
+    /// Half the width of the 95% confidence interval of `elo`.
+    pub fn elo_error(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+
+        let error = Z_95 * (self.regularised().1 / self.games() as f64).sqrt();
+        let low = (self.clamped_score() - error).clamp(1e-6, 1.0 - 1e-6);
+        let high = (self.clamped_score() + error).clamp(1e-6, 1.0 - 1e-6);
+
+        (elo(high) - elo(low)) / 2.0
+    }
+}
+
+impl Display for MatchScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} - {} - {} [{:.3}] {}",
+            self.wins,
+            self.losses,
+            self.draws,
+            self.score(),
+            self.games()
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprtVerdict {
+    /// The first player is no stronger than `elo0`.
+    H0,
+    /// The first player is at least `elo1` stronger.
+    H1,
+}
+
+/// A sequential probability ratio test of whether the first player is `elo0`
+/// or `elo1` stronger than the second, with false positive and negative rates
+/// of `alpha` and `beta`.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// The log likelihood ratio of the results, approximated as in the
+    /// generalised SPRT. A match where every game had the same outcome is
+    /// regularised with half a game of each, so that it can still reach a
+    /// verdict.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+
+        let (mean, variance) = score.regularised();
+
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        let n = score.games() as f64;
+
+        (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance / n)
+    }
+
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Which hypothesis has been accepted, if either.
+    pub fn verdict(&self, score: &MatchScore) -> Option<SprtVerdict> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+
+        if llr >= upper {
+            Some(SprtVerdict::H1)
+        } else if llr <= lower {
+            Some(SprtVerdict::H0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchScore, Sprt, SprtVerdict};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.01, "{a} != {b}");
+    }
+
+    #[test]
+    fn elo() {
+        let even = MatchScore {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        };
+        assert_close(even.elo(), 0.0);
+
+        let score = MatchScore {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        assert_close(score.score(), 0.7);
+        assert_close(score.elo(), 147.19);
+        assert_close(score.elo_error(), 66.01);
+    }
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+
+        let (lower, upper) = sprt.bounds();
+        assert_close(lower, -2.94);
+        assert_close(upper, 2.94);
+
+        let score = MatchScore {
+            wins: 1000,
+            draws: 1000,
+            losses: 800,
+        };
+        assert_close(sprt.llr(&score), 7.21);
+        assert_eq!(sprt.verdict(&score), Some(SprtVerdict::H1));
+
+        let score = MatchScore {
+            wins: 800,
+            draws: 1000,
+            losses: 1000,
+        };
+        assert_eq!(sprt.verdict(&score), Some(SprtVerdict::H0));
+
+        let score = MatchScore {
+            wins: 10,
+            draws: 10,
+            losses: 8,
+        };
+        assert_eq!(sprt.verdict(&score), None);
+        assert_eq!(sprt.llr(&MatchScore::default()), 0.0);
+    }
+
+    #[test]
+    fn one_sided() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+
+        // A match where one side never loses still reaches a verdict.
+        let score = MatchScore {
+            wins: 200,
+            draws: 100,
+            losses: 0,
+        };
+        assert!(sprt.llr(&score) > 0.0);
+        assert_eq!(sprt.verdict(&score), Some(SprtVerdict::H1));
+
+        let score = MatchScore {
+            wins: 0,
+            draws: 0,
+            losses: 300,
+        };
+        assert_eq!(sprt.verdict(&score), Some(SprtVerdict::H0));
+
+        let score = MatchScore {
+            wins: 1,
+            draws: 0,
+            losses: 0,
+        };
+        assert_eq!(sprt.verdict(&score), None);
+
+        // Perfect scores give a large but finite Elo.
+        let perfect = MatchScore {
+            wins: 10,
+            draws: 0,
+            losses: 0,
+        };
+        assert_close(perfect.elo(), 511.5);
+        assert!(perfect.elo_error().is_finite());
+
+        let zero = MatchScore {
+            losses: 10,
+            ..Default::default()
+        };
+        assert_close(zero.elo(), -511.5);
+        assert_eq!(MatchScore::default().elo(), 0.0);
+    }
+}